@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use omsim_rs::data::{Atom, HexIndex, HexMap};
+use omsim_rs::sim::collision::{self, Collider, ColliderType, Movement};
+
+// Benchmarks for HexMap against the std HashMap it replaced, and for collision checks on crowded boards.
+// Run with `cargo bench`.
+
+fn main(){
+    divan::main();
+}
+
+/// The hexes of a compact molecule of `n` atoms, spiralling out from the origin.
+fn molecule_hexes(n: usize) -> Vec<HexIndex>{
+    HexIndex::default().spiral(4).take(n).collect()
+}
+
+/// Positions to look up: every atom of the molecule, and as many hexes just outside it.
+fn probes(n: usize) -> Vec<HexIndex>{
+    let hexes = molecule_hexes(n);
+    hexes.iter().copied().chain(hexes.iter().map(|h| *h + HexIndex::new(5, 0))).collect()
+}
+
+#[divan::bench(args = [4, 12, 48])]
+fn build_hex_map(n: usize) -> HexMap<Atom>{
+    molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect()
+}
+
+#[divan::bench(args = [4, 12, 48])]
+fn build_hash_map(n: usize) -> HashMap<HexIndex, Atom>{
+    molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect()
+}
+
+#[divan::bench(args = [4, 12, 48])]
+fn lookup_hex_map(bencher: divan::Bencher, n: usize){
+    let map: HexMap<Atom> = molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect();
+    let probes = probes(n);
+    bencher.bench(|| probes.iter().filter(|p| map.contains_key(divan::black_box(p))).count());
+}
+
+#[divan::bench(args = [4, 12, 48])]
+fn lookup_hash_map(bencher: divan::Bencher, n: usize){
+    let map: HashMap<HexIndex, Atom> = molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect();
+    let probes = probes(n);
+    bencher.bench(|| probes.iter().filter(|p| map.contains_key(divan::black_box(p))).count());
+}
+
+/// Matching a molecule against a product, as outputs do: every atom of one must be in the other, with the same element.
+#[divan::bench(args = [4, 12, 48])]
+fn match_hex_map(bencher: divan::Bencher, n: usize){
+    let a: HexMap<Atom> = molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect();
+    let b = a.clone();
+    bencher.bench(|| divan::black_box(&a).iter().all(|(k, v)| b.get(k) == Some(v)));
+}
+
+#[divan::bench(args = [4, 12, 48])]
+fn match_hash_map(bencher: divan::Bencher, n: usize){
+    let a: HashMap<HexIndex, Atom> = molecule_hexes(n).into_iter().map(|h| (h, Atom::Salt)).collect();
+    let b = a.clone();
+    bencher.bench(|| divan::black_box(&a).iter().all(|(k, v)| b.get(k) == Some(v)));
+}
+
+/// A board of idle atoms, as waste piles up, with one arm base moving past them without touching.
+fn crowded_board(n: i32) -> Vec<Collider>{
+    let atoms = (0..n).map(|i| Collider{ ty: ColliderType::Atom, movement: Movement::Stay{ at: HexIndex::new(2 * (i % 40), 2 * (i / 40)) } });
+    atoms.chain([Collider{ ty: ColliderType::ArmBase, movement: Movement::Translate{ start: HexIndex::new(-3, 0), end: HexIndex::new(-2, 0) } }]).collect()
+}
+
+#[divan::bench(args = [10, 100, 1000])]
+fn collision(bencher: divan::Bencher, n: i32){
+    let colliders = crowded_board(n);
+    bencher.bench(|| collision::first_collision(divan::black_box(&colliders), 16));
+}
+
+/// Every pair of colliders compared at every step, as collisions were checked before.
+#[divan::bench(args = [10, 100, 1000])]
+fn collision_all_pairs(bencher: divan::Bencher, n: i32){
+    let colliders = crowded_board(n);
+    bencher.bench(|| {
+        for i in 0..=16{
+            let time = i as f32 / 16.0;
+            let objs: Vec<_> = divan::black_box(&colliders).iter().map(|c| (c.movement.pos_at(time), c.ty)).collect();
+            for (l, (lpos, lty)) in objs.iter().enumerate(){
+                for (r, (rpos, rty)) in objs.iter().enumerate(){
+                    if l != r && lty.radius_with(*rty).is_some_and(|radius| lpos.dist2(*rpos) < radius * radius){
+                        return Some(*lpos);
+                    }
+                }
+            }
+        }
+        None
+    });
+}
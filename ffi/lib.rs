@@ -0,0 +1,4 @@
+// The exported functions live in omsim-rs behind its `ffi` and `wasm` features; this crate only links them into a dynamic library.
+pub use omsim_rs::ffi;
+#[cfg(feature = "wasm")]
+pub use omsim_rs::wasm;
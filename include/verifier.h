@@ -0,0 +1,38 @@
+// C interface to omsim-rs, built as a dynamic library by the omsim-ffi crate in ffi/.
+// Matches the reference omsim's verifier.h, so callers of one can link against the other.
+
+#ifndef OMSIM_RS_VERIFIER_H
+#define OMSIM_RS_VERIFIER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Create a verifier from a puzzle file and a solution file. Parse errors are reported through verifier_error.
+void *verifier_create(const char *puzzle_filename, const char *solution_filename);
+// Create a verifier from the contents of a puzzle file and a solution file, which are copied.
+void *verifier_create_from_bytes(const char *puzzle_bytes, int puzzle_length, const char *solution_bytes, int solution_length);
+void verifier_destroy(void *verifier);
+
+// Set the number of cycles a solution may run for before simulation gives up.
+void verifier_set_cycle_limit(void *verifier, int cycle_limit);
+
+// The last error that occurred, or NULL. Owned by the verifier.
+const char *verifier_error(void *verifier);
+int verifier_error_cycle(void *verifier);
+int verifier_error_location_u(void *verifier);
+int verifier_error_location_v(void *verifier);
+void verifier_error_clear(void *verifier);
+
+// Supported metrics: "parsed cycles", "parsed cost", "parsed area", "parsed instructions",
+// "cycles", "cost", "area", "instructions", "height", "width*2", "overlap", "throughput cycles", "throughput outputs".
+// Returns -1 and sets an error on failure.
+int verifier_evaluate_metric(void *verifier, const char *metric);
+// Additionally supports "width" and "throughput". Returns -1 and sets an error on failure.
+double verifier_evaluate_approximate_metric(void *verifier, const char *metric);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
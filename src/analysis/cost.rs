@@ -0,0 +1,50 @@
+use crate::data::{PartCategory, PartType, Solution};
+
+/// A solution's cost split by part category, with the cost of every part.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CostBreakdown{
+    /// Total cost of arms, pistons, and Van Berlo's wheel.
+    pub arms: i32,
+    /// Total cost of glyphs.
+    pub glyphs: i32,
+    /// Total cost of track.
+    pub track: i32,
+    /// Total cost of inputs, outputs, and conduits.
+    pub io: i32,
+    /// The cost of each part, in solution order.
+    pub items: Vec<CostItem>
+}
+
+/// The cost of a single part.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CostItem{
+    /// Index of the part in the solution.
+    pub part_index: usize,
+    pub ty: PartType,
+    pub category: PartCategory,
+    pub cost: i32
+}
+
+impl CostBreakdown{
+    /// Total cost across every category.
+    pub fn total(&self) -> i32{
+        self.arms + self.glyphs + self.track + self.io
+    }
+}
+
+/// Split the cost of a solution into part categories.
+pub fn cost_breakdown(solution: &Solution) -> CostBreakdown{
+    let mut breakdown = CostBreakdown::default();
+    for (part_index, part) in solution.parts.iter().enumerate(){
+        let category = part.ty.category();
+        let cost = part.cost();
+        match category{
+            PartCategory::Arm => breakdown.arms += cost,
+            PartCategory::Glyph => breakdown.glyphs += cost,
+            PartCategory::Track => breakdown.track += cost,
+            PartCategory::Io => breakdown.io += cost
+        }
+        breakdown.items.push(CostItem{ part_index, ty: part.ty.clone(), category, cost });
+    }
+    breakdown
+}
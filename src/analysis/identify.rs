@@ -0,0 +1,95 @@
+use crate::data::{PartType, Puzzle, Solution};
+use crate::sim::SimOptions;
+use crate::verify::{verify_with_options, DEFAULT_CYCLE_LIMIT};
+
+/// Find which of the given puzzles a solution solves, returning its index.
+/// Puzzles whose reagents and products can't satisfy the solution's inputs and outputs are skipped without simulating.
+/// The puzzle named by the solution is tried first, so that it wins if several puzzles are solved.
+pub fn identify_puzzle(solution: &Solution, puzzles: &[Puzzle]) -> Option<usize>{
+    let mut candidates: Vec<usize> = (0..puzzles.len()).filter(|&i| fits_puzzle(solution, &puzzles[i])).collect();
+    candidates.sort_by_key(|&i| puzzles[i].name != solution.puzzle_name);
+    candidates.into_iter().find(|&i| solves(solution, &puzzles[i]))
+}
+
+/// Whether a solution's inputs and outputs refer to valid reagents and products of a puzzle, with an output for every product.
+pub fn fits_puzzle(solution: &Solution, puzzle: &Puzzle) -> bool{
+    if puzzle.clean_solution(solution).is_err(){
+        return false;
+    }
+    let mut has_output = vec![false; puzzle.products.len()];
+    for part in &solution.parts{
+        if part.ty == PartType::Output || part.ty == PartType::PolymerOutput{
+            has_output[part.index as usize] = true;
+        }
+    }
+    has_output.into_iter().all(|h| h)
+}
+
+/// How confident a puzzle match is, from most to least confident.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchConfidence{
+    /// The puzzle has the name given by the solution.
+    Named,
+    /// The solution was verified against the puzzle.
+    Verified,
+    /// The solution's inputs and outputs fit the puzzle, it uses every reagent, and it uses polymer outputs and conduits only where the puzzle has them.
+    Structural,
+    /// The solution's inputs and outputs refer to valid reagents and products, but it's otherwise inconsistent with the puzzle.
+    Partial
+}
+
+/// The puzzle that a solution most likely belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PuzzleMatch{
+    /// Index of the puzzle.
+    pub index: usize,
+    pub confidence: MatchConfidence,
+    /// The number of puzzles that matched with the same confidence, including this one.
+    /// Anything above 1 means the match is ambiguous, and the first such puzzle was picked.
+    pub candidates: usize
+}
+
+/// Find the puzzle a solution most likely belongs to, falling back to verification and then structural matching when no puzzle has the name the solution gives.
+pub fn resolve_puzzle(solution: &Solution, puzzles: &[Puzzle]) -> Option<PuzzleMatch>{
+    let named: Vec<usize> = (0..puzzles.len()).filter(|&i| puzzles[i].name == solution.puzzle_name).collect();
+    if let Some(&index) = named.first(){
+        return Some(PuzzleMatch{ index, confidence: MatchConfidence::Named, candidates: named.len() });
+    }
+    let fitting: Vec<usize> = (0..puzzles.len()).filter(|&i| fits_puzzle(solution, &puzzles[i])).collect();
+    let verified: Vec<usize> = fitting.iter().cloned().filter(|&i| solves(solution, &puzzles[i])).collect();
+    if let Some(&index) = verified.first(){
+        return Some(PuzzleMatch{ index, confidence: MatchConfidence::Verified, candidates: verified.len() });
+    }
+    let confidences: Vec<(usize, MatchConfidence)> = fitting.into_iter().map(|i| (i, structural_confidence(solution, &puzzles[i]))).collect();
+    let best = confidences.iter().map(|(_, c)| *c).min()?;
+    let mut best_matches = confidences.iter().filter(|(_, c)| *c == best);
+    let (index, _) = *best_matches.next()?;
+    Some(PuzzleMatch{ index, confidence: best, candidates: 1 + best_matches.count() })
+}
+
+/// Whether a solution completes a puzzle, even if its parts overlap.
+fn solves(solution: &Solution, puzzle: &Puzzle) -> bool{
+    verify_with_options(puzzle, solution, DEFAULT_CYCLE_LIMIT, SimOptions{ allow_overlap: true, strict_drops: false }).is_ok()
+}
+
+/// How well a solution that fits a puzzle matches it structurally.
+fn structural_confidence(solution: &Solution, puzzle: &Puzzle) -> MatchConfidence{
+    let mut uses_reagent = vec![false; puzzle.reagents.len()];
+    for part in &solution.parts{
+        let consistent = match part.ty{
+            PartType::Input => { uses_reagent[part.index as usize] = true; true }
+            PartType::Output => !is_polymer(puzzle, part.index),
+            PartType::PolymerOutput => is_polymer(puzzle, part.index),
+            PartType::Conduit => puzzle.production_info.is_some(),
+            _ => true
+        };
+        if !consistent{
+            return MatchConfidence::Partial;
+        }
+    }
+    if uses_reagent.into_iter().all(|u| u) { MatchConfidence::Structural } else { MatchConfidence::Partial }
+}
+
+fn is_polymer(puzzle: &Puzzle, product: i32) -> bool{
+    puzzle.products[product as usize].is_polymer()
+}
@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{Instruction, PartCategory, Solution};
+
+/// Instruction usage across every arm of a solution.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct InstructionHistogram{
+    /// Statistics for each arm, in solution order.
+    pub arms: Vec<ArmInstructions>,
+    /// The number of slots between the first and last instruction of any arm, inclusive.
+    pub period: i32
+}
+
+/// Instruction usage of a single arm.
+/// Tapes are measured as stored, without expanding reset or repeat instructions.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ArmInstructions{
+    /// Index of the arm in the solution.
+    pub part_index: usize,
+    /// The number of each kind of instruction on this arm's tape, excluding blanks.
+    pub counts: HashMap<Instruction, u32>,
+    /// The total number of non-blank instructions on this arm's tape.
+    pub total: u32,
+    /// Index of this arm's first instruction, or 0 if it has none.
+    pub tape_start: i32,
+    /// The number of slots between this arm's first and last instruction, inclusive.
+    pub tape_length: i32
+}
+
+impl InstructionHistogram{
+    /// The arm with the most instructions, if there are any arms.
+    pub fn busiest_arm(&self) -> Option<&ArmInstructions>{
+        self.arms.iter().max_by_key(|arm| arm.total)
+    }
+}
+
+/// Count the instructions used by each arm of a solution.
+pub fn instruction_histogram(solution: &Solution) -> InstructionHistogram{
+    let mut arms = Vec::new();
+    let (mut first, mut last) = (i32::MAX, i32::MIN);
+    for (part_index, part) in solution.parts.iter().enumerate(){
+        if part.ty.category() != PartCategory::Arm{
+            continue;
+        }
+        let mut stats = ArmInstructions{ part_index, ..ArmInstructions::default() };
+        let (mut arm_first, mut arm_last) = (i32::MAX, i32::MIN);
+        for (instr, index) in &part.instructions{
+            if *instr == Instruction::Blank{
+                continue;
+            }
+            *stats.counts.entry(*instr).or_insert(0) += 1;
+            stats.total += 1;
+            arm_first = arm_first.min(*index);
+            arm_last = arm_last.max(*index);
+        }
+        if stats.total > 0{
+            stats.tape_start = arm_first;
+            stats.tape_length = arm_last - arm_first + 1;
+            first = first.min(arm_first);
+            last = last.max(arm_last);
+        }
+        arms.push(stats);
+    }
+    let period = if first <= last { last - first + 1 } else { 0 };
+    InstructionHistogram{ arms, period }
+}
+
+/// The instructions metric of a solution, compared against the value recorded in its file.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct InstructionReport{
+    /// The instructions metric, as counted by `Solution::instructions`.
+    pub computed: i32,
+    /// The instructions metric recorded in the solution file, if it has metrics.
+    pub recorded: Option<i32>,
+    /// The number of counted instructions on each arm, as `(part index, count)`, in solution order.
+    pub arm_counts: Vec<(usize, i32)>,
+    /// Anything about the tapes that the game wouldn't allow.
+    pub problems: Vec<InstructionProblem>
+}
+
+/// A tape layout that the game wouldn't produce.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InstructionProblem{
+    /// A part that isn't an arm has instructions.
+    NotAnArm{ part_index: usize },
+    /// An arm has more than one instruction at the same index.
+    DuplicateIndex{ part_index: usize, index: i32 }
+}
+
+impl InstructionReport{
+    /// Whether the recorded instructions metric disagrees with the computed one. Solutions without metrics never disagree.
+    pub fn has_discrepancy(&self) -> bool{
+        self.recorded.is_some_and(|recorded| recorded != self.computed)
+    }
+
+    /// Whether the instructions metric is consistent and every tape is well-formed.
+    pub fn is_valid(&self) -> bool{
+        !self.has_discrepancy() && self.problems.is_empty()
+    }
+}
+
+/// Count the instructions of a solution per arm, check each tape, and compare the total against the recorded metrics.
+pub fn check_instructions(solution: &Solution) -> InstructionReport{
+    let mut report = InstructionReport{
+        computed: solution.instructions(),
+        recorded: solution.metrics.map(|m| m.instructions),
+        ..InstructionReport::default()
+    };
+    for (part_index, part) in solution.parts.iter().enumerate(){
+        if part.ty.category() != PartCategory::Arm{
+            if !part.instructions.is_empty(){
+                report.problems.push(InstructionProblem::NotAnArm{ part_index });
+            }
+            continue;
+        }
+        let mut seen = HashSet::new();
+        for (_, index) in &part.instructions{
+            if !seen.insert(*index){
+                report.problems.push(InstructionProblem::DuplicateIndex{ part_index, index: *index });
+            }
+        }
+        let count = part.instructions.iter().filter(|(instr, _)| instr.is_counted()).count() as i32;
+        report.arm_counts.push((part_index, count));
+    }
+    report
+}
@@ -0,0 +1,7 @@
+pub mod cost;
+pub mod instructions;
+pub mod timeline;
+pub mod symmetry;
+pub mod identify;
+pub mod pareto;
+pub mod steady_state;
@@ -0,0 +1,26 @@
+use crate::data::Metrics;
+use crate::verify::MetricKind;
+
+// Pareto frontiers of solutions, where lower is better for every metric.
+
+/// Indices of the metrics on the Pareto frontier over all four of the game's metrics, in order.
+/// See `pareto_frontier_by`.
+pub fn pareto_frontier(metrics: &[Metrics]) -> Vec<usize>{
+    pareto_frontier_by(metrics, &MetricKind::ALL)
+}
+
+/// Indices of the metrics that no others dominate in the given subset of metrics, in order.
+/// One set of metrics dominates another if it's no worse in every metric of the subset and better in at least one.
+/// When several are equal in every metric of the subset, only the first is on the frontier.
+pub fn pareto_frontier_by(metrics: &[Metrics], subset: &[MetricKind]) -> Vec<usize>{
+    let key = |m: &Metrics| subset.iter().map(|kind| kind.of(m)).collect::<Vec<i32>>();
+    let keys: Vec<Vec<i32>> = metrics.iter().map(key).collect();
+    (0..keys.len())
+        .filter(|&i| !keys.iter().enumerate().any(|(j, other)| j != i && (dominates(other, &keys[i]) || (j < i && *other == keys[i]))))
+        .collect()
+}
+
+/// Whether `a` is no worse than `b` in every metric and better in at least one.
+fn dominates(a: &[i32], b: &[i32]) -> bool{
+    a.iter().zip(b).all(|(a, b)| a <= b) && a != b
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use crate::data::{HexIndex, HexMap, Puzzle, Solution};
+use crate::sim::{Sim, SimError, SimOptions, SimPartType};
+use crate::verify::{spans, ExtendedMetrics, Throughput};
+
+/// The periodic state a solution settles into, proven to repeat forever.
+/// Molecules left where nothing can reach them, such as waste, are ignored, so solutions that pile up waste can still settle.
+/// Molecules that only change out of reach, such as a chain pulled further out every pass, are taken to keep changing the same way
+/// once two passes in a row leave everything in reach the same and grow the area by the same amount.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SteadyState{
+    /// When the loop starts, how long it is, and how many of each product are output during it.
+    pub throughput: Throughput,
+    /// The area when the loop starts.
+    pub area_at_loop_start: usize,
+    /// The area after the first pass through the loop.
+    pub area_at_infinity: usize,
+    /// How many hexes each pass through the loop adds to the area, which is 0 unless something keeps growing out of reach.
+    pub area_growth_per_loop: usize,
+    /// The height after the first pass through the loop. See `ExtendedMetrics::height`.
+    pub height_at_infinity: i32,
+    /// The width after the first pass through the loop. See `ExtendedMetrics::width`.
+    pub width_at_infinity: f32
+}
+
+/// How tall and wide a solution gets if it runs forever, with `None` for a measure that grows without bound.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SizeAtInfinity{
+    pub height: Option<i32>,
+    pub width: Option<f32>
+}
+
+/// Simulate a solution until it provably repeats, checking its state at the start of every period of its tapes.
+/// Fails with `SimError::TimedOut` if no loop is proven within `cycle_limit` cycles, such as for solutions whose molecules keep growing.
+pub fn find_steady_state(puzzle: &Puzzle, solution: &Solution, cycle_limit: i32, options: SimOptions) -> Result<SteadyState, SimError>{
+    let mut sim = Sim::create_with_options(puzzle, solution, options).map_err(SimError::InvalidSolution)?;
+    let mut reachable = fixed_hexes(&sim);
+    let mut seen: HashMap<u64, i32> = HashMap::new();
+    loop{
+        // states hashed while fewer hexes were reachable can't be compared with later ones
+        if add_grippers(&sim, &mut reachable){
+            seen.clear();
+        }
+        if sim.period == 0 || sim.cycle % sim.period == 0{
+            if let Some(loop_start) = seen.insert(sim.reachable_state_key(&reachable).stable_hash(), sim.cycle){
+                if let Some(steady) = prove_loop(&sim, loop_start, &reachable)?{
+                    return Ok(steady);
+                }
+            }
+        }
+        if sim.cycle >= cycle_limit{
+            return Err(SimError::TimedOut{ cycle_limit });
+        }
+        sim.step()?;
+    }
+}
+
+/// Check that a simulation whose state hash matches the one from `loop_start` repeats, by running one more pass and comparing full states,
+/// or if only molecules out of reach differ, two more passes that must agree within reach and add the same area.
+/// Grippers that reach no new hexes during the passes can't have touched anything left out of the state.
+fn prove_loop(sim: &Sim, loop_start: i32, reachable: &HexMap<()>) -> Result<Option<SteadyState>, SimError>{
+    let loop_cycles = sim.cycle - loop_start;
+    let mut next_reachable = reachable.clone();
+    let Some(next) = run_pass(sim, loop_cycles, &mut next_reachable)? else { return Ok(None) };
+    let area_growth_per_loop = next.area() - sim.area();
+    if next.active_state_key(reachable) != sim.active_state_key(reachable){
+        if next.reachable_state_key(reachable) != sim.reachable_state_key(reachable){
+            return Ok(None);
+        }
+        let Some(after) = run_pass(&next, loop_cycles, &mut next_reachable)? else { return Ok(None) };
+        if after.reachable_state_key(reachable) != next.reachable_state_key(reachable) || after.area() - next.area() != area_growth_per_loop{
+            return Ok(None);
+        }
+    }
+
+    let mut outputs_per_loop = vec![0; sim.product_count];
+    for delivery in sim.deliveries.iter().filter(|d| d.cycle > loop_start){
+        outputs_per_loop[delivery.product] += 1;
+    }
+    let extended = ExtendedMetrics::from_hexes(sim.swept_hexes.keys());
+    let area_at = |cycle: i32| sim.area_changes.iter().take_while(|c| c.cycle <= cycle).last().map_or(0, |c| c.area);
+    Ok(Some(SteadyState{
+        throughput: Throughput{ loop_start, loop_cycles, outputs_per_loop },
+        area_at_loop_start: area_at(loop_start),
+        area_at_infinity: sim.area(),
+        area_growth_per_loop,
+        height_at_infinity: extended.height,
+        width_at_infinity: extended.width
+    }))
+}
+
+/// Run a copy of a simulation for one pass through a loop, or `None` if a gripper reaches a hex it hadn't.
+fn run_pass(sim: &Sim, loop_cycles: i32, reachable: &mut HexMap<()>) -> Result<Option<Sim>, SimError>{
+    let mut next = sim.clone();
+    for _ in 0..loop_cycles{
+        next.step()?;
+        if add_grippers(&next, reachable){
+            return Ok(None);
+        }
+    }
+    Ok(Some(next))
+}
+
+/// Find the height and width a solution reaches if it runs forever.
+/// Solutions that settle into a steady state without growing are measured exactly. Otherwise, the solution is run for `cycle_limit` cycles, and a row direction
+/// that it's still spreading across or along over the second half of them is taken to grow forever; the height or width is unbounded only if all three are.
+pub fn find_size_at_infinity(puzzle: &Puzzle, solution: &Solution, cycle_limit: i32, options: SimOptions) -> Result<SizeAtInfinity, SimError>{
+    match find_steady_state(puzzle, solution, cycle_limit, options){
+        Ok(steady) if steady.area_growth_per_loop == 0 => return Ok(SizeAtInfinity{ height: Some(steady.height_at_infinity), width: Some(steady.width_at_infinity) }),
+        Ok(_) | Err(SimError::TimedOut{ .. }) => {}
+        Err(error) => return Err(error)
+    }
+    let mut sim = Sim::create_with_options(puzzle, solution, options).map_err(SimError::InvalidSolution)?;
+    sim.run_until(cycle_limit / 2)?;
+    let halfway = spans(sim.swept_hexes.keys()).unwrap_or_default();
+    sim.run_until(cycle_limit)?;
+    let end = spans(sim.swept_hexes.keys()).unwrap_or_default();
+    let settled = |measure: fn(&(i32, i32)) -> i32| (0..3).filter(|i| measure(&halfway[*i]) == measure(&end[*i])).map(|i| measure(&end[i])).min();
+    Ok(SizeAtInfinity{
+        height: settled(|(rows, _)| *rows),
+        width: settled(|(_, columns)| *columns).map(|columns| columns as f32 / 2.0)
+    })
+}
+
+/// Every hex that a part other than an arm acts on: glyphs, inputs, outputs, and conduits.
+fn fixed_hexes(sim: &Sim) -> HexMap<()>{
+    let mut hexes = HexMap::new();
+    for part in &sim.parts{
+        hexes.extend(part.glyph_hexes());
+        match &part.ty{
+            SimPartType::Input(molecule) | SimPartType::Output(molecule, _) | SimPartType::PolymerOutput(molecule, _) => {
+                hexes.extend(molecule.rotated(HexIndex::default(), part.rotation).translated(part.pos).atoms.keys().copied());
+            }
+            SimPartType::Conduit(conduit) => hexes.extend(conduit.iter().copied()),
+            _ => {}
+        }
+    }
+    hexes
+}
+
+/// Add the current position of every gripper, returning whether any of them weren't already included.
+fn add_grippers(sim: &Sim, hexes: &mut HexMap<()>) -> bool{
+    let mut added = false;
+    for part in &sim.parts{
+        if let SimPartType::Arm(arm) = &part.ty{
+            for gripper in arm.gripper_positions(part.pos, part.rotation){
+                added |= hexes.insert_hex(gripper);
+            }
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::{Atom, Molecule, PartType};
+
+    /// An arm on a track that carries salt onto a bonder, adding it to the end of a chain, then pulls the chain one hex further each pass.
+    fn growing_chain() -> (Puzzle, Solution){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle")
+            .input(0, HexIndex::default(), 0)
+            .glyph(PartType::Bonding, HexIndex{ q: -2, r: 0 }, 0)
+            .track(&[HexIndex{ q: 0, r: 1 }, HexIndex{ q: -1, r: 1 }, HexIndex{ q: -2, r: 1 }])
+            .arm(HexIndex{ q: 0, r: 1 }, 4, 1)
+            .instructions(0, "GAAgaa")
+            .build().unwrap();
+        (puzzle, solution)
+    }
+
+    #[test]
+    fn chains_growing_out_of_reach_settle_with_area_growth(){
+        let (puzzle, solution) = growing_chain();
+        let steady = find_steady_state(&puzzle, &solution, 1000, SimOptions::default()).unwrap();
+        assert_eq!(steady.throughput.loop_cycles, 6);
+        assert_eq!(steady.area_growth_per_loop, 1);
+
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.run_until(steady.throughput.loop_start + 11 * steady.throughput.loop_cycles).unwrap();
+        assert_eq!(sim.area(), steady.area_at_infinity + 10 * steady.area_growth_per_loop);
+    }
+}
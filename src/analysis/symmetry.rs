@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use crate::data::{HexIndex, HexRotation, Instruction, Part, Solution};
+
+/// A reflection of the hex grid, across an axis that may not pass through the origin.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MirrorAxis{
+    /// The axis direction, as a rotation applied after reflecting across the Q axis.
+    pub rotation: HexRotation,
+    /// The translation applied after reflecting and rotating.
+    pub offset: HexIndex
+}
+
+impl MirrorAxis{
+    /// Reflect a position across this axis.
+    pub fn reflect(self, pos: HexIndex) -> HexIndex{
+        self.transform().pos(pos)
+    }
+
+    /// Reflect a relative offset across this axis, ignoring its translation.
+    pub fn reflect_offset(self, offset: HexIndex) -> HexIndex{
+        self.transform().relative(offset)
+    }
+
+    /// Reflect a part rotation across this axis.
+    pub fn reflect_rotation(self, rotation: HexRotation) -> HexRotation{
+        self.transform().rotation(rotation)
+    }
+
+    fn transform(self) -> Transform{
+        Transform{ mirror: true, rotation: self.rotation, offset: self.offset }
+    }
+}
+
+/// How closely a solution matches its own mirror image across some axis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorSymmetry{
+    /// The axis that the solution is closest to being symmetric across.
+    pub axis: MirrorAxis,
+    /// Indices of parts with no mirror image across the axis, in solution order.
+    pub unmatched: Vec<usize>
+}
+
+impl MirrorSymmetry{
+    /// Whether every part has a mirror image across the axis.
+    pub fn is_symmetric(&self) -> bool{
+        self.unmatched.is_empty()
+    }
+}
+
+/// How closely a solution matches itself when rotated around some point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RotationalSymmetry{
+    /// The number of rotations in the symmetry group: 2, 3, or 6.
+    pub order: u8,
+    /// The translation applied after rotating around the origin by `6 / order` turns.
+    /// Together these rotate around the centre of symmetry, which may be a hex, edge, or vertex.
+    pub offset: HexIndex,
+    /// Indices of parts with no rotated image, in solution order.
+    pub unmatched: Vec<usize>
+}
+
+impl RotationalSymmetry{
+    /// Whether every part has a rotated image.
+    pub fn is_symmetric(&self) -> bool{
+        self.unmatched.is_empty()
+    }
+}
+
+/// Find the axis that a solution is closest to being mirror-symmetric across.
+/// Parts are compared by type, position, rotation, and arm length, and arms' tapes must match with rotation directions swapped.
+/// Input and output molecules are not compared. Returns `None` if the solution has no parts.
+pub fn mirror_symmetry(solution: &Solution) -> Option<MirrorSymmetry>{
+    (0..6u8)
+        .filter_map(|turns| {
+            let rotation = HexRotation::from_unsigned(turns);
+            closest_image(&solution.parts, true, rotation).map(|(offset, unmatched)| MirrorSymmetry{ axis: MirrorAxis{ rotation, offset }, unmatched })
+        })
+        .min_by_key(|m| m.unmatched.len())
+}
+
+/// Find the centre that a solution is closest to being symmetric around under rotations of the given order (2, 3, or 6).
+/// Parts are compared as in `mirror_symmetry`, with tapes unchanged.
+/// Returns `None` if the solution has no parts or the order is not 2, 3, or 6.
+pub fn rotational_symmetry(solution: &Solution, order: u8) -> Option<RotationalSymmetry>{
+    if ![2, 3, 6].contains(&order){
+        return None;
+    }
+    let rotation = HexRotation::from_unsigned(6 / order);
+    closest_image(&solution.parts, false, rotation).map(|(offset, unmatched)| RotationalSymmetry{ order, offset, unmatched })
+}
+
+/// The order of the largest rotational symmetry group a solution exactly has, or 1 if it has no rotational symmetry.
+pub fn rotational_symmetry_order(solution: &Solution) -> u8{
+    [6, 3, 2].into_iter()
+        .find(|order| rotational_symmetry(solution, *order).is_some_and(|r| r.is_symmetric()))
+        .unwrap_or(1)
+}
+
+/// A rigid transformation of the hex grid: an optional reflection, then a rotation around the origin, then a translation.
+#[derive(Copy, Clone, Debug)]
+struct Transform{
+    mirror: bool,
+    rotation: HexRotation,
+    offset: HexIndex
+}
+
+impl Transform{
+    fn relative(self, offset: HexIndex) -> HexIndex{
+        let offset = if self.mirror { offset.mirrored() } else { offset };
+        offset.rotated(HexIndex::default(), self.rotation)
+    }
+
+    fn pos(self, pos: HexIndex) -> HexIndex{
+        self.relative(pos) + self.offset
+    }
+
+    fn rotation(self, rotation: HexRotation) -> HexRotation{
+        (if self.mirror { rotation.mirrored() } else { rotation }) + self.rotation
+    }
+
+    fn instruction(self, instr: Instruction) -> Instruction{
+        if self.mirror { instr.mirrored() } else { instr }
+    }
+
+    /// Whether `other` is the image of `part` under this transformation.
+    fn maps(self, part: &Part, other: &Part) -> bool{
+        if part.ty != other.ty || part.arm_length != other.arm_length || part.index != other.index{
+            return false;
+        }
+        if self.pos(part.pos) != other.pos || self.rotation(HexRotation::from_signed(part.rotation)) != HexRotation::from_signed(other.rotation){
+            return false;
+        }
+        let tape: HashSet<_> = part.instructions.iter().map(|(instr, idx)| (self.instruction(*instr), *idx)).collect();
+        if tape != other.instructions.iter().cloned().collect(){
+            return false;
+        }
+        let map_all = |hexes: &Vec<HexIndex>| hexes.iter().map(|h| self.relative(*h)).collect::<HashSet<_>>();
+        map_all(&part.track_hexes) == other.track_hexes.iter().cloned().collect()
+            && map_all(&part.conduit_hexes) == other.conduit_hexes.iter().cloned().collect()
+    }
+}
+
+/// Find the translation that, after the given reflection and rotation, leaves the fewest parts without an image.
+fn closest_image(parts: &[Part], mirror: bool, rotation: HexRotation) -> Option<(HexIndex, Vec<usize>)>{
+    let linear = Transform{ mirror, rotation, offset: HexIndex::default() };
+    // any translation worth considering maps some part onto another of the same type
+    let mut offsets = HashSet::new();
+    for a in parts{
+        for b in parts.iter().filter(|b| b.ty == a.ty){
+            offsets.insert(b.pos - linear.pos(a.pos));
+        }
+    }
+    let mut best: Option<(HexIndex, Vec<usize>)> = None;
+    for offset in offsets{
+        let transform = Transform{ offset, ..linear };
+        let unmatched: Vec<usize> = (0..parts.len())
+            .filter(|&i| !parts.iter().any(|other| transform.maps(&parts[i], other)))
+            .collect();
+        if best.as_ref().is_none_or(|(_, b)| unmatched.len() < b.len()){
+            best = Some((offset, unmatched));
+        }
+    }
+    best
+}
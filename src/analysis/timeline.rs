@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+use crate::data::{Instruction, PartCategory, Solution};
+use crate::tape::{self, Tape};
+
+/// Every arm's instructions laid out on the timeline a simulation runs, with one row per arm and one column per cycle of the period.
+/// Reset and repeat instructions are expanded into the instructions they stand for, as in `Tape`, and period overrides are blank.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Timeline{
+    /// The tape index of the first column, which runs on the first cycle.
+    pub start: i32,
+    /// The number of columns in every row, which is the number of cycles before every tape loops.
+    pub length: usize,
+    /// One row per arm, in solution order.
+    pub rows: Vec<TimelineRow>
+}
+
+/// A single arm's instructions on a timeline.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct TimelineRow{
+    /// Index of the arm in the solution.
+    pub part_index: usize,
+    /// The instruction in each column, with `Blank` where the arm does nothing.
+    pub cells: Vec<Instruction>
+}
+
+/// Lay out the instructions of every arm in a solution on a shared timeline.
+/// Fails if any tape can't be laid out, as `Tape::from_part` and `tape::timeline` describe.
+pub fn timeline(solution: &Solution) -> Result<Timeline, &'static str>{
+    let arms: Vec<_> = solution.parts.iter().enumerate().filter(|(_, p)| p.ty.category() == PartCategory::Arm).collect();
+    let tapes = arms.iter().map(|(_, part)| Tape::from_part(part)).collect::<Result<Vec<_>, _>>()?;
+    let (start, period) = tape::timeline(&tapes)?;
+    let rows = arms.iter().zip(&tapes).map(|((part_index, _), tape)| {
+        TimelineRow{ part_index: *part_index, cells: (start..start + period).map(|idx| tape.at(idx)).collect() }
+    }).collect();
+    Ok(Timeline{ start, length: period as usize, rows })
+}
+
+impl Timeline{
+    /// Render this timeline as text, with a header of cycle numbers, one line per arm, and `.` for blanks.
+    pub fn to_text(&self) -> String{
+        let label_width = self.rows.iter().map(|r| format!("arm {}", r.part_index).len()).max().unwrap_or(0);
+        let mut out = String::new();
+        // mark every tenth column
+        let _ = write!(out, "{:label_width$} |", "");
+        let mut col = 0;
+        while col < self.length{
+            let label = col.to_string();
+            if col % 10 == 0 && col + label.len() <= self.length{
+                out.push_str(&label);
+                col += label.len();
+            }else{
+                out.push(' ');
+                col += 1;
+            }
+        }
+        out.push('\n');
+        for row in &self.rows{
+            let _ = write!(out, "{:label_width$} |", format!("arm {}", row.part_index));
+            for cell in &row.cells{
+                out.push(if *cell == Instruction::Blank { '.' } else { cell.id() as char });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Render this timeline as JSON, with instructions as their single-character solution file IDs and blanks as `null`.
+    pub fn to_json(&self) -> String{
+        let mut out = String::new();
+        let _ = write!(out, "{{\"start\":{},\"length\":{},\"rows\":[", self.start, self.length);
+        for (i, row) in self.rows.iter().enumerate(){
+            if i > 0{
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"part_index\":{},\"cells\":[", row.part_index);
+            for (j, cell) in row.cells.iter().enumerate(){
+                if j > 0{
+                    out.push(',');
+                }
+                if *cell == Instruction::Blank{
+                    out.push_str("null");
+                }else{
+                    let _ = write!(out, "\"{}\"", cell.id() as char);
+                }
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::SolutionBuilder;
+    use crate::data::HexIndex;
+
+    #[test]
+    fn columns_are_cycles(){
+        let solution = SolutionBuilder::new("s", "p")
+            .arm(HexIndex::default(), 0, 1)
+            .arm(HexIndex{ q: 3, r: 0 }, 0, 1)
+            .instructions(0, "  Gr X")
+            .instructions(1, "   G C")
+            .build().unwrap();
+        let timeline = timeline(&solution).unwrap();
+        assert_eq!(timeline.start, 2);
+        assert_eq!(timeline.length, 5);
+        assert_eq!(timeline.to_text(), "      |0    \narm 0 |Gr.gR\narm 1 |.G.G.\n");
+    }
+}
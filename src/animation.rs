@@ -0,0 +1,320 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::data::{BondType, HexIndex, HexRotation, PartType, Puzzle, Solution};
+use crate::render::{atom_style, part_fill, ARM_COLOUR, BOND_COLOUR, BOND_WIDTH, TRACK_COLOUR, TRIPLEX_COLOURS, TRIPLEX_SPACING, TRIPLEX_WIDTH};
+use crate::sim::collision::{ColliderType, Vector2, HEX_HEIGHT, HEX_WIDTH};
+use crate::sim::{Motion, Sim, SimArm, SimError, SimMolecule, SimOptions, SimPart, SimPartType};
+use crate::verify::DEFAULT_CYCLE_LIMIT;
+
+// Animated GIFs of simulation runs, drawn with the same colours as the SVG renderer.
+
+/// Colour of the background behind the board.
+const BACKGROUND: &str = "#f4efe4";
+
+/// Settings for `record_gif`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationOptions{
+    /// The first cycle to record.
+    pub first_cycle: i32,
+    /// The cycle to stop recording at, or `None` to record until the solution completes.
+    pub last_cycle: Option<i32>,
+    /// The number of frames drawn for each cycle, evenly spaced through its movement. 1 draws only the state between cycles.
+    pub frames_per_cycle: u32,
+    /// Frames shown per second. GIFs time frames in hundredths of a second, so the delay between frames is rounded.
+    pub frame_rate: u32,
+    /// The distance between the centres of neighbouring hexes, in pixels.
+    pub hex_size: f32,
+    /// Hexes at two opposite corners of the region to draw, or `None` to fit everything the solution covers while recording.
+    pub crop: Option<(HexIndex, HexIndex)>
+}
+
+impl Default for AnimationOptions{
+    fn default() -> Self{
+        AnimationOptions{ first_cycle: 0, last_cycle: None, frames_per_cycle: 4, frame_rate: 20, hex_size: 32.0, crop: None }
+    }
+}
+
+/// Why a simulation run couldn't be recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnimationError{
+    /// The solution couldn't be simulated through the recorded cycles.
+    Sim(SimError),
+    /// The frames couldn't be encoded, e.g. because they're too large for a GIF.
+    Encoding(&'static str)
+}
+
+impl From<SimError> for AnimationError{
+    fn from(error: SimError) -> Self{
+        AnimationError::Sim(error)
+    }
+}
+
+/// Simulate a solution and record it as a looping animated GIF.
+/// Parts are drawn as in `render::render_solution`, without labels; atoms are told apart by colour alone.
+pub fn record_gif(puzzle: &Puzzle, solution: &Solution, options: &AnimationOptions) -> Result<Vec<u8>, AnimationError>{
+    // GIFs are the only record of solutions in the overlap category, so they can be recorded too
+    let mut sim = Sim::create_with_options(puzzle, solution, SimOptions{ allow_overlap: true, strict_drops: false }).map_err(SimError::InvalidSolution)?;
+    while sim.cycle < options.first_cycle{
+        advance(&mut sim)?;
+    }
+
+    let view = match options.crop{
+        Some((a, b)) => View::fit([a, b].into_iter(), options.hex_size),
+        None => {
+            let mut preview = sim.clone();
+            while !finished(&preview, options){
+                advance(&mut preview)?;
+            }
+            View::fit(preview.swept_hexes.keys().copied(), options.hex_size)
+        }
+    };
+    if view.width > u16::MAX as usize || view.height > u16::MAX as usize{
+        return Err(AnimationError::Encoding("animation is too large for a GIF"));
+    }
+
+    let mut palette = Palette::default();
+    let background = palette.index(BACKGROUND);
+    let floor = draw_floor(&sim, &view, &mut palette, background);
+    let delay = (100 / options.frame_rate.max(1)).clamp(1, u16::MAX as u32) as u16;
+    let steps = options.frames_per_cycle.max(1);
+
+    let mut frames = Vec::new();
+    while !finished(&sim, options){
+        let before = sim.clone();
+        advance(&mut sim)?;
+        for step in 0..steps{
+            let mut canvas = floor.clone();
+            draw_moving(&mut canvas, &view, &mut palette, &before.parts, &sim, step as f32 / steps as f32);
+            frames.push(canvas);
+        }
+    }
+    let mut last = floor.clone();
+    draw_moving(&mut last, &view, &mut palette, &sim.parts, &sim, 1.0);
+    frames.push(last);
+
+    let mut out = Vec::new();
+    {
+        let encoding = |_| AnimationError::Encoding("could not encode GIF");
+        let mut encoder = Encoder::new(&mut out, view.width as u16, view.height as u16, &palette.rgb()).map_err(encoding)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(encoding)?;
+        for canvas in frames{
+            let frame = Frame{ width: view.width as u16, height: view.height as u16, delay, buffer: Cow::Owned(canvas.pixels), ..Frame::default() };
+            encoder.write_frame(&frame).map_err(encoding)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Whether the recording should stop before the next cycle.
+fn finished(sim: &Sim, options: &AnimationOptions) -> bool{
+    match options.last_cycle{
+        Some(last) => sim.cycle >= last,
+        None => sim.is_complete()
+    }
+}
+
+/// Simulate one cycle, giving up after `DEFAULT_CYCLE_LIMIT` cycles.
+fn advance(sim: &mut Sim) -> Result<(), SimError>{
+    if sim.cycle >= DEFAULT_CYCLE_LIMIT{
+        return Err(SimError::TimedOut{ cycle_limit: DEFAULT_CYCLE_LIMIT });
+    }
+    sim.step()
+}
+
+/// Draw the parts that never move: glyphs, inputs, outputs, track, and conduits.
+fn draw_floor(sim: &Sim, view: &View, palette: &mut Palette, background: u8) -> Canvas{
+    let mut hexes: HashMap<HexIndex, u8> = HashMap::new();
+    for part in &sim.parts{
+        let (covered, colour): (Vec<HexIndex>, &str) = match &part.ty{
+            SimPartType::Arm(_) => continue,
+            SimPartType::Track(path) => (path.clone(), TRACK_COLOUR),
+            SimPartType::Conduit(covered) => (covered.clone(), part_fill(&PartType::Conduit)),
+            SimPartType::Input(molecule) | SimPartType::Output(molecule, _) | SimPartType::PolymerOutput(molecule, _) => {
+                let ty = if let SimPartType::Input(_) = part.ty { PartType::Input } else { PartType::Output };
+                (molecule.atoms.keys().map(|h| h.rotated(HexIndex::default(), part.rotation) + part.pos).collect(), part_fill(&ty))
+            }
+            _ => (part.glyph_hexes(), part_fill(&PartType::Bonding))
+        };
+        let colour = palette.index(colour);
+        hexes.extend(covered.into_iter().map(|h| (h, colour)));
+    }
+    let mut canvas = Canvas{ width: view.width, height: view.height, pixels: vec![background; view.width * view.height] };
+    for y in 0..view.height{
+        for x in 0..view.width{
+            if let Some(colour) = hexes.get(&view.point_at(x, y).to_hex_index()){
+                canvas.pixels[y * view.width + x] = *colour;
+            }
+        }
+    }
+    canvas
+}
+
+/// Draw arms and molecules partway through a cycle, `time` of the way from `before` to the parts of `after`.
+fn draw_moving(canvas: &mut Canvas, view: &View, palette: &mut Palette, before: &[SimPart], after: &Sim, time: f32){
+    let (arm, bond) = (palette.index(ARM_COLOUR), palette.index(BOND_COLOUR));
+    let scale = view.scale;
+    for (start, end) in before.iter().zip(&after.parts){
+        let (SimPartType::Arm(start_arm), SimPartType::Arm(end_arm)) = (&start.ty, &end.ty) else { continue };
+        let base = lerp(Vector2::from_hex_index(start.pos), Vector2::from_hex_index(end.pos), time);
+        let angle = turns_angle(start.rotation) + shortest_turn(start.rotation, end.rotation) * std::f32::consts::FRAC_PI_3 * time;
+        let length = (start_arm.length as f32 + (end_arm.length - start_arm.length) as f32 * time) * HEX_WIDTH;
+        for turns in SimArm::gripper_rotations(&start_arm.ty){
+            let a = angle + *turns as f32 * std::f32::consts::FRAC_PI_3;
+            let gripper = base + Vector2::new(a.cos(), a.sin()) * length;
+            canvas.line(view.pixel_at(base), view.pixel_at(gripper), BOND_WIDTH * scale, arm);
+            canvas.circle(view.pixel_at(gripper), ColliderType::ArmGripper.radius() * scale / 2.0, arm);
+        }
+        canvas.circle(view.pixel_at(base), ColliderType::ArmBase.radius() * scale, arm);
+    }
+
+    let molecules: Vec<(&SimMolecule, Motion)> = if time < 1.0{
+        after.last_movement.iter().map(|(m, motion)| (m, *motion)).collect()
+    }else{
+        after.molecules.iter().map(|m| (m, Motion::Stay)).collect()
+    };
+    for (molecule, motion) in molecules{
+        let at = |pos: HexIndex| motion.movement_of(pos + molecule.pos).pos_at(time);
+        for b in &molecule.layout.bonds{
+            let (start, end) = (view.pixel_at(at(b.start)), view.pixel_at(at(b.end)));
+            match b.ty{
+                BondType::Normal => canvas.line(start, end, BOND_WIDTH * scale, bond),
+                BondType::Triplex{ red, black, yellow } => {
+                    let along = end - start;
+                    let across = Vector2::new(-along.y, along.x) * (1.0 / along.length().max(f32::EPSILON));
+                    for (slot, (present, colour)) in [red, black, yellow].into_iter().zip(TRIPLEX_COLOURS).enumerate(){
+                        if present{
+                            let offset = across * ((slot as f32 - 1.0) * TRIPLEX_SPACING * scale);
+                            canvas.line(start + offset, end + offset, TRIPLEX_WIDTH * scale, palette.index(colour));
+                        }
+                    }
+                }
+            }
+        }
+        for (pos, atom) in &molecule.layout.atoms{
+            let centre = view.pixel_at(at(*pos));
+            let radius = ColliderType::Atom.radius() * scale;
+            canvas.circle(centre, radius, bond);
+            canvas.circle(centre, radius - 1.5, palette.index(atom_style(*atom).0));
+        }
+    }
+}
+
+fn lerp(a: Vector2, b: Vector2, time: f32) -> Vector2{
+    a + (b - a) * time
+}
+
+fn turns_angle(rotation: HexRotation) -> f32{
+    rotation.turns() as f32 * std::f32::consts::FRAC_PI_3
+}
+
+/// The number of turns, from -3 to 2, to go the short way round from one rotation to another.
+fn shortest_turn(from: HexRotation, to: HexRotation) -> f32{
+    let turns = (to - from).turns() as f32;
+    if turns > 3.0 { turns - 6.0 } else { turns }
+}
+
+/// The region of the board drawn in each frame, and how it maps to pixels.
+#[derive(Copy, Clone, Debug)]
+struct View{
+    min: Vector2,
+    max: Vector2,
+    scale: f32,
+    width: usize,
+    height: usize
+}
+
+impl View{
+    /// A view covering every given hex, with a hex's margin around them.
+    fn fit(hexes: impl Iterator<Item = HexIndex>, hex_size: f32) -> View{
+        let (mut min, mut max) = (Vector2::new(f32::MAX, f32::MAX), Vector2::new(f32::MIN, f32::MIN));
+        for hex in hexes{
+            let v = Vector2::from_hex_index(hex);
+            min = Vector2::new(min.x.min(v.x), min.y.min(v.y));
+            max = Vector2::new(max.x.max(v.x), max.y.max(v.y));
+        }
+        if min.x > max.x{
+            (min, max) = (Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0));
+        }
+        let margin = Vector2::new(HEX_WIDTH, HEX_HEIGHT);
+        let (min, max) = (min - margin, max + margin);
+        let scale = hex_size / HEX_WIDTH;
+        View{ min, max, scale, width: ((max.x - min.x) * scale).ceil().max(1.0) as usize, height: ((max.y - min.y) * scale).ceil().max(1.0) as usize }
+    }
+
+    /// The pixel a point on the board is drawn at. Pixel rows go down the image, so Y is flipped.
+    fn pixel_at(&self, point: Vector2) -> Vector2{
+        Vector2::new((point.x - self.min.x) * self.scale, (self.max.y - point.y) * self.scale)
+    }
+
+    /// The point on the board at the centre of a pixel.
+    fn point_at(&self, x: usize, y: usize) -> Vector2{
+        Vector2::new((x as f32 + 0.5) / self.scale + self.min.x, self.max.y - (y as f32 + 0.5) / self.scale)
+    }
+}
+
+/// A frame being drawn, as indices into a `Palette`.
+#[derive(Clone, Debug)]
+struct Canvas{
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>
+}
+
+impl Canvas{
+    /// Fill every pixel whose centre is within a distance of a line segment.
+    fn line(&mut self, start: Vector2, end: Vector2, width: f32, colour: u8){
+        let radius = width / 2.0;
+        let along = end - start;
+        let length2 = along.length2().max(f32::EPSILON);
+        self.fill_near(Vector2::new(start.x.min(end.x), start.y.min(end.y)), Vector2::new(start.x.max(end.x), start.y.max(end.y)), radius, colour, |p| {
+            let t = (((p - start).x * along.x + (p - start).y * along.y) / length2).clamp(0.0, 1.0);
+            p.dist2(start + along * t)
+        });
+    }
+
+    fn circle(&mut self, centre: Vector2, radius: f32, colour: u8){
+        self.fill_near(centre, centre, radius, colour, |p| p.dist2(centre));
+    }
+
+    /// Fill every pixel in a box, grown by `radius`, whose centre has a squared distance less than `radius` squared.
+    fn fill_near(&mut self, min: Vector2, max: Vector2, radius: f32, colour: u8, dist2: impl Fn(Vector2) -> f32){
+        let clamp_x = |v: f32| (v.max(0.0) as usize).min(self.width);
+        let clamp_y = |v: f32| (v.max(0.0) as usize).min(self.height);
+        for y in clamp_y(min.y - radius)..clamp_y(max.y + radius + 1.0){
+            for x in clamp_x(min.x - radius)..clamp_x(max.x + radius + 1.0){
+                if dist2(Vector2::new(x as f32 + 0.5, y as f32 + 0.5)) < radius * radius{
+                    self.pixels[y * self.width + x] = colour;
+                }
+            }
+        }
+    }
+}
+
+/// The colours used in an animation, in the order they were first used.
+#[derive(Clone, Debug, Default)]
+struct Palette{
+    colours: Vec<&'static str>
+}
+
+impl Palette{
+    /// The index of a `#rrggbb` colour, adding it if it's new.
+    fn index(&mut self, colour: &'static str) -> u8{
+        match self.colours.iter().position(|c| *c == colour){
+            Some(index) => index as u8,
+            None => {
+                self.colours.push(colour);
+                (self.colours.len() - 1) as u8
+            }
+        }
+    }
+
+    /// The palette as RGB triples.
+    fn rgb(&self) -> Vec<u8>{
+        self.colours.iter()
+            .flat_map(|c| [1, 3, 5].map(|i| u8::from_str_radix(&c[i..i + 2], 16).unwrap_or(0)))
+            .collect()
+    }
+}
@@ -0,0 +1,12 @@
+use rayon::prelude::*;
+
+use crate::data::{Puzzle, Solution};
+use crate::sim::SimError;
+use crate::verify::{verify, VerificationResult};
+
+// Verifying many solutions at once across threads, for re-verifying large archives of solutions.
+
+/// Verify every puzzle and solution pair, spread across rayon's global thread pool, returning results in the same order as the pairs.
+pub fn verify_batch(pairs: &[(Puzzle, Solution)]) -> Vec<Result<VerificationResult, SimError>>{
+    pairs.par_iter().map(|(puzzle, solution)| verify(puzzle, solution)).collect()
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use omsim_rs::data::{Metrics, Puzzle, Solution};
+use omsim_rs::parse::{parse_any, FileKind};
+use omsim_rs::sim::SimOptions;
+use omsim_rs::verify::MetricsReport;
+
+use crate::check;
+
+// Verifying every solution in a directory tree at once.
+
+/// Arguments to `verify-all`.
+pub struct Options{
+    pub dir: PathBuf,
+    /// How many solutions to verify at once.
+    pub jobs: usize,
+    /// Where to write the JSON report, if anywhere.
+    pub json: Option<PathBuf>,
+    /// Settings for simulating each solution.
+    pub sim: SimOptions
+}
+
+impl Options{
+    /// Parse the arguments following `verify-all`, or None if they're malformed.
+    pub fn parse(args: &[&str]) -> Option<Options>{
+        let mut dir = None;
+        let mut jobs = 1;
+        let mut json = None;
+        let mut sim = SimOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next(){
+            match *arg{
+                "--jobs" | "-j" => jobs = args.next()?.parse().ok().filter(|j| *j > 0)?,
+                "--json" => json = Some(PathBuf::from(args.next()?)),
+                "--allow-overlap" => sim.allow_overlap = true,
+                _ if dir.is_none() && !arg.starts_with('-') => dir = Some(PathBuf::from(arg)),
+                _ => return None
+            }
+        }
+        Some(Options{ dir: dir?, jobs, json, sim })
+    }
+}
+
+/// The outcome of verifying one solution file.
+struct Outcome{
+    path: PathBuf,
+    puzzle_name: Option<String>,
+    /// The computed metrics, or why there aren't any.
+    result: Result<Metrics, String>,
+    /// The metrics recorded in the solution file, if it's solved.
+    recorded: Option<Metrics>
+}
+
+/// Find every puzzle and solution under a directory, pair them by puzzle name, and verify each solution.
+/// Prints a table of results, writes a JSON report if asked to, and fails if any solution didn't verify.
+pub fn verify_all_command(options: &Options) -> ExitCode{
+    let mut files = Vec::new();
+    collect_files(&options.dir, &mut files);
+    files.sort();
+
+    let mut puzzles: HashMap<String, Puzzle> = HashMap::new();
+    let mut solutions: Vec<(PathBuf, Solution)> = Vec::new();
+    let mut outcomes = Vec::new();
+    for path in files{
+        let parsed = fs::read(&path).map_err(|e| e.to_string()).and_then(|data| parse_any(&data).map_err(|e| e.to_string()));
+        match parsed{
+            Ok(FileKind::Puzzle(puzzle)) => {
+                puzzles.entry(puzzle.name.clone()).or_insert(puzzle);
+            }
+            Ok(FileKind::Solution(solution)) => solutions.push((path, solution)),
+            // only report files that were meant to be solutions, since anything else can't have been verified
+            Err(error) if is_solution_path(&path) => outcomes.push(Outcome{ path, puzzle_name: None, result: Err(format!("could not parse solution: {error}")), recorded: None }),
+            Err(_) => {}
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(solutions.len()));
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.min(solutions.len()).max(1){
+            scope.spawn(|| {
+                while let Some((path, solution)) = solutions.get(next.fetch_add(1, Ordering::Relaxed)){
+                    let result = match puzzles.get(&solution.puzzle_name){
+                        Some(puzzle) => check(puzzle, solution, options.sim).map(|r| r.metrics),
+                        None => Err(format!("no puzzle named {}", solution.puzzle_name))
+                    };
+                    let outcome = Outcome{ path: path.clone(), puzzle_name: Some(solution.puzzle_name.clone()), result, recorded: solution.metrics };
+                    results.lock().unwrap_or_else(|e| e.into_inner()).push(outcome);
+                }
+            });
+        }
+    });
+    outcomes.extend(results.into_inner().unwrap_or_else(|e| e.into_inner()));
+    outcomes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    print_table(&outcomes, &options.dir);
+    if let Some(json_path) = &options.json{
+        if let Err(error) = fs::write(json_path, json_report(&outcomes, &options.dir)){
+            eprintln!("could not write report {}: {error}", json_path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    if outcomes.iter().all(|o| o.result.is_ok()){
+        ExitCode::SUCCESS
+    }else{
+        ExitCode::FAILURE
+    }
+}
+
+fn is_solution_path(path: &Path) -> bool{
+    path.extension().is_some_and(|e| e == "solution")
+}
+
+/// Recursively list every puzzle and solution file under a directory, skipping anything unreadable.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>){
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten(){
+        let path = entry.path();
+        if path.is_dir(){
+            collect_files(&path, files);
+        }else if is_solution_path(&path) || path.extension().is_some_and(|e| e == "puzzle"){
+            files.push(path);
+        }
+    }
+}
+
+fn display_path(path: &Path, root: &Path) -> String{
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+fn print_table(outcomes: &[Outcome], root: &Path){
+    let paths: Vec<String> = outcomes.iter().map(|o| display_path(&o.path, root)).collect();
+    let width = paths.iter().map(String::len).max().unwrap_or(0).max("solution".len());
+    println!("{:<width$}  {:>7}  {:>6}  {:>6}  {:>6}  status", "solution", "cycles", "cost", "area", "instrs");
+    for (outcome, path) in outcomes.iter().zip(&paths){
+        match &outcome.result{
+            Ok(m) => {
+                let status = if MetricsReport::new(*m, outcome.recorded).mismatches.is_empty() { "ok" } else { "ok (differs from recorded)" };
+                println!("{path:<width$}  {:>7}  {:>6}  {:>6}  {:>6}  {status}", m.cycles, m.cost, m.area, m.instructions);
+            }
+            Err(error) => println!("{path:<width$}  {:>7}  {:>6}  {:>6}  {:>6}  failed: {error}", "-", "-", "-", "-")
+        }
+    }
+    let passed = outcomes.iter().filter(|o| o.result.is_ok()).count();
+    println!("\n{passed} of {} solutions verified", outcomes.len());
+}
+
+/// Encode the outcomes as a JSON object, by hand so that reports don't need the `serde` feature.
+fn json_report(outcomes: &[Outcome], root: &Path) -> String{
+    let passed = outcomes.iter().filter(|o| o.result.is_ok()).count();
+    let mut json = format!("{{\"total\":{},\"passed\":{passed},\"failed\":{},\"solutions\":[", outcomes.len(), outcomes.len() - passed);
+    for (i, outcome) in outcomes.iter().enumerate(){
+        if i > 0{
+            json.push(',');
+        }
+        let _ = write!(json, "{{\"path\":{},\"puzzle\":", json_string(&display_path(&outcome.path, root)));
+        match &outcome.puzzle_name{
+            Some(name) => json.push_str(&json_string(name)),
+            None => json.push_str("null")
+        }
+        match &outcome.result{
+            Ok(metrics) => {
+                let _ = write!(json, ",\"ok\":true,\"metrics\":{},\"error\":null", json_metrics(metrics));
+            }
+            Err(error) => {
+                let _ = write!(json, ",\"ok\":false,\"metrics\":null,\"error\":{}", json_string(error));
+            }
+        }
+        json.push_str(",\"recorded\":");
+        match &outcome.recorded{
+            Some(metrics) => json.push_str(&json_metrics(metrics)),
+            None => json.push_str("null")
+        }
+        json.push('}');
+    }
+    json.push_str("]}\n");
+    json
+}
+
+fn json_metrics(m: &Metrics) -> String{
+    format!("{{\"cycles\":{},\"cost\":{},\"area\":{},\"instructions\":{}}}", m.cycles, m.cost, m.area, m.instructions)
+}
+
+fn json_string(s: &str) -> String{
+    let mut out = String::from("\"");
+    for c in s.chars(){
+        match c{
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,141 @@
+use std::env;
+use std::process::ExitCode;
+use omsim_rs::data::{Puzzle, Solution};
+use omsim_rs::metric::MetricExpression;
+use omsim_rs::sim::{Sim, SimOptions};
+use omsim_rs::validate::{validate, ValidationError};
+use omsim_rs::verify::{verify_with_options, MetricMismatch, MetricsReport, VerificationResult, DEFAULT_CYCLE_LIMIT};
+
+mod batch;
+
+const USAGE: &str = "usage: omsim verify [--allow-overlap] <puzzle file> <solution file>
+       omsim verify-all [--jobs <threads>] [--json <report file>] [--allow-overlap] <directory>
+       omsim eval <puzzle file> <solution file> <metric>...
+       omsim inspect <puzzle file> <solution file>";
+
+fn main() -> ExitCode{
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice(){
+        ["verify", puzzle, solution] => verify_command(puzzle, solution, SimOptions::default()),
+        ["verify", "--allow-overlap", puzzle, solution] => verify_command(puzzle, solution, SimOptions{ allow_overlap: true, strict_drops: false }),
+        ["verify-all", rest @ ..] => match batch::Options::parse(rest){
+            Some(options) => batch::verify_all_command(&options),
+            None => usage()
+        },
+        ["eval", puzzle, solution, metrics @ ..] if !metrics.is_empty() => eval_command(puzzle, solution, metrics),
+        #[cfg(feature = "schema")]
+        ["schema", "puzzle"] => print_schema(omsim_rs::schema::puzzle_schema()),
+        #[cfg(feature = "schema")]
+        ["schema", "solution"] => print_schema(omsim_rs::schema::solution_schema()),
+        ["inspect", puzzle, solution] => inspect_command(puzzle, solution),
+        _ => usage()
+    }
+}
+
+fn usage() -> ExitCode{
+    eprintln!("{USAGE}");
+    ExitCode::from(2)
+}
+
+/// Parse both files, or print why one couldn't be parsed.
+fn load(puzzle_path: &str, solution_path: &str) -> Option<(Puzzle, Solution)>{
+    let puzzle = Puzzle::from_path(puzzle_path).map_err(|e| eprintln!("could not parse puzzle {puzzle_path}: {e}")).ok()?;
+    let solution = Solution::from_path(solution_path).map_err(|e| eprintln!("could not parse solution {solution_path}: {e}")).ok()?;
+    Some((puzzle, solution))
+}
+
+/// Check that a solution is legal, then simulate it to completion.
+/// Overlapping parts are only an error if the options don't allow them.
+fn check(puzzle: &Puzzle, solution: &Solution, options: SimOptions) -> Result<VerificationResult, String>{
+    let mut errors = validate(solution, puzzle);
+    if options.allow_overlap{
+        errors.retain(|e| !matches!(e, ValidationError::Overlap(_)));
+    }
+    if !errors.is_empty(){
+        return Err(errors.iter().map(|e| format!("invalid solution: {e:?}")).collect::<Vec<_>>().join("; "));
+    }
+    verify_with_options(puzzle, solution, DEFAULT_CYCLE_LIMIT, options).map_err(|e| e.to_string())
+}
+
+/// Check and simulate a solution, printing its metrics. Fails if the solution is illegal or doesn't complete.
+/// Metrics that differ from the ones recorded in the solution file are reported, but aren't a failure.
+fn verify_command(puzzle_path: &str, solution_path: &str, options: SimOptions) -> ExitCode{
+    let Some((puzzle, solution)) = load(puzzle_path, solution_path) else { return ExitCode::FAILURE };
+    let result = match check(&puzzle, &solution, options){
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let metrics = result.metrics;
+    println!("cycles: {}", metrics.cycles);
+    println!("cost: {}", metrics.cost);
+    println!("area: {}", metrics.area);
+    println!("instructions: {}", metrics.instructions);
+    println!("height: {}", result.extended.height);
+    println!("width: {}", result.extended.width);
+    for MetricMismatch{ metric, recorded, computed } in MetricsReport::new(metrics, solution.metrics).mismatches{
+        let name = metric.name();
+        eprintln!("note: computed {name} {computed} differs from recorded {name} {recorded}");
+    }
+    ExitCode::SUCCESS
+}
+
+/// Check and simulate a solution, then print the value of each metric expression on its own line, in order.
+/// Overlapping parts are allowed, so that they can be measured with the `overlap` metric.
+fn eval_command(puzzle_path: &str, solution_path: &str, metrics: &[&str]) -> ExitCode{
+    let expressions = match metrics.iter().map(|m| MetricExpression::parse(m)).collect::<Result<Vec<_>, _>>(){
+        Ok(expressions) => expressions,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some((puzzle, solution)) = load(puzzle_path, solution_path) else { return ExitCode::FAILURE };
+    let result = match check(&puzzle, &solution, SimOptions{ allow_overlap: true, strict_drops: false }){
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for expression in &expressions{
+        match expression.evaluate(&puzzle, &solution, &result){
+            Ok(value) => println!("{value}"),
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print a JSON Schema for the JSON encoding of puzzles or solutions.
+#[cfg(feature = "schema")]
+fn print_schema(schema: serde_json::Value) -> ExitCode{
+    println!("{schema:#}");
+    ExitCode::SUCCESS
+}
+
+/// Print everything parsed from a puzzle and solution, and the simulation they start as.
+fn inspect_command(puzzle_path: &str, solution_path: &str) -> ExitCode{
+    let Some((puzzle, sol)) = load(puzzle_path, solution_path) else { return ExitCode::FAILURE };
+    println!("it's like {puzzle:?}\n");
+
+    println!("and then!!");
+    println!("they're like {sol:?}\n");
+
+    match Sim::create(&puzzle, &sol){
+        Ok(sim) => {
+            println!("and now we set the empty stage of {sim:?}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("could not simulate solution: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::data::{Atom, Bond, BondType, HexIndex, HexMap, Instruction, Metrics, Molecule, Part, PartCategory, PartType, Permissions, ProductionInfo, Puzzle, Solution};
+
+// Builders for constructing puzzles and solutions in code.
+
+/// Builds a `Puzzle` step by step.
+/// Puzzles start with no reagents or products, a product multiplier of 1, and the permissions of a newly-created puzzle.
+#[derive(Clone, Debug)]
+pub struct PuzzleBuilder{
+    puzzle: Puzzle
+}
+
+impl PuzzleBuilder{
+    pub fn new(name: impl Into<String>) -> PuzzleBuilder{
+        PuzzleBuilder{
+            puzzle: Puzzle{
+                name: name.into(),
+                creator_id: 0,
+                reagents: Vec::new(),
+                products: Vec::new(),
+                product_multiplier: 1,
+                permissions: Permissions::DEFAULT_PERMISSIONS,
+                production_info: None
+            }
+        }
+    }
+
+    /// Set the Steam ID of the puzzle's creator.
+    pub fn creator(mut self, creator_id: u64) -> PuzzleBuilder{
+        self.puzzle.creator_id = creator_id;
+        self
+    }
+
+    /// Add an input molecule.
+    pub fn reagent(mut self, molecule: Molecule) -> PuzzleBuilder{
+        self.puzzle.reagents.push(molecule);
+        self
+    }
+
+    /// Add an output molecule.
+    pub fn product(mut self, molecule: Molecule) -> PuzzleBuilder{
+        self.puzzle.products.push(molecule);
+        self
+    }
+
+    /// Set the multiplier for the number of output molecules required.
+    pub fn product_multiplier(mut self, multiplier: i32) -> PuzzleBuilder{
+        self.puzzle.product_multiplier = multiplier;
+        self
+    }
+
+    /// Enable some permissions, in addition to those already enabled.
+    pub fn allow(mut self, permissions: Permissions) -> PuzzleBuilder{
+        self.puzzle.permissions |= permissions;
+        self
+    }
+
+    /// Disable some permissions.
+    pub fn forbid(mut self, permissions: Permissions) -> PuzzleBuilder{
+        self.puzzle.permissions &= !permissions;
+        self
+    }
+
+    /// Replace the enabled permissions entirely.
+    pub fn permissions(mut self, permissions: Permissions) -> PuzzleBuilder{
+        self.puzzle.permissions = permissions;
+        self
+    }
+
+    /// Make this a production puzzle with the given chambers and conduits.
+    pub fn production(mut self, production_info: ProductionInfo) -> PuzzleBuilder{
+        self.puzzle.production_info = Some(production_info);
+        self
+    }
+
+    pub fn build(self) -> Puzzle{
+        self.puzzle
+    }
+}
+
+/// Builds a `Molecule` an atom and bond at a time, checking that every bond connects two atoms.
+/// Positions are given as `(q, r)` pairs.
+#[derive(Clone, Debug, Default)]
+pub struct MoleculeBuilder{
+    atoms: HexMap<Atom>,
+    bonds: Vec<Bond>,
+    error: Option<&'static str>
+}
+
+impl MoleculeBuilder{
+    pub fn new() -> MoleculeBuilder{
+        MoleculeBuilder::default()
+    }
+
+    /// Add an atom, failing if there's already one at that position.
+    pub fn atom(mut self, q: i32, r: i32, atom: Atom) -> MoleculeBuilder{
+        if self.atoms.insert(HexIndex::new(q, r), atom).is_some(){
+            self.error.get_or_insert("two atoms given at the same position");
+        }
+        self
+    }
+
+    /// Add a normal bond.
+    pub fn bond(self, start: (i32, i32), end: (i32, i32)) -> MoleculeBuilder{
+        self.bond_of(BondType::Normal, start, end)
+    }
+
+    /// Add a triplex bond with all three colours.
+    pub fn triplex(self, start: (i32, i32), end: (i32, i32)) -> MoleculeBuilder{
+        self.bond_of(BondType::Triplex{ red: true, black: true, yellow: true }, start, end)
+    }
+
+    /// Add a bond of any type.
+    pub fn bond_of(mut self, ty: BondType, start: (i32, i32), end: (i32, i32)) -> MoleculeBuilder{
+        if start == end{
+            self.error.get_or_insert("bond given from an atom to itself");
+        }
+        self.bonds.push(Bond{ start: HexIndex::new(start.0, start.1), end: HexIndex::new(end.0, end.1), ty });
+        self
+    }
+
+    /// Finish building, failing if atoms overlapped or any bond is missing an atom at either end.
+    /// Bonds may be given before the atoms they connect.
+    pub fn build(self) -> Result<Molecule, &'static str>{
+        if let Some(error) = self.error{
+            return Err(error);
+        }
+        if self.bonds.iter().any(|b| !self.atoms.contains_key(&b.start) || !self.atoms.contains_key(&b.end)){
+            return Err("bond given between positions without atoms");
+        }
+        Ok(Molecule{ atoms: self.atoms, bonds: self.bonds.into_iter().collect() })
+    }
+}
+
+/// Builds a `Molecule` from atoms written as `(q, r): Atom`, then after a `;`, bonds written as `(q, r) - (q, r)`,
+/// using `=` instead of `-` for triplex bonds. For example, `molecule![(0, 0): Salt, (1, 0): Air; (0, 0) - (1, 0)]`.
+/// Panics if two atoms share a position or a bond is missing an atom; use `MoleculeBuilder` to handle these as errors.
+#[macro_export]
+macro_rules! molecule{
+    ($(($q:expr, $r:expr): $atom:ident),* $(,)? $(; $(($sq:expr, $sr:expr) $op:tt ($eq:expr, $er:expr)),* $(,)?)?) => {{
+        let builder = $crate::builder::MoleculeBuilder::new()$(.atom($q, $r, $crate::data::Atom::$atom))*;
+        $($(let builder = $crate::molecule!(@bond builder $op ($sq, $sr) ($eq, $er));)*)?
+        builder.build().expect("invalid molecule")
+    }};
+    (@bond $builder:ident - $start:tt $end:tt) => { $builder.bond($start, $end) };
+    (@bond $builder:ident = $start:tt $end:tt) => { $builder.triplex($start, $end) };
+}
+
+impl Molecule{
+    /// Start building a molecule an atom and bond at a time.
+    pub fn builder() -> MoleculeBuilder{
+        MoleculeBuilder::new()
+    }
+}
+
+/// Builds a `Solution` step by step, filling in the fields of each part that don't apply to it.
+/// Parts are added in order, and arms are numbered in the order they're added.
+#[derive(Clone, Debug)]
+pub struct SolutionBuilder{
+    solution: Solution,
+    /// Index of each arm in the solution's parts, in the order they were added.
+    arms: Vec<usize>,
+    error: Option<&'static str>
+}
+
+impl SolutionBuilder{
+    pub fn new(name: impl Into<String>, puzzle_name: impl Into<String>) -> SolutionBuilder{
+        SolutionBuilder{
+            solution: Solution{ name: name.into(), puzzle_name: puzzle_name.into(), metrics: None, parts: Vec::new() },
+            arms: Vec::new(),
+            error: None
+        }
+    }
+
+    /// Set the metrics recorded in the solution.
+    pub fn metrics(mut self, metrics: Metrics) -> SolutionBuilder{
+        self.solution.metrics = Some(metrics);
+        self
+    }
+
+    /// Add an already-constructed part.
+    pub fn part(mut self, part: Part) -> SolutionBuilder{
+        if part.ty.category() == PartCategory::Arm{
+            self.arms.push(self.solution.parts.len());
+        }
+        self.solution.parts.push(part);
+        self
+    }
+
+    /// Add a single-gripper arm.
+    pub fn arm(self, pos: HexIndex, rotation: i32, length: i32) -> SolutionBuilder{
+        self.arm_of(PartType::Arm, pos, rotation, length)
+    }
+
+    /// Add an arm, piston, or Van Berlo's wheel of the given type.
+    pub fn arm_of(self, ty: PartType, pos: HexIndex, rotation: i32, length: i32) -> SolutionBuilder{
+        let arm_number = self.arms.len() as i32;
+        self.part(Part{ arm_number, arm_length: length, ..blank_part(ty, pos, rotation) })
+    }
+
+    /// Add a glyph.
+    pub fn glyph(self, ty: PartType, pos: HexIndex, rotation: i32) -> SolutionBuilder{
+        self.part(blank_part(ty, pos, rotation))
+    }
+
+    /// Add an input for the reagent with the given index.
+    pub fn input(self, index: i32, pos: HexIndex, rotation: i32) -> SolutionBuilder{
+        self.part(Part{ index, ..blank_part(PartType::Input, pos, rotation) })
+    }
+
+    /// Add an output for the product with the given index.
+    pub fn output(self, index: i32, pos: HexIndex, rotation: i32) -> SolutionBuilder{
+        self.part(Part{ index, ..blank_part(PartType::Output, pos, rotation) })
+    }
+
+    /// Add a track covering the given board hexes, in order. The track is placed at the first hex.
+    pub fn track(self, hexes: &[HexIndex]) -> SolutionBuilder{
+        let pos = hexes.first().cloned().unwrap_or_default();
+        self.part(Part{ track_hexes: hexes.iter().map(|h| *h - pos).collect(), ..blank_part(PartType::Track, pos, 0) })
+    }
+
+    /// Set the tape of an arm, given by the order arms were added, from a string of instruction IDs as used in solution files.
+    /// Each character is placed at its index in the string, and spaces are left blank.
+    pub fn instructions(mut self, arm_index: usize, tape: &str) -> SolutionBuilder{
+        let Some(&part_index) = self.arms.get(arm_index) else {
+            self.error.get_or_insert("instructions given for an arm that doesn't exist");
+            return self;
+        };
+        let mut instructions = Vec::new();
+        for (index, id) in tape.bytes().enumerate(){
+            match Instruction::from_id(id){
+                Some(Instruction::Blank) => {}
+                Some(instruction) => instructions.push((instruction, index as i32)),
+                None => {
+                    self.error.get_or_insert("invalid instruction ID in tape");
+                    return self;
+                }
+            }
+        }
+        self.solution.parts[part_index].instructions = instructions;
+        self
+    }
+
+    /// Finish building, failing if any tape given to `instructions` was invalid.
+    pub fn build(self) -> Result<Solution, &'static str>{
+        match self.error{
+            Some(error) => Err(error),
+            None => Ok(self.solution)
+        }
+    }
+}
+
+fn blank_part(ty: PartType, pos: HexIndex, rotation: i32) -> Part{
+    Part{
+        ty,
+        pos,
+        rotation,
+        arm_number: 0,
+        arm_length: 1,
+        index: 0,
+        conduit_index: 0,
+        track_hexes: Vec::new(),
+        conduit_hexes: Vec::new(),
+        instructions: Vec::new()
+    }
+}
@@ -0,0 +1,17 @@
+use bincode::{Decode, Encode};
+
+// Compact binary encoding of parsed data, for caching between runs. This is not the game's file format.
+
+/// Encode a parsed value (such as a `Puzzle` or `Solution`) for caching.
+pub fn to_cache_bytes<T: Encode>(value: &T) -> Vec<u8>{
+    bincode::encode_to_vec(value, bincode::config::standard()).expect("encoding to a vec should not fail")
+}
+
+/// Decode a value encoded with `to_cache_bytes`. Fails if the data is truncated, has trailing bytes, or was encoded from a different type.
+pub fn from_cache_bytes<T: Decode<()>>(data: &[u8]) -> Result<T, &'static str>{
+    let (value, read) = bincode::decode_from_slice(data, bincode::config::standard()).map_err(|_| "invalid cached data")?;
+    if read != data.len(){
+        return Err("trailing bytes after cached data");
+    }
+    Ok(value)
+}
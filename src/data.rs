@@ -1,14 +1,19 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::Into;
 use std::fmt::Debug;
-use std::ops::{Add, AddAssign, Sub, SubAssign};
-use bitflags::bitflags;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use bitflags::{bitflags, Flags};
+
+pub use crate::hex_map::HexMap;
 
 // Puzzle and solution files
 
 /// A puzzle, as parsed from a puzzle file.
 /// No attempt is made to check for invalid puzzles. In particular, they may have no inputs or outputs, no enabled parts, or be unsolveable.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Puzzle{
     /// String ID.
     pub name: String,
@@ -30,6 +35,9 @@ pub struct Puzzle{
 /// A solution to a puzzle, as parsed from a solution file.
 /// No attempt is made to check for invalid solutions. In particular, parts may have invalid state (like sizes >3).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Solution{
     /// Display name.
     pub name: String,
@@ -42,8 +50,113 @@ pub struct Solution{
     pub parts: Vec<Part>
 }
 
+impl Solution{
+    /// The total cost of every part in this solution, computed from the part cost table rather than the recorded metrics.
+    pub fn cost(&self) -> i32{
+        self.parts.iter().map(Part::cost).sum()
+    }
+
+    /// This solution with every part moved by an offset.
+    pub fn translated(&self, by: HexIndex) -> Solution{
+        Solution{ parts: self.parts.iter().map(|p| p.translated(by)).collect(), ..self.clone() }
+    }
+
+    /// This solution rotated around the origin by a number of rotation steps, which may be negative.
+    /// Rotated solutions behave the same as long as inputs and outputs are rotated with them.
+    pub fn rotated(&self, steps: i32) -> Solution{
+        Solution{ parts: self.parts.iter().map(|p| p.rotated(steps)).collect(), ..self.clone() }
+    }
+
+    /// This solution translated so that the bounding box of its parts' footprints, in Q and R, is centred on the origin.
+    /// Inputs and outputs are counted by their position only. When the box has an even width or height, the centre is rounded down.
+    pub fn normalized(&self) -> Solution{
+        let hexes: Vec<HexIndex> = self.parts.iter()
+            .flat_map(|p| std::iter::once(p.pos).chain(p.footprint()))
+            .collect();
+        let (Some(min_q), Some(max_q)) = (hexes.iter().map(|h| h.q).min(), hexes.iter().map(|h| h.q).max()) else { return self.clone() };
+        let (min_r, max_r) = (hexes.iter().map(|h| h.r).min().unwrap(), hexes.iter().map(|h| h.r).max().unwrap());
+        self.translated(-HexIndex{ q: (min_q + max_q).div_euclid(2), r: (min_r + max_r).div_euclid(2) })
+    }
+
+    /// This solution with every arm's instructions shifted so that the earliest of them is at index 0.
+    /// Simulations start from the earliest instruction of any arm, so this behaves the same, and is useful before comparing or fingerprinting solutions.
+    pub fn normalized_tapes(&self) -> Solution{
+        let is_arm = |p: &Part| p.ty.category() == PartCategory::Arm;
+        let Some(start) = self.parts.iter().filter(|p| is_arm(p)).flat_map(|p| p.instructions.iter().map(|(_, idx)| *idx)).min() else { return self.clone() };
+        let mut result = self.clone();
+        for part in result.parts.iter_mut().filter(|p| is_arm(p)){
+            for (_, idx) in &mut part.instructions{
+                // spans too wide to fit can't be simulated anyway, and are left where they are
+                let Some(shifted) = idx.checked_sub(start) else { return self.clone() };
+                *idx = shifted;
+            }
+        }
+        result
+    }
+
+    /// This solution reflected across the Q axis, which solves the reflection of its puzzle. See `Part::mirrored`.
+    pub fn mirrored(&self) -> Solution{
+        Solution{ parts: self.parts.iter().map(Part::mirrored).collect(), ..self.clone() }
+    }
+
+    /// The number of instructions in this solution, counted by the game's rules rather than taken from the recorded metrics.
+    /// Instructions on parts other than arms are ignored.
+    pub fn instructions(&self) -> i32{
+        self.parts.iter()
+            .filter(|p| p.ty.category() == PartCategory::Arm)
+            .map(|p| p.instructions.iter().filter(|(instr, _)| instr.is_counted()).count() as i32)
+            .sum()
+    }
+}
+
+/// A puzzle whose name borrows from the data it was parsed from, to avoid allocating strings. See `parse::parse_puzzle_ref`.
+#[derive(Debug, Clone)]
+pub struct PuzzleRef<'a>{
+    pub name: &'a str,
+    pub creator_id: u64,
+    pub reagents: Vec<Molecule>,
+    pub products: Vec<Molecule>,
+    pub product_multiplier: i32,
+    pub permissions: Permissions,
+    pub production_info: Option<ProductionInfo>
+}
+
+impl PuzzleRef<'_>{
+    /// Copy the borrowed strings into an owned `Puzzle`.
+    pub fn into_owned(self) -> Puzzle{
+        Puzzle{
+            name: self.name.to_owned(),
+            creator_id: self.creator_id,
+            reagents: self.reagents,
+            products: self.products,
+            product_multiplier: self.product_multiplier,
+            permissions: self.permissions,
+            production_info: self.production_info
+        }
+    }
+}
+
+/// A solution whose names borrow from the data it was parsed from, to avoid allocating strings. See `parse::parse_solution_ref`.
+#[derive(Debug, Clone)]
+pub struct SolutionRef<'a>{
+    pub name: &'a str,
+    pub puzzle_name: &'a str,
+    pub metrics: Option<Metrics>,
+    pub parts: Vec<Part>
+}
+
+impl SolutionRef<'_>{
+    /// Copy the borrowed strings into an owned `Solution`.
+    pub fn into_owned(self) -> Solution{
+        Solution{ name: self.name.to_owned(), puzzle_name: self.puzzle_name.to_owned(), metrics: self.metrics, parts: self.parts }
+    }
+}
+
 /// Metrics that a solved solution may have.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Metrics{
     pub cycles: i32,
     pub cost: i32,
@@ -100,20 +213,73 @@ bitflags! {
     }
 }
 
+impl Permissions{
+    /// The bits that are set but don't belong to any named permission. They're kept, but don't enable anything.
+    pub fn unknown_bits(self) -> u64{
+        let named = Permissions::FLAGS.iter().filter(|flag| flag.is_named()).fold(0, |bits, flag| bits | flag.value().bits());
+        self.bits() & !named
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl bincode::Encode for Permissions{
+    fn encode<E: bincode::enc::Encoder>(&self, encoder: &mut E) -> Result<(), bincode::error::EncodeError>{
+        self.bits().encode(encoder)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<Context> bincode::Decode<Context> for Permissions{
+    fn decode<D: bincode::de::Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, bincode::error::DecodeError>{
+        Ok(Permissions::from_bits_retain(u64::decode(decoder)?))
+    }
+}
+
+#[cfg(feature = "bincode")]
+bincode::impl_borrow_decode!(Permissions);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Permissions{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        self.bits().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Permissions{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>{
+        Ok(Permissions::from_bits_retain(u64::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Permissions{
+    fn schema_name() -> std::borrow::Cow<'static, str>{
+        "Permissions".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema{
+        // serialized as the raw bits, like in puzzle files
+        u64::json_schema(generator)
+    }
+}
+
 impl Puzzle{
+    /// This puzzle with every reagent and product reflected across the Q axis. See `Solution::mirrored`.
+    pub fn mirrored(&self) -> Puzzle{
+        Puzzle{
+            reagents: self.reagents.iter().map(Molecule::mirrored).collect(),
+            products: self.products.iter().map(Molecule::mirrored).collect(),
+            ..self.clone()
+        }
+    }
 
+    /// A copy of a solution to this puzzle, after checking that every input and output refers to a reagent or product the puzzle has.
+    /// Parts the puzzle doesn't allow are kept; see `Solution::check_permissions` to find them.
     pub fn clean_solution(&self, solution: &Solution) -> Result<Solution, &'static str>{
-        // check puzzle name // don't actually, it's implicit in filenames. check filenames?
-        // if self.name != solution.puzzle_name{
-        //     return Err("solution is for the wrong puzzle");
-        // }
-
-        // check that there are no IOOB inputs/outputs
         for part in &solution.parts{
-            if part.ty == PartType::Input || part.ty == PartType::Output || part.ty == PartType::PolymerOutput{
-                if part.index < 0 {
-                    return Err("solution contains input/output with negative index");
-                }
+            if (part.ty == PartType::Input || part.ty == PartType::Output || part.ty == PartType::PolymerOutput) && part.index < 0{
+                return Err("solution contains input/output with negative index");
             }
             if part.ty == PartType::Input && (part.index as usize) >= self.reagents.len(){
                 return Err("solution contains input with out-of-bounds index");
@@ -122,18 +288,18 @@ impl Puzzle{
                 return Err("solution contains output with out-of-bounds index");
             }
         }
-        // remove forbidden parts
-        let cleaned = solution.clone();
-        // TODO
-        Ok(cleaned)
+        Ok(solution.clone())
     }
 }
 
 // Production info
 
 /// Information relevant only to production puzzles.
-/// Purely visual information, like vial placement, is not stored.
+/// Purely visual information, like vial placement, is not stored, but can be kept by parsing with `parse::ParseOptions::lossless`.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProductionInfo{
     /// Whether the inputs and outputs must be placed in different chambers.
     pub isolation: bool,
@@ -145,8 +311,27 @@ pub struct ProductionInfo{
     pub conduits: Vec<Conduit>
 }
 
+impl ProductionInfo{
+    /// Index of the first chamber covering a hex, if any chamber does.
+    pub fn chamber_at(&self, hex: HexIndex) -> Option<usize>{
+        self.chambers.iter().position(|c| c.contains(hex))
+    }
+
+    /// The hexes just outside the chambers, where their walls are. Atoms and grippers can't pass through these.
+    pub fn wall_hexes(&self) -> HashSet<HexIndex>{
+        self.chambers.iter()
+            .flat_map(Chamber::hexes)
+            .flat_map(HexIndex::neighbors)
+            .filter(|h| self.chamber_at(*h).is_none())
+            .collect()
+    }
+}
+
 /// A chamber/cabinet that parts may be placed within in production puzzles.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Chamber{
     /// Position on the board, as an offset from the origin (within u8,u8 range).
     pub pos: HexIndex,
@@ -154,10 +339,28 @@ pub struct Chamber{
     pub ty: ChamberType
 }
 
+impl Chamber{
+    /// The hexes inside this chamber, on the board.
+    pub fn hexes(&self) -> impl Iterator<Item = HexIndex> + '_{
+        let (width, height) = self.ty.size();
+        (0..height).flat_map(move |r| (0..width).map(move |q| HexIndex{ q: q - r.div_euclid(2), r } + self.pos))
+    }
+
+    /// Whether a hex is inside this chamber.
+    pub fn contains(&self, hex: HexIndex) -> bool{
+        let (width, height) = self.ty.size();
+        let offset = hex - self.pos;
+        (0..height).contains(&offset.r) && (0..width).contains(&(offset.q + offset.r.div_euclid(2)))
+    }
+}
+
 /// A conduit defined by a puzzle.
 /// Note that these are only used when creating a new solution to a puzzle; solutions may have any number and layout of conduits.
 /// Since the game does not allow moving conduits between chambers, conduits store only starting positions and not chamber indices.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Conduit{
     /// Default starting position of one end of the conduit.
     pub pos_a: HexIndex,
@@ -169,6 +372,9 @@ pub struct Conduit{
 
 /// Supported chamber sizes.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ChamberType{
     Small, SmallWide, SmallWider,
     Medium, MediumWide,
@@ -188,15 +394,79 @@ impl ChamberType{
             _ => return None
         })
     }
+
+    /// The number of hexes in each row of a chamber of this type, and its number of rows.
+    /// Rows go up the R axis from the chamber's position, shifting back every other row so that the chamber's sides are vertical.
+    pub const fn size(self) -> (i32, i32){
+        match self{
+            ChamberType::Small => (4, 3),
+            ChamberType::SmallWide => (5, 3),
+            ChamberType::SmallWider => (6, 3),
+            ChamberType::Medium => (4, 4),
+            ChamberType::MediumWide => (5, 4),
+            ChamberType::Large => (6, 5)
+        }
+    }
+
+    pub fn name(self) -> &'static str{
+        match self{
+            ChamberType::Small => "Small",
+            ChamberType::SmallWide => "SmallWide",
+            ChamberType::SmallWider => "SmallWider",
+            ChamberType::Medium => "Medium",
+            ChamberType::MediumWide => "MediumWide",
+            ChamberType::Large => "Large"
+        }
+    }
+}
+
+// Lossless round-tripping
+
+/// Parts of a file that don't affect anything this crate models, kept by parsing with `parse::ParseOptions::lossless`
+/// so that `encode::write_puzzle_lossless` and `encode::write_solution_lossless` can reproduce the file exactly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RawExtras{
+    /// Whether a production puzzle's layout is drawn shrunk on the left.
+    pub shrink_left: bool,
+    /// Whether a production puzzle's layout is drawn shrunk on the right.
+    pub shrink_right: bool,
+    /// The vials drawn around a production puzzle's chambers.
+    pub vials: Vec<Vial>,
+    /// The order atom positions and bonds appear in for each reagent, then each product, since molecules don't keep one.
+    pub molecule_orders: Vec<(Vec<HexIndex>, Vec<Bond>)>,
+    /// Any bytes after the end of the file's known contents.
+    pub trailing: Vec<u8>
+}
+
+/// A decorative vial in a production puzzle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Vial{
+    /// Position on the board, within i8 range.
+    pub pos: HexIndex,
+    /// Whether the vial is drawn above its position, rather than below.
+    pub top: bool,
+    /// How many vials are drawn in the stack.
+    pub count: i32
 }
 
 // Atoms and molecules
 
 /// A molecule, or collection of bonded atoms that move together.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Molecule{
     /// The atoms in this molecule by relative position.
-    pub atoms: HashMap<HexIndex, Atom>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::atom_map"))]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<(HexIndex, Atom)>"))]
+    pub atoms: HexMap<Atom>,
     /// The bonds between atoms.
     pub bonds: HashSet<Bond>
 }
@@ -204,7 +474,7 @@ pub struct Molecule{
 impl Molecule{
     pub fn mapped_positions(&self, f: impl Fn(HexIndex) -> HexIndex) -> Molecule{
         // it's just easier to copy it
-        let mut next_atoms = HashMap::with_capacity(self.atoms.len());
+        let mut next_atoms = HexMap::new();
         let mut next_bonds = HashSet::with_capacity(self.bonds.len());
         for (pos, atom) in &self.atoms{
             next_atoms.insert(f(*pos), *atom);
@@ -215,22 +485,159 @@ impl Molecule{
         Molecule{ atoms: next_atoms, bonds: next_bonds }
     }
 
+    /// Move every atom and bond by an offset.
     pub fn translated(&self, by: HexIndex) -> Molecule{
         self.mapped_positions(|pos| pos + by)
     }
 
+    /// Rotate every atom and bond around a point.
     pub fn rotated(&self, around: HexIndex, by: HexRotation) -> Molecule{
         self.mapped_positions(|pos| pos.rotated(around, by))
     }
 
+    /// Rotate every atom and bond around a point by a number of rotation steps, which may be negative.
+    pub fn rotate60_about(&self, origin: HexIndex, steps: i32) -> Molecule{
+        self.rotated(origin, HexRotation::from_signed(steps))
+    }
+
+    /// Reflect every atom and bond across the Q axis.
+    /// Triplex bonds keep their colours, since reflection doesn't change which atoms they connect.
+    pub fn mirrored(&self) -> Molecule{
+        self.mapped_positions(HexIndex::mirrored)
+    }
+
     pub fn contains_pos(&self, pos: HexIndex) -> bool{
         self.atoms.contains_key(&pos)
     }
+
+    /// The groups of atoms that are connected by bonds, each as a separate molecule with the bonds between its atoms.
+    /// A connected molecule gives a single component equal to itself, and an empty one gives none.
+    pub fn connected_components(&self) -> Vec<Molecule>{
+        let mut remaining: HexMap<()> = self.atoms.keys().copied().collect();
+        let mut components = Vec::new();
+        for &start in self.atoms.keys(){
+            if remaining.remove(&start).is_none(){
+                continue;
+            }
+            let mut atoms = HexMap::new();
+            let mut stack = vec![start];
+            while let Some(pos) = stack.pop(){
+                atoms.insert(pos, self.atoms[&pos]);
+                for bond in &self.bonds{
+                    let other = if bond.start == pos { bond.end } else if bond.end == pos { bond.start } else { continue };
+                    if remaining.remove(&other).is_some(){
+                        stack.push(other);
+                    }
+                }
+            }
+            let bonds = self.bonds.iter().filter(|b| atoms.contains_key(&b.start)).cloned().collect();
+            components.push(Molecule{ atoms, bonds });
+        }
+        components
+    }
+
+    /// The number of atoms of each element in this molecule.
+    pub fn atom_counts(&self) -> HashMap<Atom, usize>{
+        let mut counts = HashMap::new();
+        for atom in self.atoms.values(){
+            *counts.entry(*atom).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// The smallest and largest Q and R of any atom, as the corners `(min, max)`, or `None` for an empty molecule.
+    pub fn bounding_box(&self) -> Option<(HexIndex, HexIndex)>{
+        let mut positions = self.atoms.keys();
+        let first = *positions.next()?;
+        Some(positions.fold((first, first), |(min, max), pos| (
+            HexIndex{ q: min.q.min(pos.q), r: min.r.min(pos.r) },
+            HexIndex{ q: max.q.max(pos.q), r: max.r.max(pos.r) }
+        )))
+    }
+
+    /// The number of bonds connected to the atom at a position, counting triplex bonds once.
+    pub fn bond_degree(&self, pos: HexIndex) -> usize{
+        self.bonds.iter().filter(|b| b.start == pos || b.end == pos).count()
+    }
+
+    /// Whether this molecule is the repeating unit of a polymer, with a repeat atom marking where the next unit starts.
+    pub fn is_polymer(&self) -> bool{
+        self.atoms.values().any(|a| *a == Atom::Repeat)
+    }
+
+    /// This polymer unit extended into a chain of `repetitions` units, such as `POLYMER_REPETITIONS`.
+    /// The repeat atom gives the offset from each unit to the next, and bonds to it join the atom at the origin of the next unit.
+    /// Bonds to the repeat atom of the last unit are left out. Molecules without exactly one repeat atom are returned unchanged.
+    pub fn extended(&self, repetitions: i32) -> Molecule{
+        let mut repeats = self.atoms.iter().filter(|(_, a)| **a == Atom::Repeat).map(|(pos, _)| *pos);
+        let (Some(step), None) = (repeats.next(), repeats.next()) else { return self.clone() };
+        let mut atoms = HexMap::new();
+        let mut bonds = HashSet::with_capacity(self.bonds.len() * repetitions.max(0) as usize);
+        for unit in 0..repetitions{
+            let offset = step * unit;
+            atoms.extend(self.atoms.iter().filter(|(pos, _)| **pos != step).map(|(pos, atom)| (*pos + offset, *atom)));
+            let last = unit == repetitions - 1;
+            bonds.extend(self.bonds.iter()
+                .filter(|b| !last || (b.start != step && b.end != step))
+                .map(|b| Bond{ start: b.start + offset, end: b.end + offset, ty: b.ty }));
+        }
+        Molecule{ atoms, bonds }
+    }
+
+    /// A standard placement of this molecule, so that molecules that differ only by translation and rotation have equal canonical forms.
+    /// Bonds are also stored with a consistent direction, so that bonds that only differ by which end is the start become equal.
+    pub fn canonicalize(&self) -> Molecule{
+        let (_, rotation, offset) = self.canonical_placement();
+        let placed = self.rotated(HexIndex::default(), rotation).translated(offset);
+        Molecule{
+            atoms: placed.atoms,
+            bonds: placed.bonds.into_iter().map(|b| if (b.start.r, b.start.q) <= (b.end.r, b.end.q) { b } else { Bond{ start: b.end, end: b.start, ty: b.ty } }).collect()
+        }
+    }
+
+    /// Whether two molecules have the same atoms and bonds, up to translation and rotation.
+    pub fn matches(&self, other: &Molecule) -> bool{
+        self.atoms.len() == other.atoms.len()
+            && self.bonds.len() == other.bonds.len()
+            && self.canonical_placement().0 == other.canonical_placement().0
+    }
+
+    /// The rotation around the origin and then translation that give the smallest shape key, along with that key.
+    fn canonical_placement(&self) -> (ShapeKey, HexRotation, HexIndex){
+        (0..6u8)
+            .map(|turns| {
+                let rotation = HexRotation::from_unsigned(turns);
+                let rotated = self.rotated(HexIndex::default(), rotation);
+                let offset = rotated.atoms.keys().map(|p| (p.r, p.q)).min().map_or(HexIndex::default(), |(r, q)| HexIndex{ q: -q, r: -r });
+                (rotated.translated(offset).shape_key(), rotation, offset)
+            })
+            .min_by(|a, b| a.0.cmp(&b.0))
+            .unwrap()
+    }
+
+    /// The atoms and bonds of this molecule as sorted lists, which compare equal for equal molecules regardless of hash order or bond direction.
+    fn shape_key(&self) -> ShapeKey{
+        let mut atoms: Vec<_> = self.atoms.iter().map(|(p, a)| (p.r, p.q, a.id())).collect();
+        atoms.sort();
+        let mut bonds: Vec<_> = self.bonds.iter().map(|b| {
+            let (start, end) = ((b.start.r, b.start.q), (b.end.r, b.end.q));
+            let (start, end) = (start.min(end), start.max(end));
+            (start.0, start.1, end.0, end.1, b.ty.id())
+        }).collect();
+        bonds.sort();
+        (atoms, bonds)
+    }
 }
 
+/// Sorted `(r, q, atom ID)` and `(start r, start q, end r, end q, bond type ID)` lists describing a placed molecule.
+type ShapeKey = (Vec<(i32, i32, u8)>, Vec<(i32, i32, i32, i32, u8)>);
+
 /// A bond between atoms.
 /// Note that `start` and `end` may be non-adjacent in the case of quantum bonds.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Bond{
     /// One end of the bond.
     pub start: HexIndex,
@@ -242,12 +649,18 @@ pub struct Bond{
 
 /// An atom type, or element.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Atom{
     #[default] Salt, Air, Earth, Fire, Water,
     Quicksilver, Vitae, Mors,
     Lead, Tin, Iron, Copper, Silver, Gold,
     Quintessence,
-    Repeat
+    Repeat,
+    /// An atom type this crate doesn't know, such as one added by a mod, by its byte ID.
+    /// Only produced by lenient parsing; see `parse::ParseOptions::lenient`.
+    Unknown(u8)
 }
 
 impl Atom{
@@ -273,20 +686,59 @@ impl Atom{
             _ => return None
         })
     }
+
+    /// Get the byte ID of this atom type.
+    pub fn id(self) -> u8{
+        match self{
+            Atom::Salt => 1,
+            Atom::Air => 2,
+            Atom::Earth => 3,
+            Atom::Fire => 4,
+            Atom::Water => 5,
+            Atom::Quicksilver => 6,
+            Atom::Gold => 7,
+            Atom::Silver => 8,
+            Atom::Copper => 9,
+            Atom::Iron => 10,
+            Atom::Tin => 11,
+            Atom::Lead => 12,
+            Atom::Vitae => 13,
+            Atom::Mors => 14,
+            Atom::Repeat => 15,
+            Atom::Quintessence => 16,
+            Atom::Unknown(id) => id
+        }
+    }
 }
 
 /// A bond type (normal or triplex).
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum BondType{
     #[default] Normal,
     Triplex{ red: bool, black: bool, yellow: bool }
 }
 
+impl BondType{
+    /// Get the byte ID of this bond type, as used in puzzle files.
+    pub const fn id(self) -> u8{
+        match self{
+            BondType::Normal => 1,
+            BondType::Triplex{ red, black, yellow } => ((red as u8) << 1) | ((black as u8) << 2) | ((yellow as u8) << 3)
+        }
+    }
+}
+
 // Parts
 
 /// A part, as parsed from a solution file.
 /// Invalid state, such as arms with sizes >3, or instructions on glyphs, is preserved.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Part{
     pub ty: PartType,
     pub pos: HexIndex,
@@ -307,8 +759,91 @@ pub struct Part{
     pub instructions: Vec<(Instruction, i32)>
 }
 
+impl Part{
+    /// The cost of this part. Track costs `TRACK_HEX_COST` per hex it covers, not counting the hex repeated to close a loop.
+    pub fn cost(&self) -> i32{
+        match &self.ty{
+            PartType::Track => TRACK_HEX_COST * (self.track_hexes.len() - usize::from(self.is_looped_track())) as i32,
+            ty => ty.cost()
+        }
+    }
+
+    /// Whether this is a track closed into a loop, by listing its first hex again at the end.
+    pub fn is_looped_track(&self) -> bool{
+        self.ty == PartType::Track && self.track_hexes.len() > 3 && self.track_hexes.first() == self.track_hexes.last()
+    }
+
+    /// The hexes this part covers on the board, at its position and rotation.
+    /// Track and conduits cover their listed hexes. Inputs and outputs cover the hexes of their molecule, which is
+    /// defined by the puzzle, so they give an empty footprint here; use `footprint_in` to include them.
+    pub fn footprint(&self) -> Vec<HexIndex>{
+        match &self.ty{
+            PartType::Track => self.track_hexes.iter().map(|h| *h + self.pos).collect(),
+            PartType::Conduit => self.conduit_hexes.iter().map(|h| *h + self.pos).collect(),
+            ty => ty.footprint().iter().map(|h| h.rotate60_about(HexIndex::default(), self.rotation) + self.pos).collect()
+        }
+    }
+
+    /// The hexes this part covers on the board, taking the molecules of inputs and outputs from a puzzle.
+    /// Polymer molecules cover their whole extended chain. Inputs and outputs with an index the puzzle doesn't have give an empty footprint.
+    pub fn footprint_in(&self, puzzle: &Puzzle) -> Vec<HexIndex>{
+        let molecules = match self.ty{
+            PartType::Input => &puzzle.reagents,
+            PartType::Output | PartType::PolymerOutput => &puzzle.products,
+            _ => return self.footprint()
+        };
+        usize::try_from(self.index).ok().and_then(|i| molecules.get(i))
+            .map(|m| m.extended(POLYMER_REPETITIONS).atoms.keys().map(|h| h.rotate60_about(HexIndex::default(), self.rotation) + self.pos).collect())
+            .unwrap_or_default()
+    }
+
+    /// This part moved by an offset.
+    pub fn translated(&self, by: HexIndex) -> Part{
+        Part{ pos: self.pos + by, ..self.clone() }
+    }
+
+    /// This part rotated around the origin by a number of rotation steps, which may be negative.
+    pub fn rotated(&self, steps: i32) -> Part{
+        let origin = HexIndex::default();
+        Part{
+            pos: self.pos.rotate60_about(origin, steps),
+            rotation: self.rotation + steps,
+            track_hexes: self.track_hexes.iter().map(|h| h.rotate60_about(origin, steps)).collect(),
+            conduit_hexes: self.conduit_hexes.iter().map(|h| h.rotate60_about(origin, steps)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// This part reflected across the Q axis, so that it does the same thing in a mirrored solution.
+    /// Instructions are swapped between clockwise and anticlockwise, and track hexes keep their order so that advancing and retreating are unchanged.
+    /// Glyphs whose footprints aren't symmetric are moved and turned so that each hex keeps its role,
+    /// except for triplex bonders, which can only cover the same hexes with two of their bond colours swapped.
+    /// Unification and dispersion glyphs are chiral, so no placement keeps their roles: they cover the same hexes with the elements
+    /// on opposite sides swapped, so a mirrored solution using them needs those elements brought to different hexes.
+    pub fn mirrored(&self) -> Part{
+        let (offset, turns) = self.ty.mirror_placement();
+        Part{
+            pos: self.pos.mirrored() + offset.rotate60_about(HexIndex::default(), -self.rotation),
+            rotation: turns - self.rotation,
+            track_hexes: self.track_hexes.iter().map(|h| h.mirrored()).collect(),
+            conduit_hexes: self.conduit_hexes.iter().map(|h| h.mirrored()).collect(),
+            instructions: self.instructions.iter().map(|(instr, idx)| (instr.mirrored(), *idx)).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The cost of each hex of track.
+pub const TRACK_HEX_COST: i32 = 5;
+
+/// The number of units that polymer reagents and products are extended to, for inputs to spawn and polymer outputs to accept.
+pub const POLYMER_REPETITIONS: i32 = 6;
+
 /// A part type, or kind of mechanism or glyph.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum PartType{
     // IO
     Input, Output, PolymerOutput,
@@ -323,7 +858,10 @@ pub enum PartType{
     TriplexBonding,
     Disposal,
     // Misc
-    Conduit
+    Conduit,
+    /// A part type this crate doesn't know, such as one added by a mod, by its name in solution files.
+    /// Only produced by lenient parsing; see `parse::ParseOptions::lenient`.
+    Unknown(String)
 }
 
 impl PartType {
@@ -356,10 +894,136 @@ impl PartType {
             _ => return None
         })
     }
+
+    /// Get the name of this part type, as used in solution files.
+    pub fn name(&self) -> &str{
+        match self{
+            PartType::Input => "input",
+            PartType::Output => "out-std",
+            PartType::PolymerOutput => "out-rep",
+            PartType::Arm => "arm1",
+            PartType::BiArm => "arm2",
+            PartType::TriArm => "arm3",
+            PartType::HexArm => "arm6",
+            PartType::PistonArm => "piston",
+            PartType::Track => "track",
+            PartType::Berlo => "baron",
+            PartType::Equilibrium => "glyph-marker",
+            PartType::Bonding => "bonder",
+            PartType::MultiBonding => "bonder-speed",
+            PartType::Unbonding => "unbonder",
+            PartType::Calcification => "glyph-calcification",
+            PartType::Projection => "glyph-projection",
+            PartType::Purification => "glyph-purification",
+            PartType::Duplication => "glyph-duplication",
+            PartType::Animismus => "glyph-life-and-death",
+            PartType::Unification => "glyph-unification",
+            PartType::Dispersion => "glyph-dispersion",
+            PartType::TriplexBonding => "bonder-prisma",
+            PartType::Disposal => "glyph-disposal",
+            PartType::Conduit => "pipe",
+            PartType::Unknown(name) => name
+        }
+    }
+
+    /// The cost of a single part of this type. For track, this is the cost of a single hex.
+    pub const fn cost(&self) -> i32{
+        match self{
+            PartType::Input | PartType::Output | PartType::PolymerOutput | PartType::Conduit | PartType::Unknown(_) => 0,
+            PartType::Arm => 20,
+            PartType::BiArm | PartType::TriArm | PartType::HexArm => 30,
+            PartType::PistonArm => 40,
+            PartType::Track => TRACK_HEX_COST,
+            PartType::Berlo => 30,
+            PartType::Equilibrium | PartType::Disposal => 0,
+            PartType::Bonding | PartType::Unbonding | PartType::Calcification => 10,
+            PartType::MultiBonding => 30,
+            PartType::Projection | PartType::Purification | PartType::Duplication | PartType::Animismus
+            | PartType::Unification | PartType::Dispersion | PartType::TriplexBonding => 20
+        }
+    }
+
+    /// The permission a puzzle must enable for parts of this type to be placed, or `None` if they're always available.
+    /// Conduits need no permission, but may only be placed in production puzzles.
+    /// Unknown parts need no permission either, but are never allowed.
+    pub const fn required_permission(&self) -> Option<Permissions>{
+        Some(match self{
+            PartType::Input | PartType::Output | PartType::PolymerOutput | PartType::Equilibrium | PartType::Conduit | PartType::Unknown(_) => return None,
+            PartType::Arm => Permissions::SIMPLE_ARM,
+            PartType::BiArm | PartType::TriArm | PartType::HexArm => Permissions::MULTI_ARMS,
+            PartType::PistonArm => Permissions::PISTON_ARM,
+            PartType::Track => Permissions::TRACK,
+            PartType::Berlo => Permissions::BERLO,
+            PartType::Bonding => Permissions::BONDER,
+            PartType::Unbonding => Permissions::UNBONDER,
+            PartType::MultiBonding => Permissions::MULTI_BONDER,
+            PartType::TriplexBonding => Permissions::TRIPLEX_BONDER,
+            PartType::Calcification => Permissions::CALCIFICATION,
+            PartType::Duplication => Permissions::DUPLICATION,
+            PartType::Projection => Permissions::PROJECTION,
+            PartType::Purification => Permissions::PURIFICATION,
+            PartType::Animismus => Permissions::ANIMISMUS,
+            PartType::Disposal => Permissions::DISPOSAL,
+            PartType::Unification | PartType::Dispersion => Permissions::QUINTESSENCE
+        })
+    }
+
+    /// The hexes covered by a part of this type, relative to its position, in its default rotation.
+    /// Arms, pistons, and Van Berlo's wheel cover only their base.
+    /// Inputs, outputs, track, and conduits cover hexes that depend on the part or puzzle, and give an empty footprint here; see `Part::footprint`.
+    /// Unknown parts also give an empty footprint.
+    pub const fn footprint(&self) -> &'static [HexIndex]{
+        match self{
+            PartType::Arm | PartType::BiArm | PartType::TriArm | PartType::HexArm | PartType::PistonArm | PartType::Berlo
+            | PartType::Equilibrium | PartType::Calcification => &[HexIndex{ q: 0, r: 0 }],
+            PartType::Bonding | PartType::Unbonding | PartType::Duplication | PartType::Projection => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 1, r: 0 }],
+            PartType::MultiBonding => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 1, r: 0 }, HexIndex{ q: -1, r: 1 }, HexIndex{ q: 0, r: -1 }],
+            PartType::Purification | PartType::TriplexBonding => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 1, r: 0 }, HexIndex{ q: 0, r: 1 }],
+            PartType::Animismus => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 1, r: 0 }, HexIndex{ q: 0, r: 1 }, HexIndex{ q: 1, r: -1 }],
+            PartType::Unification | PartType::Dispersion => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 0, r: 1 }, HexIndex{ q: -1, r: 1 }, HexIndex{ q: 0, r: -1 }, HexIndex{ q: 1, r: -1 }],
+            PartType::Disposal => &[HexIndex{ q: 0, r: 0 }, HexIndex{ q: 1, r: 0 }, HexIndex{ q: 0, r: 1 }, HexIndex{ q: -1, r: 1 }, HexIndex{ q: -1, r: 0 }, HexIndex{ q: 0, r: -1 }, HexIndex{ q: 1, r: -1 }],
+            PartType::Input | PartType::Output | PartType::PolymerOutput | PartType::Track | PartType::Conduit | PartType::Unknown(_) => &[]
+        }
+    }
+
+    /// The position and rotation at which a part of this type covers the reflection of its footprint in the default rotation, with each hex in the same role.
+    const fn mirror_placement(&self) -> (HexIndex, i32){
+        match self{
+            // triangular glyphs are turned around to face the other way
+            PartType::Purification | PartType::Animismus | PartType::TriplexBonding => (HexIndex{ q: 1, r: 0 }, 3),
+            _ => (HexIndex{ q: 0, r: 0 }, 0)
+        }
+    }
+
+    /// The broad category of part this is. Unknown parts are counted as glyphs.
+    pub fn category(&self) -> PartCategory{
+        match self{
+            PartType::Input | PartType::Output | PartType::PolymerOutput | PartType::Conduit => PartCategory::Io,
+            PartType::Arm | PartType::BiArm | PartType::TriArm | PartType::HexArm | PartType::PistonArm | PartType::Berlo => PartCategory::Arm,
+            PartType::Track => PartCategory::Track,
+            _ => PartCategory::Glyph
+        }
+    }
+}
+
+/// A broad category of part types.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PartCategory{
+    /// Arms, pistons, and Van Berlo's wheel.
+    Arm,
+    /// Glyphs, which act on atoms placed on them.
+    Glyph,
+    /// Track.
+    Track,
+    /// Inputs, outputs, and conduits.
+    Io
 }
 
 /// A type of instruction.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Instruction{
     #[default]
     Blank,
@@ -391,12 +1055,52 @@ impl Instruction {
             _ => return None
         })
     }
+
+    /// Get the byte ID of this instruction, as used in solution files.
+    pub const fn id(self) -> u8{
+        match self{
+            Instruction::Blank => b' ',
+            Instruction::Grab => b'G',
+            Instruction::Drop => b'g',
+            Instruction::RotateClockwise => b'R',
+            Instruction::RotateAnticlockwise => b'r',
+            Instruction::Extend => b'E',
+            Instruction::Retract => b'e',
+            Instruction::PivotClockwise => b'P',
+            Instruction::PivotAnticlockwise => b'p',
+            Instruction::Advance => b'A',
+            Instruction::Retreat => b'a',
+            Instruction::PeriodOverride => b'O',
+            Instruction::Reset => b'X',
+            Instruction::Repeat => b'C'
+        }
+    }
+
+    /// The instruction that does the same thing in the opposite rotational direction.
+    pub const fn mirrored(self) -> Instruction{
+        match self{
+            Instruction::RotateClockwise => Instruction::RotateAnticlockwise,
+            Instruction::RotateAnticlockwise => Instruction::RotateClockwise,
+            Instruction::PivotClockwise => Instruction::PivotAnticlockwise,
+            Instruction::PivotAnticlockwise => Instruction::PivotClockwise,
+            other => other
+        }
+    }
+
+    /// Whether this instruction counts towards the instructions metric.
+    /// Blanks and period overrides only shape the tape, while reset and repeat each count once however much they expand to.
+    pub const fn is_counted(self) -> bool{
+        !matches!(self, Instruction::Blank | Instruction::PeriodOverride)
+    }
 }
 
 // Misc
 
 /// A position or offset on a hex grid.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HexIndex{
     /// Position along the horizontal Q axis (also called X).
     pub q: i32,
@@ -405,6 +1109,16 @@ pub struct HexIndex{
 }
 
 impl HexIndex{
+    /// The offsets to each neighbour of a hex, starting along the Q axis and turning one rotation step at a time.
+    pub const DIRECTIONS: [HexIndex; 6] = [
+        HexIndex{ q: 1, r: 0 }, HexIndex{ q: 0, r: 1 }, HexIndex{ q: -1, r: 1 },
+        HexIndex{ q: -1, r: 0 }, HexIndex{ q: 0, r: -1 }, HexIndex{ q: 1, r: -1 }
+    ];
+
+    pub const fn new(q: i32, r: i32) -> HexIndex{
+        HexIndex{ q, r }
+    }
+
     /// Implicit S coordinate of this coordinate.
     pub const fn s(self) -> i32{
         -self.q - self.r
@@ -425,6 +1139,63 @@ impl HexIndex{
         }
         offset + around
     }
+
+    /// Rotate around a point by a number of rotation steps, which may be negative.
+    pub fn rotate60_about(self, origin: HexIndex, steps: i32) -> HexIndex{
+        self.rotated(origin, HexRotation::from_signed(steps))
+    }
+
+    /// Reflect across the Q axis.
+    pub const fn mirrored(self) -> HexIndex{
+        HexIndex{ q: self.q + self.r, r: -self.r }
+    }
+
+    /// The six hexes adjacent to this one, in the order of `DIRECTIONS`.
+    pub fn neighbors(self) -> [HexIndex; 6]{
+        Self::DIRECTIONS.map(|d| self + d)
+    }
+
+    /// The number of steps between this hex and the origin.
+    pub const fn length(self) -> i32{
+        let (q, r, s) = (self.q.abs(), self.r.abs(), self.s().abs());
+        if q > r && q > s { q } else if r > s { r } else { s }
+    }
+
+    /// The number of steps between two hexes.
+    pub fn distance(self, other: HexIndex) -> i32{
+        (self - other).length()
+    }
+
+    /// The hexes exactly `radius` steps from this one, going around anticlockwise from the one in direction 4.
+    /// A radius of 0 gives just this hex.
+    pub fn ring(self, radius: i32) -> impl Iterator<Item = HexIndex>{
+        let start = self + Self::DIRECTIONS[4] * radius;
+        let count = if radius == 0 { 1 } else { 6 * radius };
+        (0..count).scan(start, move |pos, i| {
+            let current = *pos;
+            *pos += Self::DIRECTIONS[(i / radius.max(1)) as usize % 6];
+            Some(current)
+        })
+    }
+
+    /// The hexes at most `radius` steps from this one, ring by ring outwards from this hex.
+    pub fn spiral(self, radius: i32) -> impl Iterator<Item = HexIndex>{
+        (0..=radius).flat_map(move |ring| self.ring(ring))
+    }
+}
+
+impl Mul<i32> for HexIndex{
+    type Output = HexIndex;
+    fn mul(self, rhs: i32) -> HexIndex{
+        HexIndex{ q: self.q * rhs, r: self.r * rhs }
+    }
+}
+
+impl Neg for HexIndex{
+    type Output = HexIndex;
+    fn neg(self) -> HexIndex{
+        HexIndex{ q: -self.q, r: -self.r }
+    }
 }
 
 impl Add for HexIndex{
@@ -457,6 +1228,9 @@ impl SubAssign for HexIndex{
 
 /// A rotation on a hex grid.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HexRotation{
     turns: u8,
 }
@@ -482,7 +1256,12 @@ impl HexRotation{
     }
 
     pub fn to_radians(self) -> f32{
-        return (self.turns as f32 * 60.0) * (std::f32::consts::PI / 180.0);
+        (self.turns as f32 * 60.0) * (std::f32::consts::PI / 180.0)
+    }
+
+    /// The rotation in the opposite direction, as seen in a reflection across the Q axis.
+    pub fn mirrored(self) -> HexRotation{
+        HexRotation{ turns: (6 - self.turns) % 6 }
     }
 }
 
@@ -520,4 +1299,55 @@ impl SubAssign for HexRotation{
     fn sub_assign(&mut self, rhs: HexRotation) {
         *self = *self - rhs;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const GLYPHS: [PartType; 12] = [
+        PartType::Bonding, PartType::MultiBonding, PartType::Unbonding, PartType::Calcification, PartType::Animismus, PartType::Projection,
+        PartType::Purification, PartType::Duplication, PartType::Disposal, PartType::Unification, PartType::Dispersion, PartType::TriplexBonding
+    ];
+
+    fn glyph(ty: PartType, pos: HexIndex, rotation: i32) -> Part{
+        Part{ ty, pos, rotation, arm_number: 0, arm_length: 1, index: 0, conduit_index: 0, track_hexes: Vec::new(), conduit_hexes: Vec::new(), instructions: Vec::new() }
+    }
+
+    fn sorted(mut hexes: Vec<HexIndex>) -> Vec<HexIndex>{
+        hexes.sort_by_key(|h| (h.q, h.r));
+        hexes
+    }
+
+    #[test]
+    fn mirrored_glyphs_cover_mirrored_footprints(){
+        for ty in GLYPHS{
+            for rotation in -6..6{
+                let part = glyph(ty.clone(), HexIndex{ q: 2, r: -3 }, rotation);
+                let expected = sorted(part.footprint().into_iter().map(HexIndex::mirrored).collect());
+                assert_eq!(sorted(part.mirrored().footprint()), expected, "{ty:?} at rotation {rotation}");
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_glyphs_keep_output_hexes(){
+        // the hexes where these glyphs make or change atoms, which must stay where they were
+        for (ty, role) in [(PartType::Purification, 2), (PartType::Animismus, 2), (PartType::Animismus, 3), (PartType::Projection, 1), (PartType::Duplication, 1)]{
+            for rotation in 0..6{
+                let part = glyph(ty.clone(), HexIndex{ q: -1, r: 4 }, rotation);
+                assert_eq!(part.mirrored().footprint()[role], part.footprint()[role].mirrored(), "{ty:?} at rotation {rotation}");
+            }
+        }
+    }
+
+    #[test]
+    fn mirroring_twice_gives_the_same_footprint(){
+        for ty in GLYPHS{
+            for rotation in 0..6{
+                let part = glyph(ty.clone(), HexIndex{ q: 3, r: 1 }, rotation);
+                assert_eq!(sorted(part.mirrored().mirrored().footprint()), sorted(part.footprint()), "{ty:?} at rotation {rotation}");
+            }
+        }
+    }
+}
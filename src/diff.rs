@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use crate::data::{Chamber, Conduit, HexIndex, Instruction, Molecule, Part, PartCategory, PartType, Permissions, ProductionInfo, Puzzle, Solution};
+
+// Structural differences between versions of a solution or puzzle.
+
+/// The differences between two solutions, with parts matched up between them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SolutionDiff{
+    /// Indices in the new solution of parts that have no match in the old one.
+    pub added: Vec<usize>,
+    /// Indices in the old solution of parts that have no match in the new one.
+    pub removed: Vec<usize>,
+    /// Parts that are in both solutions but changed, in the order they appear in the new solution.
+    pub changed: Vec<PartDiff>
+}
+
+impl SolutionDiff{
+    /// Whether the solutions have the same parts with the same state, though possibly in a different order.
+    pub fn is_empty(&self) -> bool{
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// How a part that's in both solutions changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartDiff{
+    /// Index of the part in the old solution.
+    pub old: usize,
+    /// Index of the part in the new solution.
+    pub new: usize,
+    /// The part's position before and after, if it moved.
+    pub moved: Option<(HexIndex, HexIndex)>,
+    /// The part's rotation before and after, if it rotated.
+    pub rotated: Option<(i32, i32)>,
+    /// Whether anything else about the part changed, such as its arm length or number, track hexes, or which input or output it is.
+    pub reshaped: bool,
+    /// Changes to the part's instructions, in tape order.
+    pub instructions: Vec<InstructionChange>
+}
+
+/// A change to a single slot on an arm's tape.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InstructionChange{
+    /// An instruction was placed in an empty slot.
+    Added{ index: i32, instruction: Instruction },
+    /// The instruction in a slot was removed.
+    Removed{ index: i32, instruction: Instruction },
+    /// The instruction in a slot was replaced by another.
+    Replaced{ index: i32, from: Instruction, to: Instruction }
+}
+
+/// Compare two solutions, matching parts between them.
+/// Identical parts are matched first, then parts of the same type that keep their identity (arm number, input or output index, or conduit index),
+/// then any remaining parts of the same type, nearest first.
+pub fn diff(old: &Solution, new: &Solution) -> SolutionDiff{
+    let mut old_matched = vec![false; old.parts.len()];
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+
+    // identical parts
+    let mut new_matched: Vec<bool> = new.parts.iter().map(|part| {
+        let found = (0..old.parts.len()).find(|o| !old_matched[*o] && old.parts[*o] == *part);
+        if let Some(o) = found{
+            old_matched[o] = true;
+        }
+        found.is_some()
+    }).collect();
+
+    // everything else, by best candidate first
+    let mut candidates: Vec<((bool, i32, usize), usize, usize)> = Vec::new();
+    for (n, new_part) in new.parts.iter().enumerate().filter(|(n, _)| !new_matched[*n]){
+        for (o, old_part) in old.parts.iter().enumerate().filter(|(o, _)| !old_matched[*o]){
+            if old_part.ty == new_part.ty{
+                let distance = (new_part.pos - old_part.pos).length();
+                let tape_distance = tape_changes(old_part, new_part).len();
+                candidates.push(((!same_identity(old_part, new_part), distance, tape_distance), o, n));
+            }
+        }
+    }
+    candidates.sort();
+    for (_, o, n) in candidates{
+        if !old_matched[o] && !new_matched[n]{
+            old_matched[o] = true;
+            new_matched[n] = true;
+            pairs.push((o, n));
+        }
+    }
+    pairs.sort_by_key(|(_, n)| *n);
+
+    SolutionDiff{
+        added: (0..new.parts.len()).filter(|n| !new_matched[*n]).collect(),
+        removed: (0..old.parts.len()).filter(|o| !old_matched[*o]).collect(),
+        changed: pairs.into_iter().map(|(o, n)| {
+            let (old_part, new_part) = (&old.parts[o], &new.parts[n]);
+            PartDiff{
+                old: o,
+                new: n,
+                moved: (old_part.pos != new_part.pos).then_some((old_part.pos, new_part.pos)),
+                rotated: (old_part.rotation != new_part.rotation).then_some((old_part.rotation, new_part.rotation)),
+                reshaped: old_part.arm_length != new_part.arm_length
+                    || old_part.arm_number != new_part.arm_number
+                    || old_part.index != new_part.index
+                    || old_part.conduit_index != new_part.conduit_index
+                    || old_part.track_hexes != new_part.track_hexes
+                    || old_part.conduit_hexes != new_part.conduit_hexes,
+                instructions: tape_changes(old_part, new_part)
+            }
+        }).collect()
+    }
+}
+
+/// Whether two parts of the same type are probably the same part, going by the state that identifies them in game.
+fn same_identity(old: &Part, new: &Part) -> bool{
+    match &old.ty{
+        PartType::Input | PartType::Output | PartType::PolymerOutput => old.index == new.index,
+        PartType::Conduit => old.conduit_index == new.conduit_index,
+        ty if ty.category() == PartCategory::Arm => old.arm_number == new.arm_number,
+        _ => false
+    }
+}
+
+fn tape_changes(old: &Part, new: &Part) -> Vec<InstructionChange>{
+    let mut slots: BTreeMap<i32, (Option<Instruction>, Option<Instruction>)> = BTreeMap::new();
+    for (instruction, index) in &old.instructions{
+        slots.entry(*index).or_default().0 = Some(*instruction);
+    }
+    for (instruction, index) in &new.instructions{
+        slots.entry(*index).or_default().1 = Some(*instruction);
+    }
+    slots.into_iter().filter_map(|(index, slot)| match slot{
+        (None, Some(instruction)) => Some(InstructionChange::Added{ index, instruction }),
+        (Some(instruction), None) => Some(InstructionChange::Removed{ index, instruction }),
+        (Some(from), Some(to)) if from != to => Some(InstructionChange::Replaced{ index, from, to }),
+        _ => None
+    }).collect()
+}
+
+/// The differences between two versions of a puzzle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PuzzleDiff{
+    /// The puzzle's name before and after, if it was renamed.
+    pub renamed: Option<(String, String)>,
+    /// Changes to reagents, by index.
+    pub reagents: Vec<MoleculeChange>,
+    /// Changes to products, by index.
+    pub products: Vec<MoleculeChange>,
+    /// The product multiplier before and after, if it changed.
+    pub product_multiplier: Option<(i32, i32)>,
+    /// Permissions that the new version allows and the old didn't.
+    pub permissions_added: Permissions,
+    /// Permissions that the old version allowed and the new doesn't.
+    pub permissions_removed: Permissions,
+    /// Changes to the chambers and conduits of production puzzles, if there were any.
+    pub production: Option<ProductionDiff>
+}
+
+impl PuzzleDiff{
+    /// Whether the puzzles are the same, up to how their molecules are placed.
+    pub fn is_empty(&self) -> bool{
+        self.renamed.is_none()
+            && self.reagents.is_empty()
+            && self.products.is_empty()
+            && self.product_multiplier.is_none()
+            && self.permissions_added.is_empty()
+            && self.permissions_removed.is_empty()
+            && self.production.is_none()
+    }
+}
+
+/// A change to one reagent or product.
+/// Molecules are compared by index, since solutions refer to them that way, and up to translation and rotation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MoleculeChange{
+    /// A molecule was added at the given index.
+    Added(usize),
+    /// The molecule at the given index was removed.
+    Removed(usize),
+    /// The molecule at the given index has different atoms or bonds.
+    Changed(usize)
+}
+
+/// The differences between the production layouts of two versions of a puzzle.
+/// A puzzle that isn't a production puzzle is treated as having no chambers or conduits.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProductionDiff{
+    /// Whether the puzzle was a production puzzle before and after, if that changed.
+    pub production: Option<(bool, bool)>,
+    /// Whether isolation was required before and after, if that changed.
+    pub isolation: Option<(bool, bool)>,
+    pub added_chambers: Vec<Chamber>,
+    pub removed_chambers: Vec<Chamber>,
+    pub added_conduits: Vec<Conduit>,
+    pub removed_conduits: Vec<Conduit>
+}
+
+/// Compare two versions of a puzzle.
+pub fn diff_puzzles(old: &Puzzle, new: &Puzzle) -> PuzzleDiff{
+    PuzzleDiff{
+        renamed: (old.name != new.name).then(|| (old.name.clone(), new.name.clone())),
+        reagents: molecule_changes(&old.reagents, &new.reagents),
+        products: molecule_changes(&old.products, &new.products),
+        product_multiplier: (old.product_multiplier != new.product_multiplier).then_some((old.product_multiplier, new.product_multiplier)),
+        permissions_added: new.permissions.difference(old.permissions),
+        permissions_removed: old.permissions.difference(new.permissions),
+        production: production_diff(old.production_info.as_ref(), new.production_info.as_ref())
+    }
+}
+
+fn molecule_changes(old: &[Molecule], new: &[Molecule]) -> Vec<MoleculeChange>{
+    (0..old.len().max(new.len())).filter_map(|i| match (old.get(i), new.get(i)){
+        (Some(_), None) => Some(MoleculeChange::Removed(i)),
+        (None, Some(_)) => Some(MoleculeChange::Added(i)),
+        (Some(a), Some(b)) if !a.matches(b) => Some(MoleculeChange::Changed(i)),
+        _ => None
+    }).collect()
+}
+
+fn production_diff(old: Option<&ProductionInfo>, new: Option<&ProductionInfo>) -> Option<ProductionDiff>{
+    let empty = ProductionInfo{ isolation: false, chambers: Vec::new(), conduits: Vec::new() };
+    let (old_info, new_info) = (old.unwrap_or(&empty), new.unwrap_or(&empty));
+    let diff = ProductionDiff{
+        production: (old.is_some() != new.is_some()).then_some((old.is_some(), new.is_some())),
+        isolation: (old_info.isolation != new_info.isolation).then_some((old_info.isolation, new_info.isolation)),
+        added_chambers: missing_from(&new_info.chambers, &old_info.chambers),
+        removed_chambers: missing_from(&old_info.chambers, &new_info.chambers),
+        added_conduits: missing_from(&new_info.conduits, &old_info.conduits),
+        removed_conduits: missing_from(&old_info.conduits, &new_info.conduits)
+    };
+    (diff != ProductionDiff::default()).then_some(diff)
+}
+
+/// The items in `items` that aren't in `from`.
+fn missing_from<T: PartialEq + Clone>(items: &[T], from: &[T]) -> Vec<T>{
+    items.iter().filter(|item| !from.contains(item)).cloned().collect()
+}
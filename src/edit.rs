@@ -0,0 +1,125 @@
+use crate::data::{HexIndex, Instruction, Part, Solution};
+
+/// A single reversible change to a solution.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Edit{
+    /// Move a part to a new position.
+    MovePart{ part: usize, to: HexIndex },
+    /// Set the rotation of a part.
+    RotatePart{ part: usize, to: i32 },
+    /// Replace the instructions on a part's tape.
+    SetTape{ part: usize, instructions: Vec<(Instruction, i32)> },
+    /// Insert a part at an index, shifting later parts along.
+    AddPart{ index: usize, part: Part },
+    /// Remove the part at an index, shifting later parts back.
+    RemovePart{ index: usize }
+}
+
+impl Edit{
+    /// Apply this edit to a solution, returning the edit that undoes it.
+    pub fn apply(self, solution: &mut Solution) -> Result<Edit, &'static str>{
+        let parts = &mut solution.parts;
+        Ok(match self{
+            Edit::MovePart{ part, to } => {
+                let part_ref = parts.get_mut(part).ok_or("edit refers to a part that doesn't exist")?;
+                Edit::MovePart{ part, to: std::mem::replace(&mut part_ref.pos, to) }
+            }
+            Edit::RotatePart{ part, to } => {
+                let part_ref = parts.get_mut(part).ok_or("edit refers to a part that doesn't exist")?;
+                Edit::RotatePart{ part, to: std::mem::replace(&mut part_ref.rotation, to) }
+            }
+            Edit::SetTape{ part, instructions } => {
+                let part_ref = parts.get_mut(part).ok_or("edit refers to a part that doesn't exist")?;
+                Edit::SetTape{ part, instructions: std::mem::replace(&mut part_ref.instructions, instructions) }
+            }
+            Edit::AddPart{ index, part } => {
+                if index > parts.len(){
+                    return Err("edit inserts a part past the end of the solution");
+                }
+                parts.insert(index, part);
+                Edit::RemovePart{ index }
+            }
+            Edit::RemovePart{ index } => {
+                if index >= parts.len(){
+                    return Err("edit refers to a part that doesn't exist");
+                }
+                Edit::AddPart{ index, part: parts.remove(index) }
+            }
+        })
+    }
+}
+
+/// A function called after every change to an `EditableSolution`.
+pub type EditListener = Box<dyn FnMut(&Solution, &Edit)>;
+
+/// A solution with undo and redo history, that notifies listeners of every change.
+/// Editors should make all changes through `apply` so that history and listeners stay in sync.
+pub struct EditableSolution{
+    solution: Solution,
+    /// Edits that undo applied changes, most recent last.
+    undo_stack: Vec<Edit>,
+    /// Edits that redo undone changes, most recently undone last.
+    redo_stack: Vec<Edit>,
+    listeners: Vec<EditListener>
+}
+
+impl EditableSolution{
+    pub fn new(solution: Solution) -> Self{
+        EditableSolution{ solution, undo_stack: Vec::new(), redo_stack: Vec::new(), listeners: Vec::new() }
+    }
+
+    /// The solution in its current state.
+    pub fn solution(&self) -> &Solution{
+        &self.solution
+    }
+
+    /// Stop editing, returning the solution in its current state.
+    pub fn into_solution(self) -> Solution{
+        self.solution
+    }
+
+    /// Register a function to be called with the solution and the applied edit after every change, including undos and redos.
+    pub fn subscribe(&mut self, listener: impl FnMut(&Solution, &Edit) + 'static){
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Apply an edit, clearing the redo history.
+    pub fn apply(&mut self, edit: Edit) -> Result<(), &'static str>{
+        let inverse = self.perform(edit)?;
+        self.undo_stack.push(inverse);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Undo the most recent change, returning whether there was one to undo.
+    pub fn undo(&mut self) -> Result<bool, &'static str>{
+        let Some(edit) = self.undo_stack.pop() else { return Ok(false) };
+        let inverse = self.perform(edit)?;
+        self.redo_stack.push(inverse);
+        Ok(true)
+    }
+
+    /// Redo the most recently undone change, returning whether there was one to redo.
+    pub fn redo(&mut self) -> Result<bool, &'static str>{
+        let Some(edit) = self.redo_stack.pop() else { return Ok(false) };
+        let inverse = self.perform(edit)?;
+        self.undo_stack.push(inverse);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool{
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool{
+        !self.redo_stack.is_empty()
+    }
+
+    fn perform(&mut self, edit: Edit) -> Result<Edit, &'static str>{
+        let inverse = edit.clone().apply(&mut self.solution)?;
+        for listener in &mut self.listeners{
+            listener(&self.solution, &edit);
+        }
+        Ok(inverse)
+    }
+}
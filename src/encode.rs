@@ -0,0 +1,240 @@
+use std::io;
+
+use super::data::*;
+
+/// Encode a puzzle in the game's (version 3) puzzle file format.
+/// Visual-only production information is written with default values: chambers aren't shrunk, and there are no vials.
+/// Fails if an atom, bond, chamber or conduit is further from the origin than the file's signed byte positions can store.
+pub fn write_puzzle(puzzle: &Puzzle) -> Result<Vec<u8>, &'static str>{
+    write_puzzle_lossless(puzzle, &RawExtras::default())
+}
+
+/// Encode a puzzle along with the extras kept by parsing with `parse::ParseOptions::lossless`, reproducing the file it was parsed from.
+/// If the puzzle was changed since, atoms and bonds not in the recorded order are written after those that are.
+/// Fails like `write_puzzle` for positions that don't fit in a signed byte.
+pub fn write_puzzle_lossless(puzzle: &Puzzle, extras: &RawExtras) -> Result<Vec<u8>, &'static str>{
+    let mut writer = BaseWriter::new();
+    writer.write_int(3);
+    writer.write_string(&puzzle.name);
+    writer.write_ulong(puzzle.creator_id);
+    writer.write_ulong(puzzle.permissions.bits());
+    let mut orders = extras.molecule_orders.iter();
+    for molecules in [&puzzle.reagents, &puzzle.products]{
+        writer.write_int(molecules.len() as i32);
+        for molecule in molecules{
+            writer.write_molecule(molecule, orders.next())?;
+        }
+    }
+    writer.write_int(puzzle.product_multiplier);
+    match &puzzle.production_info{
+        None => writer.write_bool(false),
+        Some(info) => {
+            writer.write_bool(true);
+            writer.write_bool(extras.shrink_left);
+            writer.write_bool(extras.shrink_right);
+            writer.write_bool(info.isolation);
+            writer.try_write_list(&info.chambers, |w, chamber| {
+                w.write_b_hex_index(chamber.pos)?;
+                w.write_string(chamber.ty.name());
+                Ok(())
+            })?;
+            writer.try_write_list(&info.conduits, |w, conduit| {
+                w.write_b_hex_index(conduit.pos_a)?;
+                w.write_b_hex_index(conduit.pos_b)?;
+                w.try_write_list(&conduit.hexes, |w, h| w.write_b_hex_index(*h))
+            })?;
+            writer.try_write_list(&extras.vials, |w, vial| {
+                w.write_b_hex_index(vial.pos)?;
+                w.write_bool(vial.top);
+                w.write_int(vial.count);
+                Ok(())
+            })?;
+        }
+    }
+    writer.data.extend_from_slice(&extras.trailing);
+    Ok(writer.data)
+}
+
+/// Encode a puzzle in the game's puzzle file format to a writer.
+/// Puzzles that `write_puzzle` can't encode give an `io::ErrorKind::InvalidInput` error.
+pub fn write_puzzle_to(puzzle: &Puzzle, out: &mut impl io::Write) -> io::Result<()>{
+    let data = write_puzzle(puzzle).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    out.write_all(&data)
+}
+
+/// Encode a solution in the game's solution file format.
+/// Solutions parsed with `parse_solution` are encoded to the same bytes they were parsed from.
+pub fn write_solution(solution: &Solution) -> Vec<u8>{
+    write_solution_lossless(solution, &RawExtras::default())
+}
+
+/// Encode a solution along with the trailing bytes kept by parsing with `parse::ParseOptions::lossless`, reproducing the file it was parsed from.
+pub fn write_solution_lossless(solution: &Solution, extras: &RawExtras) -> Vec<u8>{
+    let mut writer = BaseWriter::new();
+    writer.write_int(7);
+    writer.write_string(&solution.puzzle_name);
+    writer.write_string(&solution.name);
+    match solution.metrics{
+        None => writer.write_int(0),
+        Some(metrics) => {
+            writer.write_int(4);
+            for (id, value) in [metrics.cycles, metrics.cost, metrics.area, metrics.instructions].into_iter().enumerate(){
+                writer.write_int(id as i32);
+                writer.write_int(value);
+            }
+        }
+    }
+    writer.write_list(&solution.parts, |w, part| {
+        w.write_string(part.ty.name());
+        w.write_byte(1);
+        w.write_i_hex_index(part.pos);
+        w.write_int(part.arm_length);
+        w.write_int(part.rotation);
+        w.write_int(part.index);
+        w.write_list(&part.instructions, |w, (instr, idx)| {
+            w.write_int(*idx);
+            w.write_byte(instr.id());
+        });
+        if part.ty == PartType::Track{
+            w.write_list(&part.track_hexes, |w, h| w.write_i_hex_index(*h));
+        }
+        w.write_int(part.arm_number - 1);
+        if part.ty == PartType::Conduit{
+            w.write_int(part.conduit_index);
+            w.write_list(&part.conduit_hexes, |w, h| w.write_i_hex_index(*h));
+        }
+    });
+    writer.data.extend_from_slice(&extras.trailing);
+    writer.data
+}
+
+/// Encode a solution in the game's solution file format to a writer.
+pub fn write_solution_to(solution: &Solution, out: &mut impl io::Write) -> io::Result<()>{
+    out.write_all(&write_solution(solution))
+}
+
+// byte writing
+
+struct BaseWriter{
+    data: Vec<u8>
+}
+
+impl BaseWriter{
+
+    fn new() -> Self{
+        Self{ data: Vec::new() }
+    }
+
+    fn write_byte(&mut self, value: u8){
+        self.data.push(value);
+    }
+
+    fn write_sbyte(&mut self, value: i8){
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_bool(&mut self, value: bool){
+        self.write_byte(value as u8);
+    }
+
+    fn write_int(&mut self, value: i32){
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_ulong(&mut self, value: u64){
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_list<T>(&mut self, items: &[T], f: impl Fn(&mut Self, &T)){
+        self.write_int(items.len() as i32);
+        for item in items{
+            f(self, item);
+        }
+    }
+
+    fn try_write_list<T>(&mut self, items: &[T], f: impl Fn(&mut Self, &T) -> Result<(), &'static str>) -> Result<(), &'static str>{
+        self.write_int(items.len() as i32);
+        items.iter().try_for_each(|item| f(self, item))
+    }
+
+    fn write_var_int(&mut self, mut value: usize){
+        while value >= 0x80{
+            self.write_byte((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        self.write_byte(value as u8);
+    }
+
+    fn write_string(&mut self, value: &str){
+        self.write_var_int(value.len());
+        self.data.extend_from_slice(value.as_bytes());
+    }
+
+    /// Write a hex index represented with signed byte offsets, used in puzzles.
+    fn write_b_hex_index(&mut self, value: HexIndex) -> Result<(), &'static str>{
+        let (Ok(q), Ok(r)) = (i8::try_from(value.q), i8::try_from(value.r)) else { return Err("hex position doesn't fit in a puzzle file") };
+        self.write_sbyte(q);
+        self.write_sbyte(r);
+        Ok(())
+    }
+
+    /// Write a hex index represented with signed 32-bit integer offsets, used in solutions.
+    fn write_i_hex_index(&mut self, value: HexIndex){
+        self.write_int(value.q);
+        self.write_int(value.r);
+    }
+
+    fn write_bond_type(&mut self, ty: BondType){
+        self.write_byte(ty.id());
+    }
+
+    /// Write a molecule's atoms and bonds in the given order, then any others sorted.
+    fn write_molecule(&mut self, molecule: &Molecule, order: Option<&(Vec<HexIndex>, Vec<Bond>)>) -> Result<(), &'static str>{
+        let (atom_order, bond_order) = order.map_or((&[][..], &[][..]), |(atoms, bonds)| (&atoms[..], &bonds[..]));
+        // hash order isn't stable, so sort to keep output deterministic
+        let mut atoms: Vec<(&HexIndex, &Atom)> = molecule.atoms.iter().collect();
+        atoms.sort_by_key(|(pos, _)| (atom_order.iter().position(|p| p == *pos).unwrap_or(usize::MAX), pos.r, pos.q));
+        self.try_write_list(&atoms, |w, (pos, atom)| {
+            w.write_byte(atom.id());
+            w.write_b_hex_index(**pos)
+        })?;
+        let mut bonds: Vec<&Bond> = molecule.bonds.iter().collect();
+        bonds.sort_by_key(|b| (bond_order.iter().position(|o| o == *b).unwrap_or(usize::MAX), b.start.r, b.start.q, b.end.r, b.end.q));
+        self.try_write_list(&bonds, |w, bond| {
+            w.write_bond_type(bond.ty);
+            w.write_b_hex_index(bond.start)?;
+            w.write_b_hex_index(bond.end)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::PuzzleBuilder;
+    use crate::parse::parse_puzzle;
+
+    fn puzzle_with_atom_at(q: i32, r: i32) -> Puzzle{
+        let molecule = Molecule::builder().atom(q, r, Atom::Salt).build().unwrap();
+        PuzzleBuilder::new("puzzle").reagent(molecule.clone()).product(molecule).build()
+    }
+
+    #[test]
+    fn hexes_round_trip_up_to_the_byte_limits(){
+        for (q, r) in [(127, -128), (-128, 127)]{
+            let puzzle = puzzle_with_atom_at(q, r);
+            let parsed = parse_puzzle(&write_puzzle(&puzzle).unwrap()).unwrap();
+            assert_eq!(parsed.reagents, puzzle.reagents);
+        }
+    }
+
+    #[test]
+    fn out_of_range_hexes_are_errors(){
+        for (q, r) in [(128, 0), (0, -129), (300, 0)]{
+            let puzzle = puzzle_with_atom_at(q, r);
+            assert!(write_puzzle(&puzzle).is_err());
+            let err = write_puzzle_to(&puzzle, &mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
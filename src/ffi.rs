@@ -0,0 +1,299 @@
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+use std::slice;
+
+use crate::data::{HexIndex, Puzzle, Solution};
+use crate::parse::{parse_puzzle, parse_solution, ParseError};
+use crate::sim::{SimError, SimOptions};
+use crate::validate::find_overlaps;
+use crate::verify::{throughput, verify_with_options, Throughput, VerificationResult, DEFAULT_CYCLE_LIMIT};
+
+// A C interface matching the reference omsim's `verifier.h`, so that its callers can link against this instead.
+// Every function takes a verifier created by `verifier_create` or `verifier_create_from_bytes`, and failures are reported through `verifier_error`.
+
+/// Overlapping solutions are simulated, like in the reference omsim, with the overlap reported through the `overlap` metric instead.
+const OVERLAP_ALLOWED: SimOptions = SimOptions{ allow_overlap: true, strict_drops: false };
+
+/// A puzzle and solution pair, along with the results of simulating it so far and the last error that occurred.
+pub struct Verifier{
+    puzzle: Option<Puzzle>,
+    solution: Option<Solution>,
+    cycle_limit: i32,
+    result: Option<Result<VerificationResult, SimError>>,
+    throughput: Option<Result<Throughput, SimError>>,
+    error: Option<CString>,
+    error_cycle: i32,
+    error_location: HexIndex
+}
+
+impl Verifier{
+    fn new(puzzle: Result<Puzzle, String>, solution: Result<Solution, String>) -> Verifier{
+        let mut verifier = Verifier{
+            puzzle: None,
+            solution: None,
+            cycle_limit: DEFAULT_CYCLE_LIMIT,
+            result: None,
+            throughput: None,
+            error: None,
+            error_cycle: 0,
+            error_location: HexIndex::default()
+        };
+        match (puzzle, solution){
+            (Ok(puzzle), Ok(solution)) => {
+                verifier.puzzle = Some(puzzle);
+                verifier.solution = Some(solution);
+            }
+            (Err(error), _) => verifier.set_error(&format!("invalid puzzle: {error}")),
+            (_, Err(error)) => verifier.set_error(&format!("invalid solution: {error}"))
+        }
+        verifier
+    }
+
+    fn set_error(&mut self, message: &str){
+        // the message can't contain a nul, but avoid panicking across the boundary if it somehow does
+        self.error = Some(CString::new(message.replace('\0', "")).unwrap_or_default());
+    }
+
+    fn set_sim_error(&mut self, error: SimError){
+        match error{
+            SimError::InvalidInstruction{ cycle, .. } | SimError::WrongOutput{ cycle, .. } => self.error_cycle = cycle,
+            SimError::Collision{ cycle, pos, .. } | SimError::ConflictingMotion{ cycle, pos, .. } | SimError::ArmHitWall{ cycle, pos, .. } | SimError::InvalidDrop{ cycle, pos, .. } => {
+                self.error_cycle = cycle;
+                self.error_location = pos;
+            }
+            SimError::InvalidSolution(_) | SimError::TimedOut{ .. } => {}
+        }
+        self.set_error(&error.to_string());
+    }
+
+    fn files(&self) -> Result<(&Puzzle, &Solution), ()>{
+        match (&self.puzzle, &self.solution){
+            (Some(puzzle), Some(solution)) => Ok((puzzle, solution)),
+            _ => Err(())
+        }
+    }
+
+    fn result(&mut self) -> Result<&VerificationResult, ()>{
+        if self.result.is_none(){
+            let (puzzle, solution) = self.files()?;
+            self.result = Some(verify_with_options(puzzle, solution, self.cycle_limit, OVERLAP_ALLOWED));
+        }
+        if let Some(error) = self.result.as_ref().and_then(|r| r.as_ref().err()).cloned(){
+            self.set_sim_error(error);
+        }
+        self.result.as_ref().and_then(|r| r.as_ref().ok()).ok_or(())
+    }
+
+    fn throughput(&mut self) -> Result<&Throughput, ()>{
+        if self.throughput.is_none(){
+            let (puzzle, solution) = self.files()?;
+            self.throughput = Some(throughput(puzzle, solution, self.cycle_limit, OVERLAP_ALLOWED));
+        }
+        if let Some(error) = self.throughput.as_ref().and_then(|r| r.as_ref().err()).cloned(){
+            self.set_sim_error(error);
+        }
+        self.throughput.as_ref().and_then(|r| r.as_ref().ok()).ok_or(())
+    }
+
+    fn metric(&mut self, metric: &str) -> Result<i32, ()>{
+        let parsed = |v: &mut Verifier| match v.solution.as_ref().map(|s| s.metrics){
+            Some(Some(metrics)) => Ok(metrics),
+            Some(None) => {
+                v.set_error("solution is not solved");
+                Err(())
+            }
+            None => Err(())
+        };
+        Ok(match metric{
+            "parsed cycles" => parsed(self)?.cycles,
+            "parsed cost" => parsed(self)?.cost,
+            "parsed area" => parsed(self)?.area,
+            "parsed instructions" => parsed(self)?.instructions,
+            "cycles" => self.result()?.metrics.cycles,
+            "cost" => self.files()?.1.cost(),
+            "area" => self.result()?.metrics.area,
+            "instructions" => self.files()?.1.instructions(),
+            "height" => self.result()?.extended.height,
+            "width*2" => (self.result()?.extended.width * 2.0) as i32,
+            "overlap" => {
+                let (puzzle, solution) = self.files()?;
+                find_overlaps(solution, puzzle).len() as i32
+            }
+            "throughput cycles" => self.slowest_rate()?.1 as i32,
+            "throughput outputs" => self.slowest_rate()?.0 as i32,
+            _ => {
+                self.set_error("unknown metric");
+                return Err(());
+            }
+        })
+    }
+
+    /// The reduced rate `(products, cycles)` of the product that's output least often in the solution's loop.
+    fn slowest_rate(&mut self) -> Result<(u64, u64), ()>{
+        let throughput = self.throughput()?;
+        match (0..throughput.outputs_per_loop.len()).min_by_key(|p| throughput.outputs_per_loop[*p]){
+            Some(slowest) => Ok(throughput.rate(slowest)),
+            None => {
+                self.set_error("puzzle has no products");
+                Err(())
+            }
+        }
+    }
+
+    fn approximate_metric(&mut self, metric: &str) -> Result<f64, ()>{
+        match metric{
+            "width" => Ok(self.result()?.extended.width as f64),
+            "throughput" => match self.throughput()?.cycles_per_product(){
+                Some(rate) => Ok(rate.to_f64()),
+                None => {
+                    self.set_error("solution does not output every product in its loop");
+                    Err(())
+                }
+            },
+            _ => self.metric(metric).map(|m| m as f64)
+        }
+    }
+}
+
+fn parse_file<T>(path: *const c_char, parse: fn(&[u8]) -> Result<T, ParseError>) -> Result<T, String>{
+    if path.is_null(){
+        return Err("no file given".to_owned());
+    }
+    // SAFETY: the caller promises that non-null paths are nul-terminated strings
+    let path = unsafe { CStr::from_ptr(path) }.to_string_lossy().into_owned();
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    parse(&data).map_err(|e| e.to_string())
+}
+
+/// Create a verifier from a puzzle file and a solution file.
+/// If either can't be read or parsed, the verifier is still created, with the reason reported by `verifier_error`.
+///
+/// # Safety
+/// Both paths must be nul-terminated strings. The verifier must be freed with `verifier_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_create(puzzle_filename: *const c_char, solution_filename: *const c_char) -> *mut Verifier{
+    let verifier = Verifier::new(parse_file(puzzle_filename, parse_puzzle), parse_file(solution_filename, parse_solution));
+    Box::into_raw(Box::new(verifier))
+}
+
+/// Create a verifier from the contents of a puzzle file and a solution file, which are copied.
+///
+/// # Safety
+/// Each pointer must point to at least the given number of bytes. The verifier must be freed with `verifier_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_create_from_bytes(puzzle_bytes: *const c_char, puzzle_length: c_int, solution_bytes: *const c_char, solution_length: c_int) -> *mut Verifier{
+    let bytes = |data: *const c_char, length: c_int| -> &[u8]{
+        if data.is_null() || length <= 0{
+            &[]
+        }else{
+            // SAFETY: the caller promises that `data` points to `length` bytes
+            unsafe { slice::from_raw_parts(data as *const u8, length as usize) }
+        }
+    };
+    let puzzle = parse_puzzle(bytes(puzzle_bytes, puzzle_length)).map_err(|e| e.to_string());
+    let solution = parse_solution(bytes(solution_bytes, solution_length)).map_err(|e| e.to_string());
+    Box::into_raw(Box::new(Verifier::new(puzzle, solution)))
+}
+
+/// Free a verifier. Strings returned by `verifier_error` are freed along with it.
+///
+/// # Safety
+/// The verifier must have been created by `verifier_create` or `verifier_create_from_bytes`, and not already destroyed. It may be null.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_destroy(verifier: *mut Verifier){
+    if !verifier.is_null(){
+        // SAFETY: the caller promises that this came from `Box::into_raw` and is only freed once
+        drop(unsafe { Box::from_raw(verifier) });
+    }
+}
+
+/// Set the number of cycles a solution may run for before simulation gives up, which is `DEFAULT_CYCLE_LIMIT` by default.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_set_cycle_limit(verifier: *mut Verifier, cycle_limit: c_int){
+    // SAFETY: the caller promises that the verifier is live
+    let verifier = unsafe { &mut *verifier };
+    verifier.cycle_limit = cycle_limit;
+    verifier.result = None;
+    verifier.throughput = None;
+}
+
+/// The last error that occurred, or null if none has since `verifier_error_clear`.
+/// The string is owned by the verifier, and is valid until the next call that takes it.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_error(verifier: *mut Verifier) -> *const c_char{
+    // SAFETY: the caller promises that the verifier is live
+    unsafe { &*verifier }.error.as_ref().map_or(ptr::null(), |e| e.as_ptr())
+}
+
+/// The cycle on which the last simulation error occurred, or 0 if it didn't happen on a particular cycle.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_error_cycle(verifier: *mut Verifier) -> c_int{
+    // SAFETY: the caller promises that the verifier is live
+    unsafe { &*verifier }.error_cycle
+}
+
+/// The `q` coordinate of the hex where the last collision occurred.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_error_location_u(verifier: *mut Verifier) -> c_int{
+    // SAFETY: the caller promises that the verifier is live
+    unsafe { &*verifier }.error_location.q
+}
+
+/// The `r` coordinate of the hex where the last collision occurred.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_error_location_v(verifier: *mut Verifier) -> c_int{
+    // SAFETY: the caller promises that the verifier is live
+    unsafe { &*verifier }.error_location.r
+}
+
+/// Forget the last error, so that `verifier_error` returns null until another occurs.
+///
+/// # Safety
+/// The verifier must be live.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_error_clear(verifier: *mut Verifier){
+    // SAFETY: the caller promises that the verifier is live
+    let verifier = unsafe { &mut *verifier };
+    verifier.error = None;
+    verifier.error_cycle = 0;
+    verifier.error_location = HexIndex::default();
+}
+
+/// Compute a metric by name, such as `"cycles"`, `"parsed cost"` or `"width*2"`, simulating the solution if needed.
+/// Returns -1 and sets an error if the metric is unknown or the solution couldn't be simulated.
+///
+/// # Safety
+/// The verifier must be live, and the metric a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_evaluate_metric(verifier: *mut Verifier, metric: *const c_char) -> c_int{
+    // SAFETY: the caller promises that the verifier is live and the metric is a string
+    let (verifier, metric) = unsafe { (&mut *verifier, CStr::from_ptr(metric)) };
+    verifier.metric(&metric.to_string_lossy()).unwrap_or(-1)
+}
+
+/// Compute a metric that may not be an integer, such as `"width"` or `"throughput"`, or any metric accepted by `verifier_evaluate_metric`.
+/// Returns -1 and sets an error if the metric is unknown or the solution couldn't be simulated.
+///
+/// # Safety
+/// The verifier must be live, and the metric a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn verifier_evaluate_approximate_metric(verifier: *mut Verifier, metric: *const c_char) -> f64{
+    // SAFETY: the caller promises that the verifier is live and the metric is a string
+    let (verifier, metric) = unsafe { (&mut *verifier, CStr::from_ptr(metric)) };
+    verifier.approximate_metric(&metric.to_string_lossy()).unwrap_or(-1.0)
+}
@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::data::*;
+
+// `Arbitrary` implementations that only generate structurally valid data, for fuzzing and property testing.
+// Everything generated can be encoded and parsed back, but isn't necessarily solvable or runnable.
+
+const ATOMS: [Atom; 16] = [
+    Atom::Salt, Atom::Air, Atom::Earth, Atom::Fire, Atom::Water,
+    Atom::Quicksilver, Atom::Vitae, Atom::Mors,
+    Atom::Lead, Atom::Tin, Atom::Iron, Atom::Copper, Atom::Silver, Atom::Gold,
+    Atom::Quintessence, Atom::Repeat
+];
+
+const PART_TYPES: [PartType; 24] = [
+    PartType::Input, PartType::Output, PartType::PolymerOutput,
+    PartType::Arm, PartType::BiArm, PartType::TriArm, PartType::HexArm, PartType::PistonArm,
+    PartType::Track, PartType::Berlo,
+    PartType::Equilibrium, PartType::Bonding, PartType::MultiBonding, PartType::Unbonding, PartType::Calcification,
+    PartType::Projection, PartType::Purification,
+    PartType::Duplication, PartType::Animismus,
+    PartType::Unification, PartType::Dispersion,
+    PartType::TriplexBonding,
+    PartType::Disposal,
+    PartType::Conduit
+];
+
+const INSTRUCTIONS: [Instruction; 13] = [
+    Instruction::Grab, Instruction::Drop,
+    Instruction::RotateClockwise, Instruction::RotateAnticlockwise,
+    Instruction::Extend, Instruction::Retract,
+    Instruction::PivotClockwise, Instruction::PivotAnticlockwise,
+    Instruction::Advance, Instruction::Retreat,
+    Instruction::PeriodOverride, Instruction::Reset, Instruction::Repeat
+];
+
+const CHAMBER_TYPES: [ChamberType; 6] = [
+    ChamberType::Small, ChamberType::SmallWide, ChamberType::SmallWider,
+    ChamberType::Medium, ChamberType::MediumWide,
+    ChamberType::Large
+];
+
+/// A short name made of characters that can appear in any file.
+fn name(u: &mut Unstructured) -> Result<String>{
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_ ";
+    let length = u.int_in_range(1..=24)?;
+    (0..length).map(|_| u.choose(CHARS).map(|c| *c as char)).collect()
+}
+
+/// A connected path of hexes starting at the origin, as used by track and conduits.
+fn path(u: &mut Unstructured, max_length: usize) -> Result<Vec<HexIndex>>{
+    let mut hexes = vec![HexIndex::default()];
+    for _ in 1..u.int_in_range(1..=max_length)?{
+        let next = *hexes.last().unwrap() + *u.choose(&HexIndex::DIRECTIONS)?;
+        if !hexes.contains(&next){
+            hexes.push(next);
+        }
+    }
+    Ok(hexes)
+}
+
+impl<'a> Arbitrary<'a> for HexIndex{
+    /// A hex near the origin, close enough to fit puzzle files' byte coordinates.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(HexIndex::new(u.int_in_range(-16..=16)?, u.int_in_range(-16..=16)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Atom{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        u.choose(&ATOMS).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for BondType{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        if u.ratio(3, 4)?{
+            return Ok(BondType::Normal);
+        }
+        // a triplex bond always has at least one colour
+        let (red, black) = (bool::arbitrary(u)?, bool::arbitrary(u)?);
+        Ok(BondType::Triplex{ red, black, yellow: !(red || black) || bool::arbitrary(u)? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Molecule{
+    /// A connected molecule, grown one atom at a time next to an existing one, and bonded to it.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        let mut atoms = HexMap::from([(HexIndex::default(), Atom::arbitrary(u)?)]);
+        let mut bonds = HashSet::new();
+        for _ in 1..u.int_in_range(1..=8)?{
+            let positions: Vec<HexIndex> = atoms.keys().copied().collect();
+            let from = *u.choose(&positions)?;
+            let to = from + *u.choose(&HexIndex::DIRECTIONS)?;
+            if atoms.contains_key(&to) || to.length() > 8{
+                continue;
+            }
+            atoms.insert(to, Atom::arbitrary(u)?);
+            bonds.insert(Bond{ start: from, end: to, ty: BondType::arbitrary(u)? });
+        }
+        Ok(Molecule{ atoms, bonds })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Permissions{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(Permissions::from_bits_truncate(u64::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ChamberType{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        u.choose(&CHAMBER_TYPES).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProductionInfo{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        let chambers = (0..u.int_in_range(0..=4)?)
+            .map(|_| Ok(Chamber{ pos: HexIndex::arbitrary(u)?, ty: ChamberType::arbitrary(u)? }))
+            .collect::<Result<_>>()?;
+        let conduits = (0..u.int_in_range(0..=2)?)
+            .map(|_| Ok(Conduit{ pos_a: HexIndex::arbitrary(u)?, pos_b: HexIndex::arbitrary(u)?, hexes: path(u, 4)? }))
+            .collect::<Result<_>>()?;
+        Ok(ProductionInfo{ isolation: bool::arbitrary(u)?, chambers, conduits })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Puzzle{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(Puzzle{
+            name: name(u)?,
+            creator_id: u64::arbitrary(u)?,
+            reagents: (0..u.int_in_range(1..=4)?).map(|_| Molecule::arbitrary(u)).collect::<Result<_>>()?,
+            products: (0..u.int_in_range(1..=4)?).map(|_| Molecule::arbitrary(u)).collect::<Result<_>>()?,
+            product_multiplier: u.int_in_range(1..=4)?,
+            permissions: Permissions::arbitrary(u)?,
+            production_info: if u.ratio(1, 4)? { Some(ProductionInfo::arbitrary(u)?) } else { None }
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for PartType{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        u.choose(&PART_TYPES).cloned()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Instruction{
+    /// Any instruction other than `Blank`, which never appears in solution files.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        u.choose(&INSTRUCTIONS).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Part{
+    /// A part with only the state its type uses: arms have a length and instructions in distinct slots, track and conduits have connected hexes.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        let ty = PartType::arbitrary(u)?;
+        let is_arm = ty.category() == PartCategory::Arm;
+        let mut instructions = Vec::new();
+        if is_arm{
+            let mut index = u.int_in_range(0..=4)?;
+            for _ in 0..u.int_in_range(0..=16)?{
+                instructions.push((Instruction::arbitrary(u)?, index));
+                index += u.int_in_range(1..=3)?;
+            }
+        }
+        Ok(Part{
+            pos: HexIndex::arbitrary(u)?,
+            rotation: u.int_in_range(0..=5)?,
+            arm_number: if is_arm { u.int_in_range(1..=8)? } else { 1 },
+            arm_length: if is_arm && ty != PartType::Berlo { u.int_in_range(1..=3)? } else { 1 },
+            index: if matches!(ty, PartType::Input | PartType::Output | PartType::PolymerOutput) { u.int_in_range(0..=3)? } else { 0 },
+            conduit_index: if ty == PartType::Conduit { u.int_in_range(0..=1)? } else { 0 },
+            track_hexes: if ty == PartType::Track { path(u, 8)? } else { Vec::new() },
+            conduit_hexes: if ty == PartType::Conduit { path(u, 4)? } else { Vec::new() },
+            ty,
+            instructions
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Metrics{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(Metrics{
+            cycles: u.int_in_range(1..=100_000)?,
+            cost: u.int_in_range(0..=10_000)?,
+            area: u.int_in_range(0..=10_000)?,
+            instructions: u.int_in_range(0..=10_000)?
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Solution{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(Solution{
+            name: name(u)?,
+            puzzle_name: name(u)?,
+            metrics: Option::<Metrics>::arbitrary(u)?,
+            parts: (0..u.int_in_range(0..=12)?).map(|_| Part::arbitrary(u)).collect::<Result<_>>()?
+        })
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::{btree_map, BTreeMap};
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Index;
+use std::{slice, vec};
+
+use crate::data::HexIndex;
+
+// A map keyed by hex, for things like the atoms of a molecule or the hexes a solution covers.
+
+/// A map from hexes to values, iterated in canonical order: by `r`, then by `q`.
+/// Entries are kept in a grid covering their bounding box while they're close together, which makes lookups and neighbour queries cheap,
+/// and in a sorted tree once they're too spread out for the grid to be worth its size.
+#[derive(Clone)]
+pub struct HexMap<T>{
+    storage: Storage<T>,
+    len: usize
+}
+
+#[derive(Clone)]
+enum Storage<T>{
+    /// A grid of `width` by `height` cells, row by row up the R axis from `min`.
+    Dense{ min: HexIndex, width: i32, height: i32, cells: Vec<Option<(HexIndex, T)>> },
+    /// Entries by `(r, q)`.
+    Sparse(BTreeMap<(i32, i32), (HexIndex, T)>)
+}
+
+/// The number of cells a grid may have per entry before switching to a tree, once it's larger than `MIN_DENSE_CELLS`.
+const CELLS_PER_ENTRY: usize = 4;
+/// The number of cells a grid may always have, however few entries there are.
+const MIN_DENSE_CELLS: usize = 64;
+
+impl<T> HexMap<T>{
+    pub fn new() -> Self{
+        HexMap{ storage: Storage::Dense{ min: HexIndex::default(), width: 0, height: 0, cells: Vec::new() }, len: 0 }
+    }
+
+    pub fn len(&self) -> usize{
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len == 0
+    }
+
+    pub fn clear(&mut self){
+        *self = HexMap::new();
+    }
+
+    pub fn get(&self, pos: &HexIndex) -> Option<&T>{
+        match &self.storage{
+            Storage::Dense{ min, width, height, cells } => cell(*min, *width, *height, *pos).and_then(|i| cells[i].as_ref()).map(|(_, v)| v),
+            Storage::Sparse(entries) => entries.get(&(pos.r, pos.q)).map(|(_, v)| v)
+        }
+    }
+
+    pub fn get_mut(&mut self, pos: &HexIndex) -> Option<&mut T>{
+        match &mut self.storage{
+            Storage::Dense{ min, width, height, cells } => cell(*min, *width, *height, *pos).and_then(|i| cells[i].as_mut()).map(|(_, v)| v),
+            Storage::Sparse(entries) => entries.get_mut(&(pos.r, pos.q)).map(|(_, v)| v)
+        }
+    }
+
+    pub fn contains_key(&self, pos: &HexIndex) -> bool{
+        self.get(pos).is_some()
+    }
+
+    /// Insert a value at a hex, returning the value that was there before, if any.
+    pub fn insert(&mut self, pos: HexIndex, value: T) -> Option<T>{
+        if let Storage::Dense{ min, width, height, .. } = &self.storage{
+            if cell(*min, *width, *height, pos).is_none(){
+                self.grow_to(pos);
+            }
+        }
+        let previous = match &mut self.storage{
+            Storage::Dense{ min, width, height, cells } => {
+                let i = cell(*min, *width, *height, pos).expect("grid should have grown to cover the hex");
+                cells[i].replace((pos, value)).map(|(_, v)| v)
+            }
+            Storage::Sparse(entries) => entries.insert((pos.r, pos.q), (pos, value)).map(|(_, v)| v)
+        };
+        if previous.is_none(){
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Remove the value at a hex, returning it if there was one.
+    pub fn remove(&mut self, pos: &HexIndex) -> Option<T>{
+        let removed = match &mut self.storage{
+            Storage::Dense{ min, width, height, cells } => cell(*min, *width, *height, *pos).and_then(|i| cells[i].take()),
+            Storage::Sparse(entries) => entries.remove(&(pos.r, pos.q))
+        };
+        if removed.is_some(){
+            self.len -= 1;
+        }
+        removed.map(|(_, v)| v)
+    }
+
+    /// Make room for a hex outside the grid, by growing the grid to cover it or switching to a tree if the grid would be too sparse.
+    /// The grid grows by half again in the direction it's extended where there's room, so that maps built a hex at a time aren't copied on every insert.
+    fn grow_to(&mut self, pos: HexIndex){
+        let Storage::Dense{ min, width, height, cells } = &mut self.storage else { return };
+        let max_cells = MIN_DENSE_CELLS.max((self.len + 1) * CELLS_PER_ENTRY);
+        // bounds are `(min q, min r, max q, max r)`, widened so that maps spanning the whole range of `i32` don't overflow
+        let area = |(min_q, min_r, max_q, max_r): (i64, i64, i64, i64)| ((max_q - min_q + 1) as usize).saturating_mul((max_r - min_r + 1) as usize);
+        let (pos_q, pos_r) = (i64::from(pos.q), i64::from(pos.r));
+        let bounds = if *width == 0{
+            (pos_q, pos_r, pos_q, pos_r)
+        }else{
+            let (min_q, min_r) = (i64::from(min.q), i64::from(min.r));
+            let (max_q, max_r) = (min_q + i64::from(*width) - 1, min_r + i64::from(*height) - 1);
+            let exact = (min_q.min(pos_q), min_r.min(pos_r), max_q.max(pos_q), max_r.max(pos_r));
+            let (slack_q, slack_r) = (i64::from((*width / 2).max(1)), i64::from((*height / 2).max(1)));
+            let clamp = |n: i64| n.clamp(i64::from(i32::MIN), i64::from(i32::MAX));
+            let padded = (
+                if pos_q < min_q { clamp(pos_q - slack_q) } else { min_q },
+                if pos_r < min_r { clamp(pos_r - slack_r) } else { min_r },
+                if pos_q > max_q { clamp(pos_q + slack_q) } else { max_q },
+                if pos_r > max_r { clamp(pos_r + slack_r) } else { max_r }
+            );
+            if area(padded) <= max_cells { padded } else { exact }
+        };
+        let (new_width, new_height) = ((bounds.2 - bounds.0 + 1) as usize, (bounds.3 - bounds.1 + 1) as usize);
+        let new_min = HexIndex{ q: bounds.0 as i32, r: bounds.1 as i32 };
+        let entries = std::mem::take(cells).into_iter().flatten();
+        if area(bounds) > max_cells{
+            self.storage = Storage::Sparse(entries.map(|(k, v)| ((k.r, k.q), (k, v))).collect());
+        }else{
+            let mut grown: Vec<Option<(HexIndex, T)>> = std::iter::repeat_with(|| None).take(new_width * new_height).collect();
+            for (k, v) in entries{
+                let i = cell(new_min, new_width as i32, new_height as i32, k).expect("grown grid should cover every entry");
+                grown[i] = Some((k, v));
+            }
+            self.storage = Storage::Dense{ min: new_min, width: new_width as i32, height: new_height as i32, cells: grown };
+        }
+    }
+
+    /// Every entry, in canonical order.
+    pub fn iter(&self) -> Iter<'_, T>{
+        Iter(match &self.storage{
+            Storage::Dense{ cells, .. } => IterInner::Dense(cells.iter()),
+            Storage::Sparse(entries) => IterInner::Sparse(entries.values())
+        })
+    }
+
+    /// Every entry, in canonical order, with mutable values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T>{
+        IterMut(match &mut self.storage{
+            Storage::Dense{ cells, .. } => IterMutInner::Dense(cells.iter_mut()),
+            Storage::Sparse(entries) => IterMutInner::Sparse(entries.values_mut())
+        })
+    }
+
+    /// Every hex with a value, in canonical order.
+    pub fn keys(&self) -> impl Iterator<Item = &HexIndex> + '_{
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Every value, in canonical order of their hexes.
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_{
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_{
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// The smallest and largest `q` and `r` of any hex with a value, as `(min, max)`, or `None` if the map is empty.
+    pub fn bounds(&self) -> Option<(HexIndex, HexIndex)>{
+        self.keys().fold(None, |bounds, k| Some(match bounds{
+            None => (*k, *k),
+            Some((min, max)) => (HexIndex{ q: min.q.min(k.q), r: min.r.min(k.r) }, HexIndex{ q: max.q.max(k.q), r: max.r.max(k.r) })
+        }))
+    }
+
+    /// Every entry whose `q` and `r` are within those of `min` and `max` inclusive, in canonical order.
+    pub fn within(&self, min: HexIndex, max: HexIndex) -> impl Iterator<Item = (&HexIndex, &T)> + '_{
+        let (dense, sparse) = match &self.storage{
+            Storage::Dense{ cells, .. } => (Some(cells.iter().flatten()), None),
+            Storage::Sparse(entries) => (None, (min.r <= max.r).then(|| entries.range((min.r, min.q)..=(max.r, max.q)).map(|(_, e)| e)))
+        };
+        dense.into_iter().flatten().chain(sparse.into_iter().flatten())
+            .filter(move |(k, _)| (min.q..=max.q).contains(&k.q) && (min.r..=max.r).contains(&k.r))
+            .map(|(k, v)| (k, v))
+    }
+
+    /// The values in the hexes adjacent to a hex, in the order of `HexIndex::DIRECTIONS`.
+    pub fn neighbors(&self, pos: HexIndex) -> impl Iterator<Item = (HexIndex, &T)> + '_{
+        pos.neighbors().into_iter().filter_map(|n| self.get(&n).map(|v| (n, v)))
+    }
+}
+
+/// The index of a hex's cell in a grid, if the grid covers it.
+fn cell(min: HexIndex, width: i32, height: i32, pos: HexIndex) -> Option<usize>{
+    let (q, r) = (i64::from(pos.q) - i64::from(min.q), i64::from(pos.r) - i64::from(min.r));
+    ((0..i64::from(width)).contains(&q) && (0..i64::from(height)).contains(&r)).then(|| (r * i64::from(width) + q) as usize)
+}
+
+impl HexMap<()>{
+    /// Add a hex to a map used as a set, returning whether it wasn't already present.
+    pub fn insert_hex(&mut self, pos: HexIndex) -> bool{
+        self.insert(pos, ()).is_none()
+    }
+}
+
+impl<T> Default for HexMap<T>{
+    fn default() -> Self{
+        HexMap::new()
+    }
+}
+
+impl<T: Debug> Debug for HexMap<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for HexMap<T>{
+    fn eq(&self, other: &Self) -> bool{
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<T: Eq> Eq for HexMap<T>{}
+
+impl<T: Hash> Hash for HexMap<T>{
+    fn hash<H: Hasher>(&self, state: &mut H){
+        self.len.hash(state);
+        for entry in self{
+            entry.hash(state);
+        }
+    }
+}
+
+impl<T> Index<&HexIndex> for HexMap<T>{
+    type Output = T;
+
+    fn index(&self, pos: &HexIndex) -> &T{
+        self.get(pos).expect("no value at hex")
+    }
+}
+
+impl<T> FromIterator<(HexIndex, T)> for HexMap<T>{
+    fn from_iter<I: IntoIterator<Item = (HexIndex, T)>>(iter: I) -> Self{
+        let mut map = HexMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<T> Extend<(HexIndex, T)> for HexMap<T>{
+    fn extend<I: IntoIterator<Item = (HexIndex, T)>>(&mut self, iter: I){
+        for (k, v) in iter{
+            self.insert(k, v);
+        }
+    }
+}
+
+impl FromIterator<HexIndex> for HexMap<()>{
+    fn from_iter<I: IntoIterator<Item = HexIndex>>(iter: I) -> Self{
+        iter.into_iter().map(|k| (k, ())).collect()
+    }
+}
+
+impl Extend<HexIndex> for HexMap<()>{
+    fn extend<I: IntoIterator<Item = HexIndex>>(&mut self, iter: I){
+        self.extend(iter.into_iter().map(|k| (k, ())));
+    }
+}
+
+impl<T, const N: usize> From<[(HexIndex, T); N]> for HexMap<T>{
+    fn from(entries: [(HexIndex, T); N]) -> Self{
+        entries.into_iter().collect()
+    }
+}
+
+/// An iterator over the entries of a `HexMap`, in canonical order.
+pub struct Iter<'a, T>(IterInner<'a, T>);
+
+enum IterInner<'a, T>{
+    Dense(slice::Iter<'a, Option<(HexIndex, T)>>),
+    Sparse(btree_map::Values<'a, (i32, i32), (HexIndex, T)>)
+}
+
+impl<'a, T> Iterator for Iter<'a, T>{
+    type Item = (&'a HexIndex, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item>{
+        let (k, v) = match &mut self.0{
+            IterInner::Dense(cells) => cells.find_map(Option::as_ref)?,
+            IterInner::Sparse(entries) => entries.next()?
+        };
+        Some((k, v))
+    }
+}
+
+/// An iterator over the entries of a `HexMap` with mutable values, in canonical order.
+pub struct IterMut<'a, T>(IterMutInner<'a, T>);
+
+enum IterMutInner<'a, T>{
+    Dense(slice::IterMut<'a, Option<(HexIndex, T)>>),
+    Sparse(btree_map::ValuesMut<'a, (i32, i32), (HexIndex, T)>)
+}
+
+impl<'a, T> Iterator for IterMut<'a, T>{
+    type Item = (&'a HexIndex, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item>{
+        let (k, v) = match &mut self.0{
+            IterMutInner::Dense(cells) => cells.find_map(Option::as_mut)?,
+            IterMutInner::Sparse(entries) => entries.next()?
+        };
+        Some((&*k, v))
+    }
+}
+
+/// An iterator that takes the entries out of a `HexMap`, in canonical order.
+pub struct IntoIter<T>(IntoIterInner<T>);
+
+enum IntoIterInner<T>{
+    Dense(vec::IntoIter<Option<(HexIndex, T)>>),
+    Sparse(btree_map::IntoValues<(i32, i32), (HexIndex, T)>)
+}
+
+impl<T> Iterator for IntoIter<T>{
+    type Item = (HexIndex, T);
+
+    fn next(&mut self) -> Option<Self::Item>{
+        match &mut self.0{
+            IntoIterInner::Dense(cells) => cells.find_map(|c| c),
+            IntoIterInner::Sparse(entries) => entries.next()
+        }
+    }
+}
+
+impl<T> IntoIterator for HexMap<T>{
+    type Item = (HexIndex, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T>{
+        IntoIter(match self.storage{
+            Storage::Dense{ cells, .. } => IntoIterInner::Dense(cells.into_iter()),
+            Storage::Sparse(entries) => IntoIterInner::Sparse(entries.into_values())
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a HexMap<T>{
+    type Item = (&'a HexIndex, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T>{
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut HexMap<T>{
+    type Item = (&'a HexIndex, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T>{
+        self.iter_mut()
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T: bincode::Encode> bincode::Encode for HexMap<T>{
+    fn encode<E: bincode::enc::Encoder>(&self, encoder: &mut E) -> Result<(), bincode::error::EncodeError>{
+        self.iter().collect::<Vec<_>>().encode(encoder)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<Context, T: bincode::Decode<Context>> bincode::Decode<Context> for HexMap<T>{
+    fn decode<D: bincode::de::Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, bincode::error::DecodeError>{
+        Ok(Vec::<(HexIndex, T)>::decode(decoder)?.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<'de, Context, T: bincode::BorrowDecode<'de, Context>> bincode::BorrowDecode<'de, Context> for HexMap<T>{
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de, Context = Context>>(decoder: &mut D) -> Result<Self, bincode::error::DecodeError>{
+        Ok(Vec::<(HexIndex, T)>::borrow_decode(decoder)?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn hex(q: i32, r: i32) -> HexIndex{
+        HexIndex{ q, r }
+    }
+
+    #[test]
+    fn lookups_match_inserts_as_the_map_grows(){
+        let mut map = HexMap::new();
+        for i in 0..200{
+            let pos = hex(i % 17 - 8, i / 17 - 5);
+            assert_eq!(map.insert(pos, i), None);
+            assert_eq!(map.get(&pos), Some(&i));
+        }
+        assert_eq!(map.len(), 200);
+        assert_eq!(map.insert(hex(-8, -5), 1000), Some(0));
+        assert_eq!(map.remove(&hex(-8, -5)), Some(1000));
+        assert!(!map.contains_key(&hex(-8, -5)));
+        assert_eq!(map.len(), 199);
+    }
+
+    #[test]
+    fn iterates_in_canonical_order(){
+        let hexes = [hex(3, 1), hex(-2, 0), hex(0, 1), hex(5, -4), hex(1000, 0)];
+        let map: HexMap<()> = hexes.into_iter().collect();
+        let keys: Vec<_> = map.keys().copied().collect();
+        assert_eq!(keys, [hex(5, -4), hex(-2, 0), hex(1000, 0), hex(0, 1), hex(3, 1)]);
+    }
+
+    #[test]
+    fn extreme_hexes_fall_back_to_a_tree(){
+        let mut map = HexMap::new();
+        let hexes = [hex(i32::MIN, i32::MIN), hex(i32::MAX, i32::MAX), hex(i32::MAX, i32::MIN), hex(0, 0), hex(i32::MAX - 1, i32::MAX)];
+        for pos in hexes{
+            assert!(map.insert_hex(pos));
+        }
+        for pos in hexes{
+            assert!(map.contains_key(&pos));
+        }
+        assert!(!map.contains_key(&hex(i32::MIN, i32::MAX)));
+        assert_eq!(map.bounds(), Some((hex(i32::MIN, i32::MIN), hex(i32::MAX, i32::MAX))));
+    }
+
+    #[test]
+    fn grids_near_the_edge_of_the_range_grow(){
+        let mut map = HexMap::new();
+        for q in 0..10{
+            map.insert_hex(hex(i32::MAX - q, i32::MIN + q));
+        }
+        assert_eq!(map.len(), 10);
+        assert!((0..10).all(|q| map.contains_key(&hex(i32::MAX - q, i32::MIN + q))));
+    }
+}
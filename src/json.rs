@@ -0,0 +1,29 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// JSON import and export of parsed data, for tools that don't speak the game's binary formats.
+
+/// Encode a value (such as a `Puzzle` or `Solution`) as JSON.
+pub fn to_json<T: Serialize>(value: &T) -> String{
+    serde_json::to_string(value).expect("encoding to a string should not fail")
+}
+
+/// Decode a value encoded as JSON, such as by `to_json`.
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, &'static str>{
+    serde_json::from_str(json).map_err(|_| "invalid JSON data")
+}
+
+/// Serializes a molecule's atoms as a list of `[position, atom]` pairs, since JSON object keys must be strings.
+pub(crate) mod atom_map{
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::data::{Atom, HexIndex, HexMap};
+
+    pub fn serialize<S: Serializer>(atoms: &HexMap<Atom>, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_seq(atoms.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HexMap<Atom>, D::Error>{
+        Ok(Vec::<(HexIndex, Atom)>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
@@ -0,0 +1,54 @@
+use serde_json::{json, Value};
+
+use crate::data::{Puzzle, Solution};
+use crate::sim::{SimError, SimOptions};
+use crate::validate::solution_properties;
+use crate::verify::{throughput, verify_with_options, DEFAULT_CYCLE_LIMIT};
+
+// Export of verified solutions as community leaderboard records.
+
+/// Links to media hosted elsewhere, included in a leaderboard record if present.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordLinks{
+    /// A GIF of the solution running.
+    pub gif: Option<String>,
+    /// The solution file itself.
+    pub solution: Option<String>
+}
+
+/// Verify a solution and describe it as a leaderboard record, with its score, flags, and links.
+/// Solutions with overlapping parts are accepted, and flagged as such.
+/// Rate is the exact average number of cycles per product once the solution loops, written as a fraction like `"25/2"` so that ties can be detected,
+/// and is null for solutions that never settle into a loop.
+pub fn leaderboard_record(puzzle: &Puzzle, solution: &Solution, links: &RecordLinks) -> Result<Value, SimError>{
+    let options = SimOptions{ allow_overlap: true, strict_drops: false };
+    let result = verify_with_options(puzzle, solution, DEFAULT_CYCLE_LIMIT, options)?;
+    let metrics = result.metrics;
+    let properties = solution_properties(solution, puzzle);
+    let rate = throughput(puzzle, solution, DEFAULT_CYCLE_LIMIT, options).ok().and_then(|t| t.cycles_per_product()).map(|r| r.to_string());
+
+    let mut formatted = format!("{}g/{}c/{}a/{}i", metrics.cost, metrics.cycles, metrics.area, metrics.instructions);
+    if properties.trackless{
+        formatted.push_str("/T");
+    }
+    if properties.overlap{
+        formatted.push_str("/O");
+    }
+    Ok(json!({
+        "puzzle": { "id": puzzle.name },
+        "fullFormattedScore": formatted,
+        "score": {
+            "cost": metrics.cost,
+            "cycles": metrics.cycles,
+            "area": metrics.area,
+            "instructions": metrics.instructions,
+            "height": result.extended.height,
+            "width": result.extended.width,
+            "rate": rate,
+            "trackless": properties.trackless,
+            "overlap": properties.overlap
+        },
+        "gif": links.gif,
+        "solution": links.solution
+    }))
+}
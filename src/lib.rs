@@ -1,3 +1,39 @@
 pub mod parse;
+pub mod encode;
 pub mod data;
+pub mod tape;
 pub mod sim;
+pub mod verify;
+pub mod validate;
+pub mod analysis;
+pub mod sigmar;
+pub mod edit;
+pub mod diff;
+pub mod builder;
+pub mod store;
+pub mod hex_map;
+pub mod notation;
+pub mod metric;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "bincode")]
+pub mod cache;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "serde")]
+pub mod json;
+#[cfg(feature = "serde")]
+pub mod leaderboard;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "gif")]
+pub mod animation;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod vcd;
@@ -0,0 +1,203 @@
+use std::fmt::{Display, Formatter};
+
+use crate::analysis::steady_state::find_steady_state;
+use crate::data::{Puzzle, Solution};
+use crate::sim::SimOptions;
+use crate::validate::{find_overlaps, solution_properties};
+use crate::verify::{Rational, VerificationResult, DEFAULT_CYCLE_LIMIT};
+
+// Metric expressions in the style of omsim, so that community categories can be scored without new code.
+//
+// An expression is a sum of products, like `cycles+area` or `2*cost+instructions`. Each factor is a whole number or a metric:
+// `cost`, `cycles`, `area`, `instructions`, `height`, `width`, `overlap` (the number of overlapping pairs of parts), or
+// `trackless` (1 for solutions without track, otherwise 0). A metric may be prefixed with `product N` to measure it when the
+// output for product `N`, counting from 0, completes, or with `per repetition` to measure how much it grows for every product
+// output once the solution settles into a loop.
+
+/// A reason a metric expression couldn't be read or measured.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetricError{
+    /// Part of the expression isn't a number or a known metric.
+    UnknownMetric(String),
+    /// A known metric that can't be measured with the prefix it was given, like `product 0 height`.
+    Unsupported(String),
+    /// The expression refers to a product the puzzle doesn't have.
+    NoSuchProduct(usize),
+    /// A `per repetition` metric was measured for a solution that never settles into a loop that outputs every product.
+    NoRepetition,
+    /// The value of the expression is too large to represent.
+    Overflow
+}
+
+impl Display for MetricError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        match self{
+            MetricError::UnknownMetric(text) => write!(f, "unknown metric \"{text}\""),
+            MetricError::Unsupported(text) => write!(f, "metric \"{text}\" can't be measured"),
+            MetricError::NoSuchProduct(index) => write!(f, "puzzle has no product {index}"),
+            MetricError::NoRepetition => write!(f, "solution never settles into a loop that outputs every product"),
+            MetricError::Overflow => write!(f, "value is too large")
+        }
+    }
+}
+
+impl std::error::Error for MetricError{}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Metric{
+    Cost, Cycles, Area, Instructions, Height, Width, Overlap, Trackless
+}
+
+/// When a metric is measured.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scope{
+    Completion,
+    Product(usize),
+    PerRepetition
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Factor{
+    Number(u64),
+    Metric{ scope: Scope, metric: Metric, text: String }
+}
+
+/// A parsed metric expression, which can be measured for any number of verified solutions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetricExpression{
+    /// The terms that are added together, each a list of factors that are multiplied together.
+    terms: Vec<Vec<Factor>>
+}
+
+impl MetricExpression{
+    /// Read a metric expression, such as `cycles+area` or `per repetition cycles`.
+    pub fn parse(text: &str) -> Result<MetricExpression, MetricError>{
+        let terms = text.split('+')
+            .map(|term| term.split('*').map(parse_factor).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MetricExpression{ terms })
+    }
+
+    /// Measure this expression for a solution that's been verified, with `result` being the result of verifying it.
+    /// `per repetition` metrics simulate the solution again to find its loop, allowing overlap since it was already verified.
+    pub fn evaluate(&self, puzzle: &Puzzle, solution: &Solution, result: &VerificationResult) -> Result<Rational, MetricError>{
+        let mut total = Rational::from(0);
+        for term in &self.terms{
+            let mut product = Rational::from(1);
+            for factor in term{
+                let value = match factor{
+                    Factor::Number(n) => Rational::from(*n),
+                    Factor::Metric{ scope, metric, text } => measure(*scope, *metric, puzzle, solution, result).ok_or_else(|| MetricError::Unsupported(text.clone()))??
+                };
+                product = product.checked_mul(value).ok_or(MetricError::Overflow)?;
+            }
+            total = total.checked_add(product).ok_or(MetricError::Overflow)?;
+        }
+        Ok(total)
+    }
+}
+
+fn parse_factor(text: &str) -> Result<Factor, MetricError>{
+    let unknown = || MetricError::UnknownMetric(text.trim().to_owned());
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (scope, name) = match words.as_slice(){
+        [number] if number.bytes().all(|b| b.is_ascii_digit()) => return number.parse().map(Factor::Number).map_err(|_| unknown()),
+        [name] => (Scope::Completion, *name),
+        ["product", index, name] => (Scope::Product(index.parse().map_err(|_| unknown())?), *name),
+        ["per", "repetition", name] => (Scope::PerRepetition, *name),
+        _ => return Err(unknown())
+    };
+    let metric = match name{
+        "cost" => Metric::Cost,
+        "cycles" => Metric::Cycles,
+        "area" => Metric::Area,
+        "instructions" => Metric::Instructions,
+        "height" => Metric::Height,
+        "width" => Metric::Width,
+        "overlap" => Metric::Overlap,
+        "trackless" => Metric::Trackless,
+        _ => return Err(unknown())
+    };
+    Ok(Factor::Metric{ scope, metric, text: words.join(" ") })
+}
+
+/// Measure a single metric, or `None` if it can't be measured in the given scope.
+fn measure(scope: Scope, metric: Metric, puzzle: &Puzzle, solution: &Solution, result: &VerificationResult) -> Option<Result<Rational, MetricError>>{
+    let whole = |n: i64| Rational::from(n.max(0) as u64);
+    if let Scope::Product(index) = scope{
+        if index >= result.product_completion_cycles.len(){
+            return Some(Err(MetricError::NoSuchProduct(index)));
+        }
+    }
+    // parts don't change while a solution runs, so these are the same however they're measured
+    match metric{
+        Metric::Cost => return (scope != Scope::PerRepetition).then(|| Ok(whole(solution.cost() as i64))),
+        Metric::Instructions => return (scope != Scope::PerRepetition).then(|| Ok(whole(solution.instructions() as i64))),
+        Metric::Overlap => return (scope != Scope::PerRepetition).then(|| Ok(whole(find_overlaps(solution, puzzle).len() as i64))),
+        Metric::Trackless => return (scope != Scope::PerRepetition).then(|| Ok(whole(solution_properties(solution, puzzle).trackless as i64))),
+        _ => {}
+    }
+    match scope{
+        Scope::Completion => Some(Ok(match metric{
+            Metric::Cycles => whole(result.metrics.cycles as i64),
+            Metric::Area => whole(result.metrics.area as i64),
+            Metric::Height => whole(result.extended.height as i64),
+            _ => Rational::new((result.extended.width * 2.0) as u64, 2)
+        })),
+        Scope::Product(index) => {
+            let cycle = result.product_completion_cycles[index];
+            match metric{
+                Metric::Cycles => Some(Ok(whole(cycle as i64))),
+                Metric::Area => Some(Ok(whole(result.area_changes.iter().take_while(|c| c.cycle <= cycle).last().map_or(0, |c| c.area) as i64))),
+                _ => None
+            }
+        }
+        Scope::PerRepetition => {
+            if !matches!(metric, Metric::Cycles | Metric::Area){
+                return None;
+            }
+            let Ok(steady) = find_steady_state(puzzle, solution, DEFAULT_CYCLE_LIMIT, SimOptions{ allow_overlap: true, strict_drops: false }) else { return Some(Err(MetricError::NoRepetition)) };
+            let Some(slowest) = steady.throughput.outputs_per_loop.iter().min().copied().filter(|n| *n > 0) else { return Some(Err(MetricError::NoRepetition)) };
+            let grown = if metric == Metric::Cycles { steady.throughput.loop_cycles as u64 } else { steady.area_growth_per_loop as u64 };
+            Some(Ok(Rational::new(grown, slowest)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::{Atom, HexIndex, Molecule};
+    use crate::verify::verify;
+
+    fn evaluate(expression: &str) -> Result<Rational, MetricError>{
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle")
+            .input(0, HexIndex{ q: 1, r: 0 }, 0)
+            .output(0, HexIndex{ q: -1, r: 0 }, 0)
+            .arm(HexIndex::default(), 0, 1)
+            .instructions(0, "GRRRgX")
+            .build().unwrap();
+        let result = verify(&puzzle, &solution).unwrap();
+        MetricExpression::parse(expression)?.evaluate(&puzzle, &solution, &result)
+    }
+
+    #[test]
+    fn expressions_combine_metrics(){
+        assert_eq!(evaluate("cost"), Ok(Rational::from(20)));
+        assert_eq!(evaluate("2*cost+3"), Ok(Rational::from(43)));
+        assert_eq!(evaluate("per repetition area"), Ok(Rational::from(0)));
+        assert_eq!(evaluate("product 1 cycles"), Err(MetricError::NoSuchProduct(1)));
+        assert_eq!(evaluate("product 0 height"), Err(MetricError::Unsupported("product 0 height".to_owned())));
+        assert_eq!(evaluate("speed"), Err(MetricError::UnknownMetric("speed".to_owned())));
+    }
+
+    #[test]
+    fn overflow_is_an_error(){
+        assert_eq!(evaluate("18446744073709551615"), Ok(Rational::from(u64::MAX)));
+        assert_eq!(evaluate("18446744073709551615*2"), Err(MetricError::Overflow));
+        assert_eq!(evaluate("18446744073709551615+cost"), Err(MetricError::Overflow));
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::data::{Puzzle, Solution};
+use crate::parse::{parse_puzzle, parse_solution};
+
+// Parsing directly from memory-mapped files, to avoid copying every file into a buffer when scanning large archives.
+
+/// A read-only memory mapping of a whole file.
+/// The file must not be modified or truncated by anything else while it's mapped.
+#[derive(Debug)]
+pub struct MappedFile{
+    map: Mmap
+}
+
+impl MappedFile{
+    pub fn open(path: &Path) -> Result<MappedFile, &'static str>{
+        let file = File::open(path).map_err(|_| "could not open file")?;
+        // SAFETY: the mapping is read-only, and callers are told not to modify mapped files
+        let map = unsafe { Mmap::map(&file) }.map_err(|_| "could not map file")?;
+        Ok(MappedFile{ map })
+    }
+
+    pub fn bytes(&self) -> &[u8]{
+        &self.map
+    }
+}
+
+impl Deref for MappedFile{
+    type Target = [u8];
+    fn deref(&self) -> &[u8]{
+        self.bytes()
+    }
+}
+
+/// Map and parse a puzzle file.
+pub fn parse_puzzle_file(path: &Path) -> Result<Puzzle, &'static str>{
+    Ok(parse_puzzle(&MappedFile::open(path)?)?)
+}
+
+/// Map and parse a solution file.
+pub fn parse_solution_file(path: &Path) -> Result<Solution, &'static str>{
+    Ok(parse_solution(&MappedFile::open(path)?)?)
+}
@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::data::{Atom, Bond, BondType, HexIndex, HexMap, Molecule};
+
+// A text notation for molecules, laid out as hex rows so they can be written and read by hand.
+//
+// Each atom is a two-letter element code, such as `Sa` for salt, and atoms in the same row are four columns apart.
+// Rows are drawn from the highest R at the top, each shifted two columns to the right of the row below,
+// with a line between each pair of rows for the bonds that cross them:
+//
+//       Fi
+//      /  \
+//    Sa--Ai
+//
+// Normal bonds are `--` between atoms in a row, `/` to the upper-right neighbour, and `\` to the upper-left.
+// Triplex bonds are written the same way, using the digit of their colours (red 1, black 2, yellow 4) in place of the marker,
+// so a full triplex bond is `77` or `7`. The atom at the origin is written in capitals (`SA`), or as `()` if there's no atom there;
+// without either, the lowest, leftmost atom is placed at the origin.
+
+/// A reason text couldn't be read as a molecule, with the line and column it was found at, both counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotationError{
+    pub line: usize,
+    pub column: usize,
+    pub reason: &'static str
+}
+
+impl Display for NotationError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.reason)
+    }
+}
+
+impl std::error::Error for NotationError{}
+
+/// Element codes, in the order of `Atom` variants.
+const CODES: [(Atom, &str); 16] = [
+    (Atom::Salt, "Sa"), (Atom::Air, "Ai"), (Atom::Earth, "Ea"), (Atom::Fire, "Fi"), (Atom::Water, "Wa"),
+    (Atom::Quicksilver, "Qs"), (Atom::Vitae, "Vi"), (Atom::Mors, "Mo"),
+    (Atom::Lead, "Le"), (Atom::Tin, "Ti"), (Atom::Iron, "Ir"), (Atom::Copper, "Co"), (Atom::Silver, "Si"), (Atom::Gold, "Go"),
+    (Atom::Quintessence, "Qn"),
+    (Atom::Repeat, "Re")
+];
+
+/// The two-letter code used for an atom type in molecule notation, or `None` for unknown atoms, which have no code.
+pub fn atom_code(atom: Atom) -> Option<&'static str>{
+    CODES.iter().find(|(a, _)| *a == atom).map(|(_, code)| *code)
+}
+
+/// The atom type with a two-letter code, ignoring case.
+pub fn atom_from_code(code: &str) -> Option<Atom>{
+    CODES.iter().find(|(_, c)| c.eq_ignore_ascii_case(code)).map(|(a, _)| *a)
+}
+
+/// The bond type written with a marker, given the marker normal bonds use in that direction.
+fn bond_type(marker: char, normal: char) -> Option<BondType>{
+    match marker.to_digit(10){
+        _ if marker == normal => Some(BondType::Normal),
+        Some(colours @ 1..=7) => Some(BondType::Triplex{ red: colours & 1 != 0, black: colours & 2 != 0, yellow: colours & 4 != 0 }),
+        _ => None
+    }
+}
+
+/// Read a molecule written in molecule notation.
+/// Leading and trailing blank lines are ignored, as is indentation shared by every row.
+pub fn parse_molecule(text: &str) -> Result<Molecule, NotationError>{
+    let all_lines: Vec<Vec<char>> = text.lines().map(|l| l.trim_end().chars().collect()).collect();
+    let skipped = all_lines.iter().take_while(|l| l.is_empty()).count();
+    let lines = &all_lines[skipped..];
+    let error = |k: usize, x: usize, reason| NotationError{ line: skipped + k + 1, column: x + 1, reason };
+
+    // find every cell and bond marker by line and column, before knowing how the columns line up with hexes
+    let mut cells = Vec::new();
+    let mut east_bonds = Vec::new();
+    let mut diagonal_bonds = Vec::new();
+    for (k, line) in lines.iter().enumerate(){
+        if k % 2 == 1{
+            diagonal_bonds.extend(line.iter().enumerate().filter(|(_, c)| **c != ' ').map(|(x, c)| (k, x, *c)));
+            continue;
+        }
+        let mut x = 0;
+        let mut after_bond = false;
+        while x < line.len(){
+            let c = line[x];
+            if c == ' '{
+                if after_bond{
+                    return Err(error(k, x, "bond to an empty hex"));
+                }
+                x += 1;
+            }else if c.is_ascii_alphabetic() || c == '('{
+                let token: String = line[x..].iter().take(2).collect();
+                cells.push((k, x, token));
+                after_bond = false;
+                x += 2;
+                if let Some(&marker) = line.get(x){
+                    if marker != ' '{
+                        let ty = bond_type(marker, '-').ok_or(error(k, x, "unknown bond marker"))?;
+                        if line.get(x + 1) != Some(&marker){
+                            return Err(error(k, x, "bonds within a row should have two markers"));
+                        }
+                        east_bonds.push((k, x - 2, ty));
+                        after_bond = true;
+                        x += 2;
+                    }
+                }
+            }else{
+                return Err(error(k, x, "unexpected character"));
+            }
+        }
+        if after_bond{
+            return Err(error(k, x, "bond to an empty hex"));
+        }
+    }
+
+    // the column of the first cell fixes where every other hex's column is
+    let row = |k: usize| -((k / 2) as i32);
+    let base = cells.first().map_or(0, |(k, x, _)| *x as i32 - 2 * row(*k));
+    let hex_at = |k: usize, x: usize| -> Option<HexIndex>{
+        let offset = x as i32 - base - 2 * row(k);
+        (offset.rem_euclid(4) == 0).then_some(HexIndex::new(offset.div_euclid(4), row(k)))
+    };
+
+    let mut atoms = HexMap::new();
+    let mut origin = None;
+    for (k, x, token) in &cells{
+        let pos = hex_at(*k, *x).ok_or(error(*k, *x, "atom out of line with the rows around it"))?;
+        let is_origin = if token == "()"{
+            true
+        }else{
+            let atom = atom_from_code(token).ok_or(error(*k, *x, "unknown element code"))?;
+            atoms.insert(pos, atom);
+            token.chars().all(|c| c.is_ascii_uppercase())
+        };
+        if is_origin && origin.replace(pos).is_some(){
+            return Err(error(*k, *x, "origin marked more than once"));
+        }
+    }
+
+    let mut bonds = HashSet::new();
+    let mut bond = |start: HexIndex, end: HexIndex, ty, k, x| -> Result<(), NotationError>{
+        if !atoms.contains_key(&start) || !atoms.contains_key(&end){
+            return Err(error(k, x, "bond to an empty hex"));
+        }
+        bonds.insert(Bond{ start, end, ty });
+        Ok(())
+    };
+    for (k, x, ty) in east_bonds{
+        let start = hex_at(k, x).ok_or(error(k, x, "atom out of line with the rows around it"))?;
+        bond(start, start + HexIndex::new(1, 0), ty, k, x + 2)?;
+    }
+    for (k, x, marker) in diagonal_bonds{
+        // markers sit just right of the lower atom's start for up-right bonds, and at its start for up-left bonds
+        let (start, direction, normal) = match (x.checked_sub(1).and_then(|x| hex_at(k + 1, x)), hex_at(k + 1, x)){
+            (Some(lower), _) => (lower, HexIndex::new(0, 1), '/'),
+            (_, Some(lower)) => (lower, HexIndex::new(-1, 1), '\\'),
+            _ => return Err(error(k, x, "bond out of line with the atoms around it"))
+        };
+        let ty = bond_type(marker, normal).ok_or(error(k, x, if "/\\".contains(marker) { "bond marker in the wrong direction" } else { "unknown bond marker" }))?;
+        bond(start, start + direction, ty, k, x)?;
+    }
+
+    let origin = origin.or(atoms.keys().next().copied()).unwrap_or_default();
+    Ok(Molecule{ atoms, bonds }.translated(-origin))
+}
+
+/// Write a molecule in molecule notation, such that `parse_molecule` reads back the same molecule, except that bonds may be reversed.
+/// The output is the same for equal molecules, with no trailing spaces and a newline after each line.
+/// Fails if an atom is of an unknown type, or a bond can't be drawn: if it doesn't connect neighbouring atoms, or is a triplex bond with no colours.
+pub fn format_molecule(molecule: &Molecule) -> Result<String, &'static str>{
+    let Some(first) = molecule.atoms.keys().next() else {
+        return if molecule.bonds.is_empty() { Ok(String::new()) } else { Err("bond to a position without an atom") };
+    };
+    // the origin only needs marking if the parser wouldn't put it at the first atom anyway
+    let origin = HexIndex::default();
+    let mark_origin = *first != origin;
+    let drawn: Vec<HexIndex> = molecule.atoms.keys().copied().chain(mark_origin.then_some(origin)).collect();
+
+    let column = |pos: HexIndex| 4 * pos.q + 2 * pos.r;
+    let top = drawn.iter().map(|p| p.r).max().unwrap_or_default();
+    let bottom = drawn.iter().map(|p| p.r).min().unwrap_or_default();
+    let left = drawn.iter().map(|p| column(*p)).min().unwrap_or_default();
+    let right = drawn.iter().map(|p| column(*p)).max().unwrap_or_default();
+    let mut grid = vec![vec![' '; (right - left + 2) as usize]; (2 * (top - bottom) + 1) as usize];
+    let mut put = |line: i32, x: i32, c: char| grid[line as usize][(x - left) as usize] = c;
+
+    for pos in &drawn{
+        let code = match molecule.atoms.get(pos){
+            Some(atom) if *pos == origin => atom_code(*atom).ok_or("atom of an unknown type")?.to_ascii_uppercase(),
+            Some(atom) => atom_code(*atom).ok_or("atom of an unknown type")?.to_string(),
+            None => "()".to_string()
+        };
+        for (i, c) in code.chars().enumerate(){
+            put(2 * (top - pos.r), column(*pos) + i as i32, c);
+        }
+    }
+    for bond in &molecule.bonds{
+        if !molecule.atoms.contains_key(&bond.start) || !molecule.atoms.contains_key(&bond.end){
+            return Err("bond to a position without an atom");
+        }
+        let marker = |normal| match bond.ty{
+            BondType::Normal => Ok(normal),
+            BondType::Triplex{ red, black, yellow } => match (red as u32) | (black as u32) << 1 | (yellow as u32) << 2{
+                0 => Err("triplex bond with no colours"),
+                colours => Ok(char::from_digit(colours, 10).expect("colours fit in a digit"))
+            }
+        };
+        // draw every bond from its lower or left end
+        let (lower, upper) = if (bond.start.r, bond.start.q) < (bond.end.r, bond.end.q) { (bond.start, bond.end) } else { (bond.end, bond.start) };
+        let (line, x) = (2 * (top - lower.r), column(lower));
+        match upper - lower{
+            HexIndex{ q: 1, r: 0 } => {
+                let marker = marker('-')?;
+                put(line, x + 2, marker);
+                put(line, x + 3, marker);
+            }
+            HexIndex{ q: 0, r: 1 } => put(line - 1, x + 1, marker('/')?),
+            HexIndex{ q: -1, r: 1 } => put(line - 1, x, marker('\\')?),
+            _ => return Err("bond between hexes that aren't neighbours")
+        }
+    }
+
+    let mut text = String::new();
+    for line in grid{
+        text.extend(line);
+        text.truncate(text.trim_end_matches(' ').len());
+        text.push('\n');
+    }
+    Ok(text)
+}
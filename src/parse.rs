@@ -1,34 +1,286 @@
-use std::backtrace::Backtrace;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
 use arrayref::array_ref;
 use super::data::*;
 
-pub fn parse_puzzle(data: &[u8]) -> Result<Puzzle, &'static str>{
-    let mut parser = BaseParser::new(data);
-    if parser.parse_int()? != 3{
-        return Err("not an opus magnum puzzle");
-    }
-    let name = parser.parse_string()?;
-    let creator_id = parser.parse_ulong()?;
-    let permissions = Permissions::from_bits_retain(parser.parse_ulong()?);
-    let reagents = parser.parse_list(|s| s.parse_molecule())?;
-    let products = parser.parse_list(|s| s.parse_molecule())?;
-    let product_multiplier = parser.parse_int()?;
-
-    let production_info = if parser.parse_bool()?{
-        let _shrink_left = parser.parse_bool()?; // visual, don't care
-        let _shrink_right = parser.parse_bool()?;
-        let isolation = parser.parse_bool()?;
-        let chambers = parser.parse_list(|p| Ok(Chamber{
-            pos: p.parse_b_hex_index()?,
-            ty: ChamberType::from_name(&p.parse_string()?).ok_or("invalid chamber type")?
-        }))?;
-        let conduits = parser.parse_list(|p| Ok(Conduit{
-            pos_a: p.parse_b_hex_index()?,
-            pos_b: p.parse_b_hex_index()?,
-            hexes: p.parse_list(|p| p.parse_b_hex_index())?
+/// A reason a puzzle or solution file couldn't be parsed, with the field being read and the byte offset it starts at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError{
+    /// The data ended before a field could be read completely.
+    UnexpectedEnd{ field: &'static str, offset: usize, needed: usize },
+    /// A field didn't have the value the format requires, such as the markers between a solution's metrics.
+    Mismatch{ field: &'static str, offset: usize, expected: i64, found: i64 },
+    /// A field's value doesn't correspond to anything, such as an unknown atom or instruction ID.
+    InvalidValue{ field: &'static str, offset: usize, found: i64 },
+    /// A field named something that doesn't exist, such as an unknown part type.
+    UnknownName{ field: &'static str, offset: usize, found: String },
+    /// A string field wasn't valid UTF-8.
+    InvalidUtf8{ field: &'static str, offset: usize },
+    /// A field was larger than the `ParseLimits` being parsed with allow.
+    TooLarge{ field: &'static str, offset: usize, limit: usize, found: usize },
+    /// The file couldn't be read at all. Its field is "file" and its offset is 0.
+    Unreadable{ kind: std::io::ErrorKind },
+    /// The file starts with a version number whose format isn't known. Its field is "file version" and its offset is 0.
+    /// Puzzle version 3 and solution version 7 are the only layouts this crate can read.
+    UnsupportedVersion(i32),
+    /// There's more data after the end of a complete file. Its field is "end of file".
+    TrailingBytes{ offset: usize, count: usize }
+}
+
+impl ParseError{
+    /// The field that was being read.
+    pub fn field(&self) -> &'static str{
+        match self{
+            ParseError::UnexpectedEnd{ field, .. }
+            | ParseError::Mismatch{ field, .. }
+            | ParseError::InvalidValue{ field, .. }
+            | ParseError::UnknownName{ field, .. }
+            | ParseError::InvalidUtf8{ field, .. }
+            | ParseError::TooLarge{ field, .. } => field,
+            ParseError::Unreadable{ .. } => "file",
+            ParseError::UnsupportedVersion(_) => "file version",
+            ParseError::TrailingBytes{ .. } => "end of file"
+        }
+    }
+
+    /// The byte offset of the start of the field that was being read.
+    pub fn offset(&self) -> usize{
+        match self{
+            ParseError::UnexpectedEnd{ offset, .. }
+            | ParseError::Mismatch{ offset, .. }
+            | ParseError::InvalidValue{ offset, .. }
+            | ParseError::UnknownName{ offset, .. }
+            | ParseError::InvalidUtf8{ offset, .. }
+            | ParseError::TooLarge{ offset, .. }
+            | ParseError::TrailingBytes{ offset, .. } => *offset,
+            ParseError::Unreadable{ .. } | ParseError::UnsupportedVersion(_) => 0
+        }
+    }
+
+    /// A short description of the kind of problem, without any context.
+    pub fn description(&self) -> &'static str{
+        match self{
+            ParseError::UnexpectedEnd{ .. } => "not enough bytes",
+            ParseError::Mismatch{ .. } => "unexpected value",
+            ParseError::InvalidValue{ .. } => "invalid value",
+            ParseError::UnknownName{ .. } => "unknown name",
+            ParseError::InvalidUtf8{ .. } => "invalid utf8",
+            ParseError::TooLarge{ .. } => "value too large",
+            ParseError::Unreadable{ .. } => "could not read file",
+            ParseError::UnsupportedVersion(_) => "unsupported version",
+            ParseError::TrailingBytes{ .. } => "trailing bytes"
+        }
+    }
+}
+
+impl Display for ParseError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        match self{
+            ParseError::UnexpectedEnd{ field, offset, needed } => write!(f, "{field} at byte {offset}: needed {needed} more bytes"),
+            ParseError::Mismatch{ field, offset, expected, found } => write!(f, "{field} at byte {offset}: expected {expected}, found {found}"),
+            ParseError::InvalidValue{ field, offset, found } => write!(f, "{field} at byte {offset}: invalid value {found}"),
+            ParseError::UnknownName{ field, offset, found } => write!(f, "{field} at byte {offset}: unknown name {found:?}"),
+            ParseError::InvalidUtf8{ field, offset } => write!(f, "{field} at byte {offset}: invalid utf8"),
+            ParseError::TooLarge{ field, offset, limit, found } => write!(f, "{field} at byte {offset}: {found} is larger than the limit of {limit}"),
+            ParseError::Unreadable{ kind } => write!(f, "could not read file: {kind}"),
+            ParseError::UnsupportedVersion(version) => write!(f, "unsupported file version {version}"),
+            ParseError::TrailingBytes{ offset, count } => write!(f, "end of file at byte {offset}: {count} more bytes after a complete file")
+        }
+    }
+}
+
+impl std::error::Error for ParseError{}
+
+/// Caps on the sizes of things in parsed files, so that untrusted input can't make parsing or simulation use unbounded memory.
+/// Lists and strings are never trusted beyond the input's length regardless, so these only need to be tighter than that.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParseLimits{
+    /// The most elements any one list may have, such as atoms in a molecule or parts in a solution.
+    pub max_list_length: usize,
+    /// The longest any string may be, in bytes.
+    pub max_string_length: usize,
+    /// The largest slot an instruction may be placed in, since tapes are laid out up to their last instruction.
+    pub max_instruction_index: usize
+}
+
+impl ParseLimits{
+    /// No limits beyond the length of the input, for trusted files that may be larger than anything the game produces.
+    pub const UNLIMITED: ParseLimits = ParseLimits{ max_list_length: usize::MAX, max_string_length: usize::MAX, max_instruction_index: usize::MAX };
+}
+
+impl Default for ParseLimits{
+    /// Limits well above anything the game can produce.
+    fn default() -> Self{
+        ParseOptions::STRICT.limits
+    }
+}
+
+/// What to do about something in a file that's wrong, but doesn't stop the rest of it being read.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Strictness{
+    /// Fail to parse the file.
+    Error,
+    /// Parse the file, but report the problem alongside it.
+    Warn,
+    /// Parse the file as if nothing was wrong.
+    #[default]
+    Accept
+}
+
+/// How to parse a file, for `parse_puzzle_with_options` and `parse_solution_with_options`.
+/// The default accepts everything the game might have written within `ParseLimits::default()`, like `parse_puzzle` and `parse_solution`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions{
+    pub limits: ParseLimits,
+    /// Data left over after a complete file.
+    pub trailing_bytes: Strictness,
+    /// Permission bits in a puzzle that don't enable anything.
+    pub unknown_permissions: Strictness,
+    /// Values the game never writes: negative recorded metrics, product multipliers below 1, and arm lengths outside 1 to 3.
+    pub out_of_range: Strictness,
+    /// Keep atom and part types this crate doesn't know, such as from modded games, as `Atom::Unknown` and `PartType::Unknown` rather than failing.
+    /// Files parsed this way can be inspected and written back, but may not simulate.
+    pub lenient: bool,
+    /// Keep as much of a damaged file as possible, leaving everything after something that can't be read empty or default, and returning the problem as a warning.
+    /// Implies `lenient`, and also skips unknown instructions. Still fails if a puzzle's name, creator, or permissions, or a solution's names or metrics, can't be read.
+    pub partial: bool,
+    /// Keep the parts of the file that `Puzzle` and `Solution` don't, so that it can be written back exactly with `encode::write_puzzle_lossless` or `encode::write_solution_lossless`.
+    pub lossless: bool
+}
+
+impl ParseOptions{
+    /// Treat every problem as an error, for servers that should only accept files the game could have written.
+    pub const STRICT: ParseOptions = ParseOptions{
+        limits: ParseLimits{ max_list_length: 10_000, max_string_length: 1_000, max_instruction_index: 100_000 },
+        trailing_bytes: Strictness::Error,
+        unknown_permissions: Strictness::Error,
+        out_of_range: Strictness::Error,
+        lenient: false,
+        partial: false,
+        lossless: false
+    };
+}
+
+/// A file parsed with `parse_puzzle_with_options` or `parse_solution_with_options`.
+#[derive(Clone, Debug)]
+pub struct Parsed<T>{
+    pub value: T,
+    /// Problems that the options made warnings, or that partial parsing worked around, in the order they were found.
+    pub warnings: Vec<ParseError>,
+    /// When parsing losslessly, everything in the file that `value` doesn't keep.
+    pub extras: Option<RawExtras>
+}
+
+impl From<ParseError> for &'static str{
+    fn from(error: ParseError) -> &'static str{
+        error.description()
+    }
+}
+
+/// A parsed file of either kind.
+#[derive(Clone, Debug)]
+pub enum FileKind{
+    Puzzle(Puzzle),
+    Solution(Solution)
+}
+
+/// Parse a puzzle or solution, deciding which from the version number at the start of the file. Uses `ParseLimits::default()`.
+pub fn parse_any(data: &[u8]) -> Result<FileKind, ParseError>{
+    match BaseParser::new(data, ParseOptions::default()).at("file version").parse_int()?{
+        3 => parse_puzzle(data).map(FileKind::Puzzle),
+        7 => parse_solution(data).map(FileKind::Solution),
+        version => Err(ParseError::UnsupportedVersion(version))
+    }
+}
+
+impl Puzzle{
+    /// Read and parse a puzzle file, with `ParseLimits::default()`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Puzzle, ParseError>{
+        parse_puzzle(&read_file(path.as_ref())?)
+    }
+}
+
+impl Solution{
+    /// Read and parse a solution file, with `ParseLimits::default()`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Solution, ParseError>{
+        parse_solution(&read_file(path.as_ref())?)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ParseError>{
+    std::fs::read(path).map_err(|e| ParseError::Unreadable{ kind: e.kind() })
+}
+
+/// Parse a puzzle, failing if anything in it exceeds `ParseLimits::default()`.
+pub fn parse_puzzle(data: &[u8]) -> Result<Puzzle, ParseError>{
+    parse_puzzle_ref(data).map(PuzzleRef::into_owned)
+}
+
+/// Parse a puzzle as the options say, returning it along with any warnings and, if parsing losslessly, the extras it doesn't keep.
+pub fn parse_puzzle_with_options(data: &[u8], options: ParseOptions) -> Result<Parsed<Puzzle>, ParseError>{
+    let parsed = parse_with_options(data, options, read_puzzle)?;
+    Ok(Parsed{ value: parsed.value.into_owned(), warnings: parsed.warnings, extras: parsed.extras })
+}
+
+/// Parse a puzzle without copying its name out of `data`, with the default options.
+pub fn parse_puzzle_ref(data: &[u8]) -> Result<PuzzleRef<'_>, ParseError>{
+    parse_with_options(data, ParseOptions::default(), read_puzzle).map(|parsed| parsed.value)
+}
+
+fn read_puzzle<'a>(parser: &mut BaseParser<'a>) -> Result<PuzzleRef<'a>, ParseError>{
+    // version 3 is the only layout this parser knows, and guessing at others would misread them, so they fail instead
+    match parser.at("puzzle version").parse_int()?{
+        3 => {}
+        version => return Err(ParseError::UnsupportedVersion(version))
+    }
+    let name = parser.at("puzzle name").parse_str()?;
+    let creator_id = parser.at("creator id").parse_ulong()?;
+    let bits = parser.at("permissions").parse_ulong()?;
+    let permissions = Permissions::from_bits_retain(bits);
+    if permissions.unknown_bits() != 0{
+        parser.allow(parser.options.unknown_permissions, parser.invalid_value(bits as i64))?;
+    }
+    let mut puzzle = PuzzleRef{ name, creator_id, reagents: Vec::new(), products: Vec::new(), permissions, product_multiplier: 1, production_info: None };
+    let body = read_puzzle_body(parser, &mut puzzle);
+    parser.salvage(body)?;
+    Ok(puzzle)
+}
+
+/// Read everything after a puzzle's permissions into it, field by field, so that a partial parse keeps the fields before a problem.
+fn read_puzzle_body(parser: &mut BaseParser<'_>, puzzle: &mut PuzzleRef<'_>) -> Result<(), ParseError>{
+    puzzle.reagents = parser.at("reagents").parse_list(|s| s.parse_molecule())?;
+    puzzle.products = parser.at("products").parse_list(|s| s.parse_molecule())?;
+    puzzle.product_multiplier = parser.at("product multiplier").parse_int()?;
+    if puzzle.product_multiplier < 1{
+        parser.allow(parser.options.out_of_range, parser.invalid_value(puzzle.product_multiplier as i64))?;
+    }
+
+    puzzle.production_info = if parser.at("production flag").parse_bool()?{
+        let shrink_left = parser.at("shrink left").parse_bool()?; // visual, only kept for lossless parsing
+        let shrink_right = parser.at("shrink right").parse_bool()?;
+        let isolation = parser.at("isolation").parse_bool()?;
+        let chambers = parser.at("chambers").parse_list(|p| {
+            let pos = p.at("chamber position").parse_b_hex_index()?;
+            let name = p.at("chamber type").parse_str()?;
+            Ok(Chamber{ pos, ty: ChamberType::from_name(name).ok_or_else(|| p.unknown_name(name))? })
+        })?;
+        let conduits = parser.at("conduits").parse_list(|p| Ok(Conduit{
+            pos_a: p.at("conduit position").parse_b_hex_index()?,
+            pos_b: p.at("conduit position").parse_b_hex_index()?,
+            hexes: p.at("conduit hexes").parse_list(|p| p.parse_b_hex_index())?
         }))?;
-        // vial visuals also ignored
+        // vial visuals also ignored, unless parsing losslessly
+        if let Some(mut extras) = parser.extras.take(){
+            extras.shrink_left = shrink_left;
+            extras.shrink_right = shrink_right;
+            extras.vials = parser.at("vials").parse_list(|p| Ok(Vial{
+                pos: p.at("vial position").parse_b_hex_index()?,
+                top: p.at("vial top").parse_bool()?,
+                count: p.at("vial count").parse_int()?
+            }))?;
+            parser.extras = Some(extras);
+        }
         Some(ProductionInfo{
             isolation,
             chambers,
@@ -36,56 +288,103 @@ pub fn parse_puzzle(data: &[u8]) -> Result<Puzzle, &'static str>{
         })
     } else { None };
 
-    Ok(Puzzle{ name, creator_id, reagents, products, permissions, product_multiplier, production_info })
+    Ok(())
+}
+
+/// Parse a solution, failing if anything in it exceeds `ParseLimits::default()`.
+pub fn parse_solution(data: &[u8]) -> Result<Solution, ParseError>{
+    parse_solution_ref(data).map(SolutionRef::into_owned)
 }
 
-pub fn parse_solution(data: &[u8]) -> Result<Solution, &'static str>{
-    let mut parser = BaseParser::new(data);
-    if parser.parse_int()? != 7 {
-        return Err("not an opus magnum solution");
+/// Parse a solution as the options say, returning it along with any warnings and, if parsing losslessly, the extras it doesn't keep.
+pub fn parse_solution_with_options(data: &[u8], options: ParseOptions) -> Result<Parsed<Solution>, ParseError>{
+    let parsed = parse_with_options(data, options, read_solution)?;
+    Ok(Parsed{ value: parsed.value.into_owned(), warnings: parsed.warnings, extras: parsed.extras })
+}
+
+/// Parse a solution without copying its names out of `data`, with the default options.
+pub fn parse_solution_ref(data: &[u8]) -> Result<SolutionRef<'_>, ParseError>{
+    parse_with_options(data, ParseOptions::default(), read_solution).map(|parsed| parsed.value)
+}
+
+/// Parse a file with `read`, which every entry point goes through so that options and limits apply the same way to each.
+fn parse_with_options<'a, T>(data: &'a [u8], options: ParseOptions, read: fn(&mut BaseParser<'a>) -> Result<T, ParseError>) -> Result<Parsed<T>, ParseError>{
+    let mut parser = BaseParser::new(data, options);
+    let value = read(&mut parser)?;
+    let end = parser.finish();
+    parser.salvage(end)?;
+    let extras = options.lossless.then(|| parser.finish_extras());
+    Ok(Parsed{ value, warnings: parser.warnings, extras })
+}
+
+fn read_solution<'a>(parser: &mut BaseParser<'a>) -> Result<SolutionRef<'a>, ParseError>{
+    match parser.at("solution version").parse_int()?{
+        7 => {}
+        version => return Err(ParseError::UnsupportedVersion(version))
     }
-    let puzzle_name = parser.parse_string()?;
-    let name = parser.parse_string()?;
-    let metrics = match parser.parse_int()? {
+    let puzzle_name = parser.at("puzzle name").parse_str()?;
+    let name = parser.at("solution name").parse_str()?;
+    let metrics = match parser.at("metric count").parse_int()? {
         0 => None,
         4 => {
-            if parser.parse_int()? != 0 { return Err("invalid solution (0 != 0)") }
-            let cycles = parser.parse_int()?;
-            if parser.parse_int()? != 1 { return Err("invalid solution (1 != 1)") }
-            let cost = parser.parse_int()?;
-            if parser.parse_int()? != 2 { return Err("invalid solution (2 != 2)") }
-            let area = parser.parse_int()?;
-            if parser.parse_int()? != 3 { return Err("invalid solution (3 != 3)") }
-            let instructions = parser.parse_int()?;
+            parser.at("cycles metric id").expect_int(0)?;
+            let cycles = parser.at("cycles").parse_non_negative()?;
+            parser.at("cost metric id").expect_int(1)?;
+            let cost = parser.at("cost").parse_non_negative()?;
+            parser.at("area metric id").expect_int(2)?;
+            let area = parser.at("area").parse_non_negative()?;
+            parser.at("instructions metric id").expect_int(3)?;
+            let instructions = parser.at("instructions").parse_non_negative()?;
             Some(Metrics{ cycles, cost, area, instructions })
         },
-        _ => return Err("invalid number of metrics")
+        count => return Err(parser.invalid_value(count as i64))
     };
-    let parts: Vec<Part> = parser.parse_list(|p| {
-        let part_name = p.parse_string()?;
-        if p.parse_byte()? != 1 { return Err("invalid solution part (1 != 1)") }
-        let pos = p.parse_i_hex_index()?;
-        let arm_length = p.parse_int()?;
-        let rotation = p.parse_int()?;
-        let index = p.parse_int()?;
-        let instructions = p.parse_list(|p| {
-            let idx = p.parse_int()?;
-            let instr = p.parse_byte()?;
-            Ok((Instruction::from_id(instr).ok_or("invalid instruction id")?, idx))
-        })?;
+    let parts: Vec<Part> = parser.at("parts").parse_list_salvaging(|p| {
+        let part_name = p.at("part type").parse_str()?;
+        let ty = match PartType::from_name(part_name){
+            Some(ty) => ty,
+            None if p.lenient => {
+                p.note(p.unknown_name(part_name));
+                PartType::Unknown(part_name.to_owned())
+            }
+            None => return Err(p.unknown_name(part_name))
+        };
+        if p.at("part format").parse_byte()? != 1 { return Err(p.invalid_value(1)) }
+        let pos = p.at("part position").parse_i_hex_index()?;
+        let arm_length = p.at("arm length").parse_int()?;
+        if ty.category() == PartCategory::Arm && ty != PartType::Berlo && !(1..=3).contains(&arm_length){
+            p.allow(p.options.out_of_range, p.invalid_value(arm_length as i64))?;
+        }
+        let rotation = p.at("part rotation").parse_int()?;
+        let index = p.at("part index").parse_int()?;
+        let instructions = p.at("instructions").parse_list(|p| {
+            let idx = p.at("instruction index").parse_int()?;
+            p.check_limit(idx.unsigned_abs() as usize, p.options.limits.max_instruction_index)?;
+            let instr = p.at("instruction").parse_byte()?;
+            match Instruction::from_id(instr){
+                Some(instruction) => Ok(Some((instruction, idx))),
+                // every instruction is the same size, so an unknown one can be skipped
+                None if p.partial => {
+                    p.note(p.invalid_value(instr as i64));
+                    Ok(None)
+                }
+                None => Err(p.invalid_value(instr as i64))
+            }
+        })?.into_iter().flatten().collect();
 
         let track_hexes = if part_name == "track"{
-            p.parse_list(|p| { p.parse_i_hex_index() })?
+            p.at("track hexes").parse_list(|p| { p.parse_i_hex_index() })?
         }else{ Vec::new() };
 
-        let arm_number = p.parse_int()? + 1;
+        let arm_number = p.at("arm number").parse_int()?;
+        let arm_number = arm_number.checked_add(1).ok_or_else(|| p.invalid_value(arm_number as i64))?;
 
         let (conduit_index, conduit_hexes) = if part_name == "pipe"{
-            (p.parse_int()?, p.parse_list(|p| { p.parse_i_hex_index() })?)
+            (p.at("conduit index").parse_int()?, p.at("conduit hexes").parse_list(|p| { p.parse_i_hex_index() })?)
         }else{ (0, Vec::new()) };
 
         Ok(Part{
-            ty: PartType::from_name(&part_name).ok_or("invalid part type")?,
+            ty,
             pos,
             rotation,
             arm_number,
@@ -97,146 +396,357 @@ pub fn parse_solution(data: &[u8]) -> Result<Solution, &'static str>{
             instructions
         })
     })?;
-    Ok(Solution{ name, puzzle_name, metrics, parts })
+    Ok(SolutionRef{ name, puzzle_name, metrics, parts })
 }
 
 // byte parsing
 
 struct BaseParser<'a>{
-    data: &'a [u8]
+    data: &'a [u8],
+    /// The length of the whole input, for calculating offsets.
+    total: usize,
+    /// The field being read, and the offset it started at.
+    field: &'static str,
+    field_offset: usize,
+    /// How strict to be, including the limits to enforce.
+    options: ParseOptions,
+    /// Problems that the options made warnings, in the order they were found.
+    warnings: Vec<ParseError>,
+    /// If parsing losslessly, everything read so far that the parsed structures don't keep.
+    extras: Option<RawExtras>,
+    /// Whether unknown atom and part types are kept as `Unknown` rather than rejected.
+    lenient: bool,
+    /// Whether to keep everything read before a problem, noting the problem in `warnings` rather than failing.
+    partial: bool
 }
 
 impl<'a> BaseParser<'a>{
 
-    fn new(data: &'a [u8]) -> Self{
-        Self{ data }
+    fn new(data: &'a [u8], options: ParseOptions) -> Self{
+        Self{
+            data,
+            total: data.len(),
+            field: "",
+            field_offset: 0,
+            options,
+            warnings: Vec::new(),
+            extras: options.lossless.then(RawExtras::default),
+            lenient: options.lenient || options.partial,
+            partial: options.partial
+        }
     }
 
-    fn parse_byte(&mut self) -> Result<u8, &'static str>{
-        if self.data.len() == 0{
-            Err("not enough bytes")
-        }else{
-            let result = self.data[0];
-            self.data = &self.data[1..];
-            Ok(result)
+    /// Check for data after the end of a complete file.
+    fn finish(&mut self) -> Result<(), ParseError>{
+        if !self.data.is_empty(){
+            let error = ParseError::TrailingBytes{ offset: self.total - self.data.len(), count: self.data.len() };
+            self.allow(self.options.trailing_bytes, error)?;
         }
+        Ok(())
     }
 
-    fn parse_sbyte(&mut self) -> Result<i8, &'static str>{
-        if self.data.len() == 0{
-            Err("not enough bytes")
-        }else{
-            let result = i8::from_be_bytes([self.data[0]]);
-            self.data = &self.data[1..];
-            Ok(result)
+    /// When parsing partially, turn a failure into a warning, keeping what was read before it.
+    fn salvage(&mut self, result: Result<(), ParseError>) -> Result<(), ParseError>{
+        match result{
+            Err(error) if self.partial => {
+                self.warnings.push(error);
+                Ok(())
+            }
+            result => result
+        }
+    }
+
+    /// Note a problem that was worked around when parsing partially.
+    fn note(&mut self, error: ParseError){
+        if self.partial{
+            self.warnings.push(error);
+        }
+    }
+
+    /// Deal with a problem as the options say: fail, keep it as a warning, or ignore it.
+    fn allow(&mut self, strictness: Strictness, error: ParseError) -> Result<(), ParseError>{
+        match strictness{
+            Strictness::Error => return Err(error),
+            Strictness::Warn => self.warnings.push(error),
+            Strictness::Accept => {}
+        }
+        Ok(())
+    }
+
+    /// Take the extras collected while parsing, along with whatever input is left over.
+    fn finish_extras(&mut self) -> RawExtras{
+        let mut extras = self.extras.take().unwrap_or_default();
+        extras.trailing = self.data.to_vec();
+        extras
+    }
+
+    /// Mark the start of a new field, for error reporting.
+    fn at(&mut self, field: &'static str) -> &mut Self{
+        self.field = field;
+        self.field_offset = self.total - self.data.len();
+        self
+    }
+
+    fn unexpected_end(&self, needed: usize) -> ParseError{
+        ParseError::UnexpectedEnd{ field: self.field, offset: self.field_offset, needed }
+    }
+
+    fn invalid_value(&self, found: i64) -> ParseError{
+        ParseError::InvalidValue{ field: self.field, offset: self.field_offset, found }
+    }
+
+    fn unknown_name(&self, found: &str) -> ParseError{
+        ParseError::UnknownName{ field: self.field, offset: self.field_offset, found: found.to_owned() }
+    }
+
+    fn check_limit(&self, found: usize, limit: usize) -> Result<(), ParseError>{
+        if found > limit{
+            return Err(ParseError::TooLarge{ field: self.field, offset: self.field_offset, limit, found });
         }
+        Ok(())
+    }
+
+    /// Take the next `count` bytes.
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ParseError>{
+        if self.data.len() < count{
+            return Err(self.unexpected_end(count - self.data.len()));
+        }
+        let (result, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(result)
     }
 
-    fn parse_bool(&mut self) -> Result<bool, &'static str>{
+    fn parse_byte(&mut self) -> Result<u8, ParseError>{
+        Ok(self.take(1)?[0])
+    }
+
+    fn parse_sbyte(&mut self) -> Result<i8, ParseError>{
+        Ok(i8::from_be_bytes([self.parse_byte()?]))
+    }
+
+    fn parse_bool(&mut self) -> Result<bool, ParseError>{
         Ok(self.parse_byte()? != 0)
     }
 
-    fn parse_int(&mut self) -> Result<i32, &'static str>{
-        if self.data.len() >= 4{
-            let result = i32::from_le_bytes(array_ref![self.data, 0, 4].clone());
-            self.data = &self.data[4..];
-            Ok(result)
-        }else{
-            println!("a {}", Backtrace::capture());
-            Err("not enough bytes to read int")
+    fn parse_int(&mut self) -> Result<i32, ParseError>{
+        Ok(i32::from_le_bytes(*array_ref![self.take(4)?, 0, 4]))
+    }
+
+    /// Parse an int that the game never writes as negative, such as a recorded metric.
+    fn parse_non_negative(&mut self) -> Result<i32, ParseError>{
+        let value = self.parse_int()?;
+        if value < 0{
+            self.allow(self.options.out_of_range, self.invalid_value(value as i64))?;
         }
+        Ok(value)
     }
 
-    fn parse_long(&mut self) -> Result<i64, &'static str>{
-        if self.data.len() >= 8{
-            let result = i64::from_le_bytes(array_ref![self.data, 0, 8].clone());
-            self.data = &self.data[8..];
-            Ok(result)
-        }else{
-            Err("not enough bytes to read long")
+    /// Parse an int that must have a specific value.
+    fn expect_int(&mut self, expected: i32) -> Result<(), ParseError>{
+        let found = self.parse_int()?;
+        if found != expected{
+            return Err(ParseError::Mismatch{ field: self.field, offset: self.field_offset, expected: expected as i64, found: found as i64 });
         }
+        Ok(())
     }
 
-    fn parse_ulong(&mut self) -> Result<u64, &'static str>{
-        if self.data.len() >= 8{
-            let result = u64::from_le_bytes(array_ref![self.data, 0, 8].clone());
-            self.data = &self.data[8..];
-            Ok(result)
-        }else{
-            Err("not enough bytes to read ulong")
+    #[allow(dead_code)]
+    fn parse_long(&mut self) -> Result<i64, ParseError>{
+        Ok(i64::from_le_bytes(*array_ref![self.take(8)?, 0, 8]))
+    }
+
+    fn parse_ulong(&mut self) -> Result<u64, ParseError>{
+        Ok(u64::from_le_bytes(*array_ref![self.take(8)?, 0, 8]))
+    }
+
+    fn parse_list<T>(&mut self, f: fn(&mut Self) -> Result<T, ParseError>) -> Result<Vec<T>, ParseError>{
+        self.parse_list_inner(f, false)
+    }
+
+    /// Like `parse_list`, but when parsing partially, an element that can't be read ends the list, keeping the ones before it.
+    /// Only the last field of a file can be read this way, since nothing after the failed element can be found.
+    fn parse_list_salvaging<T>(&mut self, f: fn(&mut Self) -> Result<T, ParseError>) -> Result<Vec<T>, ParseError>{
+        if !self.partial{
+            return self.parse_list(f);
+        }
+        match self.parse_list_inner(f, true){
+            Err(error) => {
+                self.warnings.push(error);
+                Ok(Vec::new())
+            }
+            result => result
         }
     }
 
-    fn parse_list<T>(&mut self, f: fn(&mut Self) -> Result<T, &'static str>) -> Result<Vec<T>, &'static str>{
+    fn parse_list_inner<T>(&mut self, f: fn(&mut Self) -> Result<T, ParseError>, salvage: bool) -> Result<Vec<T>, ParseError>{
         let amount = self.parse_int()?;
-        let mut result = Vec::with_capacity(amount as usize);
+        if amount < 0{
+            return Err(self.invalid_value(amount as i64));
+        }
+        self.check_limit(amount as usize, self.options.limits.max_list_length)?;
+        // don't trust the length for preallocation, every element takes at least one byte
+        let mut result = Vec::with_capacity((amount as usize).min(self.data.len()));
         for _ in 0..amount{
-            result.push(f(self)?)
+            match f(self){
+                Ok(element) => result.push(element),
+                Err(error) if salvage => {
+                    self.warnings.push(error);
+                    break;
+                }
+                Err(error) => return Err(error)
+            }
         }
         Ok(result)
     }
 
-    fn parse_var_int(&mut self) -> Result<usize, &'static str>{
+    /// Parse a 7-bit encoded int, as used by .NET for string lengths. These are at most 5 bytes long.
+    fn parse_var_int(&mut self) -> Result<usize, ParseError>{
         let mut value: usize = 0;
-        let mut shift: i32 = 0;
-        while self.data.len() > 0{
+        for shift in (0..35).step_by(7){
             let next = self.parse_byte()?;
             value |= ((next & 0x7F) as usize) << shift;
-            shift += 7;
-            if (next & 0x80) != 1{
-                break
+            if (next & 0x80) == 0{
+                return Ok(value);
             }
         }
-
-        Ok(value)
+        Err(self.invalid_value(value as i64))
     }
 
-    fn parse_string(&mut self) -> Result<String, &'static str>{
+    fn parse_str(&mut self) -> Result<&'a str, ParseError>{
         let length = self.parse_var_int()?;
-        let result = String::from_utf8(Vec::from(&self.data[..length])).map_err(|_| "invalid utf8")?;
-        self.data = &self.data[length..];
-        Ok(result)
+        self.check_limit(length, self.options.limits.max_string_length)?;
+        let bytes = self.take(length)?;
+        std::str::from_utf8(bytes).map_err(|_| ParseError::InvalidUtf8{ field: self.field, offset: self.field_offset })
     }
 
     /// Parse a hex index represented with signed byte offsets, used in puzzles.
-    fn parse_b_hex_index(&mut self) -> Result<HexIndex, &'static str>{
+    fn parse_b_hex_index(&mut self) -> Result<HexIndex, ParseError>{
         Ok(HexIndex{ q: self.parse_sbyte()? as i32, r: self.parse_sbyte()? as i32 })
     }
 
     /// Parse a hex index represented with signed 32-bit integer offsets, used in solutions.
-    fn parse_i_hex_index(&mut self) -> Result<HexIndex, &'static str>{
+    fn parse_i_hex_index(&mut self) -> Result<HexIndex, ParseError>{
         Ok(HexIndex{ q: self.parse_int()?, r: self.parse_int()? })
     }
 
-    fn parse_atom(&mut self) -> Result<Atom, &'static str>{
-        Ok(Atom::from_id(self.parse_byte()?).ok_or("invalid atom type")?)
+    fn parse_atom(&mut self) -> Result<Atom, ParseError>{
+        let id = self.at("atom type").parse_byte()?;
+        match Atom::from_id(id){
+            Some(atom) => Ok(atom),
+            None if self.lenient => {
+                self.note(self.invalid_value(id as i64));
+                Ok(Atom::Unknown(id))
+            }
+            None => Err(self.invalid_value(id as i64))
+        }
     }
 
-    fn parse_bond_type(&mut self) -> Result<BondType, &'static str>{
-        let ty = self.parse_byte()?;
+    fn parse_bond_type(&mut self) -> Result<BondType, ParseError>{
+        let ty = self.at("bond type").parse_byte()?;
         if ty == 1 {
             Ok(BondType::Normal)
-        }else if (ty & 0b1111_000_1) != 0{
-            Err("invalid bond type")
+        }else if (ty & 0b1111_0001) != 0{
+            Err(self.invalid_value(ty as i64))
         }else{
             Ok(BondType::Triplex{ red: (ty & 0b10) != 0, black: (ty & 0b100) != 0, yellow: (ty & 0b1000) != 0 })
         }
     }
 
-    fn parse_bond(&mut self) -> Result<Bond, &'static str>{
-        Ok(Bond{ ty: self.parse_bond_type()?, start: self.parse_b_hex_index()?, end: self.parse_b_hex_index()? })
+    fn parse_bond(&mut self) -> Result<Bond, ParseError>{
+        Ok(Bond{ ty: self.parse_bond_type()?, start: self.at("bond start").parse_b_hex_index()?, end: self.at("bond end").parse_b_hex_index()? })
     }
 
-    fn parse_molecule(&mut self) -> Result<Molecule, &'static str>{
+    fn parse_molecule(&mut self) -> Result<Molecule, ParseError>{
+        let atoms = self.at("atoms").parse_list(
+            |s| {
+                let atom = s.parse_atom()?;
+                let index = s.at("atom position").parse_b_hex_index()?;
+                Ok((index, atom))
+            }
+        )?;
+        let bonds = self.at("bonds").parse_list(|s| s.parse_bond())?;
+        if let Some(extras) = &mut self.extras{
+            extras.molecule_orders.push((atoms.iter().map(|(pos, _)| *pos).collect(), bonds.clone()));
+        }
         Ok(Molecule{
-            atoms: HashMap::from_iter(self.parse_list(
-                |s| {
-                    let atom = s.parse_atom()?;
-                    let index = s.parse_b_hex_index()?;
-                    Ok((index, atom))
-                }
-            )?),
-            bonds: HashSet::from_iter(self.parse_list(|s| s.parse_bond())?.iter().cloned())
+            atoms: HexMap::from_iter(atoms),
+            bonds: HashSet::from_iter(bonds)
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::encode::{write_solution, write_solution_lossless};
+
+    fn solution_bytes() -> Vec<u8>{
+        let solution = crate::builder::SolutionBuilder::new("solution", "puzzle")
+            .arm(HexIndex::default(), 0, 1)
+            .instructions(0, "GRg")
+            .build().unwrap();
+        write_solution(&solution)
+    }
+
+    #[test]
+    fn var_ints_continue_past_their_first_byte(){
+        // a 200 byte name has a two byte length, 0xC8 0x01
+        let name = "a".repeat(200);
+        let mut data = vec![7, 0, 0, 0, 1, b'p', 0xC8, 0x01];
+        data.extend(name.bytes());
+        data.extend([0; 8]);
+        assert_eq!(parse_solution(&data).unwrap().name, name);
+    }
+
+    #[test]
+    fn unknown_versions_are_unsupported(){
+        let mut solution = solution_bytes();
+        solution[0] = 6;
+        assert_eq!(parse_solution(&solution).unwrap_err(), ParseError::UnsupportedVersion(6));
+        assert_eq!(parse_solution_ref(&solution).unwrap_err(), ParseError::UnsupportedVersion(6));
+        let mut puzzle = crate::encode::write_puzzle(&crate::builder::PuzzleBuilder::new("puzzle").build()).unwrap();
+        puzzle[0] = 2;
+        assert_eq!(parse_puzzle(&puzzle).unwrap_err(), ParseError::UnsupportedVersion(2));
+        assert_eq!(parse_any(&puzzle).unwrap_err(), ParseError::UnsupportedVersion(2));
+    }
+
+    #[test]
+    fn options_apply_to_every_mode(){
+        let mut data = solution_bytes();
+        data.extend([1, 2, 3]);
+        let limits = ParseLimits{ max_instruction_index: 1, ..ParseLimits::default() };
+        for (lenient, lossless) in [(false, false), (true, false), (false, true)]{
+            let options = ParseOptions{ limits, lenient, lossless, ..ParseOptions::default() };
+            assert!(matches!(parse_solution_with_options(&data, options), Err(ParseError::TooLarge{ .. })));
+        }
+        let parsed = parse_solution_with_options(&data, ParseOptions{ lossless: true, ..ParseOptions::default() }).unwrap();
+        assert_eq!(write_solution_lossless(&parsed.value, parsed.extras.as_ref().unwrap()), data);
+        let strict = ParseOptions{ trailing_bytes: Strictness::Warn, ..ParseOptions::default() };
+        assert_eq!(parse_solution_with_options(&data, strict).unwrap().warnings.len(), 1);
+    }
+
+    #[test]
+    fn unknown_permissions_follow_their_strictness(){
+        let permissions = Permissions::SIMPLE_ARM | Permissions::from_bits_retain(1 << 60);
+        let data = crate::encode::write_puzzle(&crate::builder::PuzzleBuilder::new("puzzle").permissions(permissions).build()).unwrap();
+        let parse = |unknown_permissions| parse_puzzle_with_options(&data, ParseOptions{ unknown_permissions, ..ParseOptions::default() });
+        assert!(matches!(parse(Strictness::Error), Err(ParseError::InvalidValue{ field: "permissions", .. })));
+        let warned = parse(Strictness::Warn).unwrap();
+        assert_eq!(warned.warnings.len(), 1);
+        assert_eq!(warned.value.permissions, permissions);
+        assert!(parse(Strictness::Accept).unwrap().warnings.is_empty());
+        assert!(parse_puzzle_with_options(&data, ParseOptions::STRICT).is_err());
+
+        let known = crate::encode::write_puzzle(&crate::builder::PuzzleBuilder::new("puzzle").permissions(Permissions::SIMPLE_ARM).build()).unwrap();
+        assert!(parse_puzzle_with_options(&known, ParseOptions::STRICT).is_ok());
+    }
+
+    #[test]
+    fn partial_parsing_keeps_what_it_can(){
+        let data = solution_bytes();
+        let parsed = parse_solution_with_options(&data[..data.len() - 3], ParseOptions{ partial: true, ..ParseOptions::default() }).unwrap();
+        assert_eq!(parsed.value.name, "solution");
+        assert_eq!(parsed.warnings.len(), 1);
+        assert!(parse_solution(&data[..data.len() - 3]).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt::Write;
+
+use crate::data::{Atom, BondType, HexIndex, Molecule, Part, PartCategory, PartType, Puzzle, Solution, POLYMER_REPETITIONS};
+use crate::sim::collision::{ColliderType, Vector2, HEX_WIDTH};
+use crate::sim::SimArm;
+
+// SVG drawings of molecules and solutions, for puzzle browsers and other tools that want to show puzzles without drawing them themselves.
+
+/// Space left around the edges of every drawing.
+const MARGIN: f32 = 10.0;
+/// Width of normal bonds.
+pub(crate) const BOND_WIDTH: f32 = 8.0;
+/// Width of each coloured line of a triplex bond, and the distance between them.
+pub(crate) const TRIPLEX_WIDTH: f32 = 4.0;
+pub(crate) const TRIPLEX_SPACING: f32 = 7.0;
+/// Width of track paths.
+pub(crate) const TRACK_WIDTH: f32 = 14.0;
+
+/// Colours of normal bonds and outlines, arms, and track.
+pub(crate) const BOND_COLOUR: &str = "#222222";
+pub(crate) const ARM_COLOUR: &str = "#4a4a52";
+pub(crate) const TRACK_COLOUR: &str = "#8a7a5a";
+/// Colours of the red, black, and yellow parts of triplex bonds.
+pub(crate) const TRIPLEX_COLOURS: [&str; 3] = ["#d03030", "#222222", "#e8c020"];
+
+/// Draw a molecule as a standalone SVG image, with each atom on its hex and bonds drawn between them.
+/// Atoms are drawn at the same size and spacing the simulator uses for collisions.
+pub fn render_molecule(molecule: &Molecule) -> String{
+    let mut svg = Svg::default();
+    svg.molecule(molecule, |h| h);
+    svg.finish(None)
+}
+
+/// Settings for `render_solution`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions{
+    /// Whether to draw the molecules of inputs and outputs over their hexes.
+    pub molecules: bool,
+    /// How opaque input and output molecules are, from 0 to 1, so they can be told apart from parts.
+    pub molecule_opacity: f32,
+    /// Whether to draw the grippers of arms, rather than only their bases.
+    pub grippers: bool,
+    /// A colour to fill the background with, or `None` to leave it transparent.
+    pub background: Option<String>
+}
+
+impl Default for RenderOptions{
+    fn default() -> Self{
+        RenderOptions{ molecules: true, molecule_opacity: 0.5, grippers: true, background: None }
+    }
+}
+
+/// Draw a solution's parts, as placed at the start of a run, as a standalone SVG image.
+/// Glyphs, inputs, outputs, and conduits are drawn as the hexes they cover, track as a path through its hexes, and arms as lines from their base to each gripper.
+/// Inputs and outputs that refer to molecules the puzzle doesn't have are drawn without them.
+pub fn render_solution(solution: &Solution, puzzle: &Puzzle, options: &RenderOptions) -> String{
+    let mut svg = Svg::default();
+    let draw_order = |p: &&Part| match p.ty.category(){
+        PartCategory::Track => 0,
+        PartCategory::Io | PartCategory::Glyph => 1,
+        PartCategory::Arm => 2
+    };
+    let mut parts: Vec<&Part> = solution.parts.iter().collect();
+    parts.sort_by_key(draw_order);
+    for part in parts{
+        match part.ty.category(){
+            PartCategory::Track => svg.track(&part.footprint(), part.ty.name()),
+            PartCategory::Arm => svg.arm(part, options.grippers),
+            PartCategory::Io | PartCategory::Glyph => {
+                let fill = part_fill(&part.ty);
+                for hex in part.footprint_in(puzzle){
+                    svg.hexagon(Svg::point(hex), fill, part.ty.name());
+                }
+                let molecule = match part.ty{
+                    PartType::Input => puzzle.reagents.get(part.index as usize),
+                    PartType::Output | PartType::PolymerOutput => puzzle.products.get(part.index as usize),
+                    _ => None
+                };
+                if let (true, Some(molecule)) = (options.molecules, molecule){
+                    let _ = writeln!(svg.body, r#"<g opacity="{}">"#, options.molecule_opacity.clamp(0.0, 1.0));
+                    svg.molecule(&molecule.extended(POLYMER_REPETITIONS), |h| h.rotate60_about(HexIndex::default(), part.rotation) + part.pos);
+                    svg.body.push_str("</g>\n");
+                }
+            }
+        }
+    }
+    svg.finish(options.background.as_deref())
+}
+
+/// The colour of the hexes covered by a part.
+pub(crate) fn part_fill(ty: &PartType) -> &'static str{
+    match ty{
+        PartType::Input => "#cfe3c4",
+        PartType::Output | PartType::PolymerOutput => "#e3c8da",
+        PartType::Conduit => "#a8bccc",
+        PartType::Equilibrium => "#e4dccb",
+        PartType::Disposal => "#b8a8a0",
+        _ => "#d2bc8c"
+    }
+}
+
+/// The fill colour and label drawn for each element.
+pub(crate) fn atom_style(atom: Atom) -> (&'static str, &'static str){
+    match atom{
+        Atom::Salt => ("#eee8d8", "Sa"),
+        Atom::Air => ("#a8d8f0", "Ai"),
+        Atom::Earth => ("#7bb05a", "Ea"),
+        Atom::Fire => ("#e0583c", "Fi"),
+        Atom::Water => ("#4a84d8", "Wa"),
+        Atom::Quicksilver => ("#c8ccd4", "Qu"),
+        Atom::Vitae => ("#f0e070", "Vi"),
+        Atom::Mors => ("#8a6a8a", "Mo"),
+        Atom::Lead => ("#6a6e76", "Pb"),
+        Atom::Tin => ("#a8aca8", "Sn"),
+        Atom::Iron => ("#9a6a5a", "Fe"),
+        Atom::Copper => ("#d0843e", "Cu"),
+        Atom::Silver => ("#dde0e8", "Ag"),
+        Atom::Gold => ("#e8c040", "Au"),
+        Atom::Quintessence => ("#dab4ec", "Qn"),
+        Atom::Repeat => ("#ffffff", "..."),
+        Atom::Unknown(_) => ("#808080", "?")
+    }
+}
+
+/// An SVG document being drawn, which grows to fit everything drawn on it.
+#[derive(Clone, Debug)]
+struct Svg{
+    body: String,
+    min: Vector2,
+    max: Vector2
+}
+
+impl Default for Svg{
+    fn default() -> Self{
+        Svg{ body: String::new(), min: Vector2::new(f32::MAX, f32::MAX), max: Vector2::new(f32::MIN, f32::MIN) }
+    }
+}
+
+impl Svg{
+    /// The centre of a hex in SVG coordinates, where Y points down.
+    fn point(hex: HexIndex) -> Vector2{
+        let v = Vector2::from_hex_index(hex);
+        Vector2::new(v.x, -v.y)
+    }
+
+    /// Grow the drawing to include a circle.
+    fn include(&mut self, centre: Vector2, radius: f32){
+        self.min = Vector2::new(self.min.x.min(centre.x - radius), self.min.y.min(centre.y - radius));
+        self.max = Vector2::new(self.max.x.max(centre.x + radius), self.max.y.max(centre.y + radius));
+    }
+
+    /// Draw a molecule, with its atom positions mapped onto the board by `place`.
+    fn molecule(&mut self, molecule: &Molecule, place: impl Fn(HexIndex) -> HexIndex){
+        // draw in a consistent order, since hash order changes between runs
+        let mut bonds: Vec<_> = molecule.bonds.iter().collect();
+        bonds.sort_by_key(|b| (b.start.r, b.start.q, b.end.r, b.end.q));
+        for bond in bonds{
+            self.bond(Self::point(place(bond.start)), Self::point(place(bond.end)), bond.ty);
+        }
+        let mut atoms: Vec<_> = molecule.atoms.iter().collect();
+        atoms.sort_by_key(|(pos, _)| (pos.r, pos.q));
+        for (pos, atom) in atoms{
+            self.atom(Self::point(place(*pos)), *atom);
+        }
+    }
+
+    /// Draw a hexagon filling a hex, labelled with a tooltip.
+    fn hexagon(&mut self, centre: Vector2, fill: &str, title: &str){
+        // pointy-topped, with flat sides meeting the neighbouring hexes
+        let radius = HEX_WIDTH / 3f32.sqrt();
+        self.include(centre, radius);
+        let corners: Vec<String> = (0..6).map(|i| {
+            let angle = std::f32::consts::FRAC_PI_6 + std::f32::consts::FRAC_PI_3 * i as f32;
+            format!("{:.1},{:.1}", centre.x + radius * angle.cos(), centre.y + radius * angle.sin())
+        }).collect();
+        let _ = writeln!(self.body, r##"<polygon points="{}" fill="{fill}" stroke="#6a5a40" stroke-width="2"><title>{title}</title></polygon>"##, corners.join(" "));
+    }
+
+    /// Draw track as a path through the centres of its hexes, in order.
+    fn track(&mut self, hexes: &[HexIndex], title: &str){
+        let points: Vec<Vector2> = hexes.iter().map(|h| Self::point(*h)).collect();
+        for point in &points{
+            self.include(*point, TRACK_WIDTH);
+        }
+        let path: Vec<String> = points.iter().map(|p| format!("{:.1},{:.1}", p.x, p.y)).collect();
+        let _ = writeln!(self.body, r##"<polyline points="{}" fill="none" stroke="{TRACK_COLOUR}" stroke-width="{TRACK_WIDTH}" stroke-linecap="round" stroke-linejoin="round"><title>{title}</title></polyline>"##, path.join(" "));
+    }
+
+    /// Draw an arm's base, and optionally a line to each of its grippers.
+    fn arm(&mut self, part: &Part, grippers: bool){
+        let base = Self::point(part.pos);
+        let radius = ColliderType::ArmBase.radius();
+        if grippers && part.ty != PartType::Berlo{
+            for turns in SimArm::gripper_rotations(&part.ty){
+                let gripper = Self::point(HexIndex{ q: part.arm_length, r: 0 }.rotate60_about(HexIndex::default(), part.rotation + *turns as i32) + part.pos);
+                self.line(base, gripper, ARM_COLOUR, BOND_WIDTH);
+                self.include(gripper, radius);
+                let _ = writeln!(self.body, r##"<circle cx="{:.1}" cy="{:.1}" r="{}" fill="none" stroke="{ARM_COLOUR}" stroke-width="4"/>"##, gripper.x, gripper.y, radius / 2.0);
+            }
+        }
+        self.include(base, radius);
+        let _ = writeln!(self.body, r##"<circle cx="{:.1}" cy="{:.1}" r="{radius}" fill="#7a7a84" stroke="#2a2a30" stroke-width="2"><title>{}</title></circle>"##, base.x, base.y, part.ty.name());
+    }
+
+    fn atom(&mut self, centre: Vector2, atom: Atom){
+        let radius = ColliderType::Atom.radius();
+        let (fill, label) = atom_style(atom);
+        self.include(centre, radius);
+        let _ = writeln!(self.body, r##"<circle cx="{:.1}" cy="{:.1}" r="{radius}" fill="{fill}" stroke="{BOND_COLOUR}" stroke-width="2"/>"##, centre.x, centre.y);
+        let _ = writeln!(self.body, r##"<text x="{:.1}" y="{:.1}" font-family="sans-serif" font-size="20" text-anchor="middle" dominant-baseline="central" fill="{BOND_COLOUR}">{label}</text>"##, centre.x, centre.y);
+    }
+
+    /// Draw a bond as a line, or as one line for each colour of a triplex bond.
+    fn bond(&mut self, start: Vector2, end: Vector2, ty: BondType){
+        match ty{
+            BondType::Normal => self.line(start, end, BOND_COLOUR, BOND_WIDTH),
+            BondType::Triplex{ red, black, yellow } => {
+                let along = end - start;
+                let length = along.length().max(f32::EPSILON);
+                let across = Vector2::new(-along.y / length, along.x / length);
+                for (slot, (present, colour)) in [red, black, yellow].into_iter().zip(TRIPLEX_COLOURS).enumerate(){
+                    if present{
+                        let offset = across * ((slot as f32 - 1.0) * TRIPLEX_SPACING);
+                        self.line(start + offset, end + offset, colour, TRIPLEX_WIDTH);
+                    }
+                }
+            }
+        }
+    }
+
+    fn line(&mut self, start: Vector2, end: Vector2, colour: &str, width: f32){
+        self.include(start, width);
+        self.include(end, width);
+        let _ = writeln!(self.body, r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{colour}" stroke-width="{width}" stroke-linecap="round"/>"#, start.x, start.y, end.x, end.y);
+    }
+
+    /// The finished SVG document, sized to fit everything drawn with a margin around it, and optionally filled with a background colour.
+    fn finish(self, background: Option<&str>) -> String{
+        let (min, max) = if self.min.x > self.max.x { (Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0)) } else { (self.min, self.max) };
+        let (x, y) = (min.x - MARGIN, min.y - MARGIN);
+        let (width, height) = (max.x - min.x + 2.0 * MARGIN, max.y - min.y + 2.0 * MARGIN);
+        let background = background.map_or(String::new(), |fill| format!("<rect x=\"{x:.1}\" y=\"{y:.1}\" width=\"{width:.1}\" height=\"{height:.1}\" fill=\"{fill}\"/>\n"));
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x:.1} {y:.1} {width:.1} {height:.1}\" width=\"{width:.0}\" height=\"{height:.0}\">\n{background}{}</svg>\n", self.body)
+    }
+}
@@ -0,0 +1,15 @@
+use serde_json::Value;
+
+use crate::data::{Puzzle, Solution};
+
+// JSON Schemas describing the JSON that `json::to_json` produces, so that tools reading it can check their types against ours.
+
+/// A JSON Schema for puzzles encoded as JSON.
+pub fn puzzle_schema() -> Value{
+    schemars::schema_for!(Puzzle).to_value()
+}
+
+/// A JSON Schema for solutions encoded as JSON.
+pub fn solution_schema() -> Value{
+    schemars::schema_for!(Solution).to_value()
+}
@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{Atom, HexIndex};
+
+// Sigmar's Garden, the solitaire minigame.
+
+/// The distance from the centre of the board to its edge.
+pub const BOARD_RADIUS: i32 = 5;
+
+/// The directions to each neighbour of a hex, in order around it.
+const DIRECTIONS: [HexIndex; 6] = [
+    HexIndex{ q: 1, r: 0 }, HexIndex{ q: 0, r: 1 }, HexIndex{ q: -1, r: 1 },
+    HexIndex{ q: -1, r: 0 }, HexIndex{ q: 0, r: -1 }, HexIndex{ q: 1, r: -1 }
+];
+
+/// The metals, in the order they must be removed.
+const METALS: [Atom; 6] = [Atom::Lead, Atom::Tin, Atom::Iron, Atom::Copper, Atom::Silver, Atom::Gold];
+
+/// A Sigmar's Garden board state: a hexagon of radius `BOARD_RADIUS` centred on the origin, with marbles on some hexes.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct SigmarBoard{
+    /// The marbles on the board, by position.
+    pub marbles: HashMap<HexIndex, Atom>
+}
+
+/// A move in Sigmar's Garden: either a pair of matching marbles, or a lone gold marble.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SigmarMove{
+    pub first: HexIndex,
+    /// The other marble of the pair, or `None` if `first` is gold.
+    pub second: Option<HexIndex>
+}
+
+impl SigmarBoard{
+
+    /// Parse a board from text, with one line per row of hexes from top to bottom and whitespace between hexes.
+    /// Rows have 6 to 11 hexes, and indentation is ignored. Each hex is `-` if empty, or one of:
+    /// `Sa` (salt), `Ai` (air), `Ea` (earth), `Fi` (fire), `Wa` (water), `Qu` (quicksilver), `Vi` (vitae), `Mo` (mors),
+    /// or the metals `Pb`, `Sn`, `Fe`, `Cu`, `Ag`, and `Au`. Case is ignored.
+    pub fn parse(text: &str) -> Result<SigmarBoard, &'static str>{
+        let rows: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        if rows.len() != (2 * BOARD_RADIUS + 1) as usize{
+            return Err("wrong number of rows on sigmar's garden board");
+        }
+        let mut marbles = HashMap::new();
+        for (row, line) in rows.into_iter().enumerate(){
+            let r = BOARD_RADIUS - row as i32;
+            let q_range = row_range(r);
+            let cells: Vec<&str> = line.split_whitespace().collect();
+            if cells.len() != q_range.clone().count(){
+                return Err("wrong number of hexes in row of sigmar's garden board");
+            }
+            for (q, cell) in q_range.zip(cells){
+                if cell != "-"{
+                    marbles.insert(HexIndex{ q, r }, marble_from_name(cell).ok_or("invalid sigmar's garden marble")?);
+                }
+            }
+        }
+        Ok(SigmarBoard{ marbles })
+    }
+
+    /// Write this board as text, in the format accepted by `parse`.
+    pub fn to_text(&self) -> String{
+        let mut out = String::new();
+        for r in (-BOARD_RADIUS..=BOARD_RADIUS).rev(){
+            out.push_str(&" ".repeat(3 * r.unsigned_abs() as usize / 2 + if r % 2 != 0 { 1 } else { 0 }));
+            let cells: Vec<&str> = row_range(r).map(|q| self.marbles.get(&HexIndex{ q, r }).map_or("-", |m| marble_name(*m))).collect();
+            out.push_str(&cells.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Whether a hex is on the board.
+    pub fn in_bounds(pos: HexIndex) -> bool{
+        pos.q.abs() <= BOARD_RADIUS && pos.r.abs() <= BOARD_RADIUS && pos.s().abs() <= BOARD_RADIUS
+    }
+
+    /// Whether every marble has been removed.
+    pub fn is_cleared(&self) -> bool{
+        self.marbles.is_empty()
+    }
+
+    /// Whether the marble at a position can be selected: it must have three consecutive empty neighbours,
+    /// and metals can only be selected once every lesser metal has been removed.
+    pub fn is_free(&self, pos: HexIndex) -> bool{
+        let Some(marble) = self.marbles.get(&pos) else { return false };
+        if METALS.contains(marble) && Some(*marble) != self.active_metal(){
+            return false;
+        }
+        let empty: Vec<bool> = DIRECTIONS.iter().map(|d| !self.marbles.contains_key(&(pos + *d))).collect();
+        (0..6).any(|i| empty[i] && empty[(i + 1) % 6] && empty[(i + 2) % 6])
+    }
+
+    /// The least metal still on the board, which is the only one that can be removed.
+    pub fn active_metal(&self) -> Option<Atom>{
+        METALS.into_iter().find(|m| self.marbles.values().any(|v| v == m))
+    }
+
+    /// Whether a move can be made on this board.
+    pub fn is_legal(&self, mv: SigmarMove) -> bool{
+        if !self.is_free(mv.first){
+            return false;
+        }
+        let first = self.marbles[&mv.first];
+        match mv.second{
+            None => first == Atom::Gold,
+            Some(second_pos) => second_pos != mv.first && self.is_free(second_pos) && marbles_match(first, self.marbles[&second_pos])
+        }
+    }
+
+    /// Every legal move on this board, in a consistent order.
+    pub fn legal_moves(&self) -> Vec<SigmarMove>{
+        let mut free: Vec<HexIndex> = self.marbles.keys().cloned().filter(|p| self.is_free(*p)).collect();
+        free.sort_by_key(|p| (p.r, p.q));
+        let mut moves = Vec::new();
+        for (i, first) in free.iter().enumerate(){
+            if self.marbles[first] == Atom::Gold{
+                moves.push(SigmarMove{ first: *first, second: None });
+            }
+            for second in &free[i + 1..]{
+                if marbles_match(self.marbles[first], self.marbles[second]){
+                    moves.push(SigmarMove{ first: *first, second: Some(*second) });
+                }
+            }
+        }
+        moves
+    }
+
+    /// Make a move, removing its marbles from the board.
+    pub fn apply(&mut self, mv: SigmarMove) -> Result<(), &'static str>{
+        if !self.is_legal(mv){
+            return Err("illegal sigmar's garden move");
+        }
+        self.marbles.remove(&mv.first);
+        if let Some(second) = mv.second{
+            self.marbles.remove(&second);
+        }
+        Ok(())
+    }
+
+    /// Find a sequence of moves that clears the board, if there is one.
+    pub fn solve(&self) -> Option<Vec<SigmarMove>>{
+        let mut moves = Vec::new();
+        let mut dead_ends = HashSet::new();
+        if solve_from(&mut self.clone(), &mut moves, &mut dead_ends){
+            Some(moves)
+        }else{
+            None
+        }
+    }
+}
+
+/// Whether two marbles can be removed together, ignoring whether they're free.
+pub fn marbles_match(a: Atom, b: Atom) -> bool{
+    let cardinal = |m: Atom| matches!(m, Atom::Air | Atom::Earth | Atom::Fire | Atom::Water);
+    let metal = |m: Atom| METALS[..5].contains(&m);
+    match (a, b){
+        (Atom::Salt, other) | (other, Atom::Salt) => other == Atom::Salt || cardinal(other),
+        (Atom::Vitae, Atom::Mors) | (Atom::Mors, Atom::Vitae) => true,
+        (Atom::Quicksilver, other) | (other, Atom::Quicksilver) => metal(other),
+        _ => a == b && cardinal(a)
+    }
+}
+
+fn solve_from(board: &mut SigmarBoard, moves: &mut Vec<SigmarMove>, dead_ends: &mut HashSet<Vec<(HexIndex, Atom)>>) -> bool{
+    if board.is_cleared(){
+        return true;
+    }
+    let mut key: Vec<(HexIndex, Atom)> = board.marbles.iter().map(|(p, m)| (*p, *m)).collect();
+    key.sort_by_key(|(p, _)| (p.r, p.q));
+    if dead_ends.contains(&key){
+        return false;
+    }
+    for mv in board.legal_moves(){
+        let removed: Vec<(HexIndex, Atom)> = [Some(mv.first), mv.second].into_iter().flatten().map(|p| (p, board.marbles[&p])).collect();
+        for (pos, _) in &removed{
+            board.marbles.remove(pos);
+        }
+        moves.push(mv);
+        if solve_from(board, moves, dead_ends){
+            return true;
+        }
+        moves.pop();
+        board.marbles.extend(removed);
+    }
+    dead_ends.insert(key);
+    false
+}
+
+fn row_range(r: i32) -> std::ops::RangeInclusive<i32>{
+    (-BOARD_RADIUS).max(-BOARD_RADIUS - r)..=BOARD_RADIUS.min(BOARD_RADIUS - r)
+}
+
+fn marble_from_name(name: &str) -> Option<Atom>{
+    Some(match name.to_ascii_lowercase().as_str(){
+        "sa" => Atom::Salt,
+        "ai" => Atom::Air,
+        "ea" => Atom::Earth,
+        "fi" => Atom::Fire,
+        "wa" => Atom::Water,
+        "qu" => Atom::Quicksilver,
+        "vi" => Atom::Vitae,
+        "mo" => Atom::Mors,
+        "pb" => Atom::Lead,
+        "sn" => Atom::Tin,
+        "fe" => Atom::Iron,
+        "cu" => Atom::Copper,
+        "ag" => Atom::Silver,
+        "au" => Atom::Gold,
+        _ => return None
+    })
+}
+
+fn marble_name(marble: Atom) -> &'static str{
+    match marble{
+        Atom::Salt => "Sa",
+        Atom::Air => "Ai",
+        Atom::Earth => "Ea",
+        Atom::Fire => "Fi",
+        Atom::Water => "Wa",
+        Atom::Quicksilver => "Qu",
+        Atom::Vitae => "Vi",
+        Atom::Mors => "Mo",
+        Atom::Lead => "Pb",
+        Atom::Tin => "Sn",
+        Atom::Iron => "Fe",
+        Atom::Copper => "Cu",
+        Atom::Silver => "Ag",
+        Atom::Gold => "Au",
+        Atom::Quintessence | Atom::Repeat | Atom::Unknown(_) => "?"
+    }
+}
@@ -0,0 +1,73 @@
+use crate::data::{HexIndex, HexRotation};
+
+use super::{glyphs, Sim, SimPartType};
+
+// Area tracking: every hex covered by a part, an arm, or an atom, including hexes swept over by rotations.
+
+/// The number of points sampled along each swept arc. Kept away from the exact midpoint, which lies on a hex boundary for adjacent hexes.
+const SWEEP_SAMPLES: i32 = 8;
+
+/// The hexes passed over by something at `pos` while it rotates 60 degrees around `around`, excluding its start and end.
+pub(super) fn swept_hexes(pos: HexIndex, around: HexIndex, by: HexRotation) -> impl Iterator<Item = HexIndex>{
+    let (x, y) = to_pixel(pos - around);
+    let dir = if by == HexRotation::R300 { -1.0 } else { 1.0 };
+    (1..SWEEP_SAMPLES).map(move |i| {
+        let angle = dir * std::f32::consts::FRAC_PI_3 * (i as f32) / (SWEEP_SAMPLES as f32);
+        let (sin, cos) = angle.sin_cos();
+        from_pixel(x * cos - y * sin, x * sin + y * cos) + around
+    })
+}
+
+/// The centre of a hex on a plane where adjacent hexes are 1 apart and the Q axis points along X.
+fn to_pixel(hex: HexIndex) -> (f32, f32){
+    (hex.q as f32 + hex.r as f32 / 2.0, hex.r as f32 * 3f32.sqrt() / 2.0)
+}
+
+/// The hex containing a point on the plane used by `to_pixel`.
+fn from_pixel(x: f32, y: f32) -> HexIndex{
+    let r = y * 2.0 / 3f32.sqrt();
+    let q = x - r / 2.0;
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds{
+        rq = -rr - rs;
+    }else if dr > ds{
+        rr = -rq - rs;
+    }
+    HexIndex{ q: rq as i32, r: rr as i32 }
+}
+
+impl Sim{
+
+    /// Mark the hexes currently covered by parts, arms, and atoms as part of the area.
+    pub(super) fn mark_area(&mut self){
+        for part in &self.parts{
+            match &part.ty{
+                SimPartType::Input(molecule) | SimPartType::Output(molecule, _) | SimPartType::PolymerOutput(molecule, _) => {
+                    self.swept_hexes.extend(molecule.atoms.keys().map(|h| h.rotated(HexIndex::default(), part.rotation) + part.pos));
+                }
+                SimPartType::Arm(arm) => {
+                    self.swept_hexes.insert_hex(part.pos);
+                    for gripper in arm.gripper_positions(part.pos, part.rotation){
+                        self.swept_hexes.extend(arm_hexes(part.pos, gripper, arm.length));
+                    }
+                }
+                SimPartType::Track(hexes) | SimPartType::Conduit(hexes) => self.swept_hexes.extend(hexes.iter().cloned()),
+                ty => {
+                    self.swept_hexes.extend(glyphs::footprint(ty).iter().map(|h| h.rotated(HexIndex::default(), part.rotation) + part.pos));
+                }
+            }
+        }
+        for molecule in &self.molecules{
+            self.swept_hexes.extend(molecule.positions());
+        }
+    }
+}
+
+/// The hexes covered by an arm segment from its base to a gripper, excluding the base.
+pub(super) fn arm_hexes(base: HexIndex, gripper: HexIndex, length: i32) -> impl Iterator<Item = HexIndex>{
+    let step = gripper - base;
+    let unit = HexIndex{ q: step.q / length.max(1), r: step.r / length.max(1) };
+    (1..=length).map(move |k| HexIndex{ q: base.q + unit.q * k, r: base.r + unit.r * k })
+}
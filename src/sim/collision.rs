@@ -20,8 +20,23 @@ impl Vector2{
         Vector2::new((h.q as f32)*HEX_WIDTH + 0.5 * (h.r as f32)*HEX_WIDTH, (h.r as f32)*HEX_HEIGHT)
     }
 
+    /// The hex whose centre is closest to this point.
+    pub fn to_hex_index(self) -> HexIndex{
+        let r = self.y / HEX_HEIGHT;
+        let q = self.x / HEX_WIDTH - 0.5 * r;
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds{
+            rq = -rr - rs;
+        }else if dr > ds{
+            rr = -rq - rs;
+        }
+        HexIndex{ q: rq as i32, r: rr as i32 }
+    }
+
     pub fn length2(self) -> f32{
-        self.x * self.x + self.y + self.y
+        self.x * self.x + self.y * self.y
     }
 
     pub fn length(self) -> f32{
@@ -43,6 +58,20 @@ impl From<HexIndex> for Vector2{
     }
 }
 
+impl HexIndex{
+    /// The centre of this hex on the board, with hexes pointy-top like in the game and `scale` between the centres of neighbouring hexes.
+    /// X runs along the Q axis and Y upwards, so renderers drawing to the screen should flip Y.
+    pub fn to_cartesian(self, scale: f32) -> (f32, f32){
+        let v = Vector2::from_hex_index(self) * (scale / HEX_WIDTH);
+        (v.x, v.y)
+    }
+
+    /// The hex containing a point on the board, as laid out by `to_cartesian` with the same `scale`.
+    pub fn from_cartesian(x: f32, y: f32, scale: f32) -> HexIndex{
+        (Vector2::new(x, y) * (HEX_WIDTH / scale)).to_hex_index()
+    }
+}
+
 impl Add for Vector2{
     type Output = Vector2;
     fn add(self, rhs: Vector2) -> Vector2{
@@ -103,11 +132,16 @@ impl ColliderType{
         }
     }
 
+    /// The distance two colliders must be kept apart by, or `None` if they can't collide.
     pub fn radius_with(self, other: ColliderType) -> Option<f32>{
         if self == ColliderType::ArmGripper && other != ColliderType::ChamberWall
         || other == ColliderType::ArmGripper && self != ColliderType::ChamberWall{
             return None;
         }
+        if self == ColliderType::ChamberWall && !matches!(other, ColliderType::Atom | ColliderType::ArmGripper)
+        || other == ColliderType::ChamberWall && !matches!(self, ColliderType::Atom | ColliderType::ArmGripper){
+            return None;
+        }
         Some(self.radius() + other.radius())
     }
 }
@@ -129,7 +163,9 @@ impl Movement{
                 start + (end - start)*time
             }
             Movement::Rotate{ start, around, rotation } => {
-                let (start, around, r): (Vector2, Vector2, f32) = (start.into(), around.into(), -rotation.to_radians() * time);
+                // take the short way round, so that 300 degrees is a clockwise turn of 60
+                let turns = if rotation.turns() > 3 { rotation.turns() as f32 - 6.0 } else { rotation.turns() as f32 };
+                let (start, around, r): (Vector2, Vector2, f32) = (start.into(), around.into(), turns * std::f32::consts::FRAC_PI_3 * time);
                 let tr = start - around;
                 Vector2::new(f32::cos(r) * tr.x - f32::sin(r) * tr.y, f32::sin(r) * tr.x + f32::cos(r) * tr.y) + around
             }
@@ -145,23 +181,145 @@ pub struct Collider{
 }
 
 /// Test whether anything in this list of colliders collides.
-pub fn collides(colliders: &Vec<Collider>, steps: u32) -> bool{
+pub fn collides(colliders: &[Collider], steps: u32) -> bool{
+    first_collision(colliders, steps).is_some()
+}
+
+/// Find the earliest point at which anything in this list of colliders collides, checking `steps + 1` evenly spaced times.
+/// Returns the position of the first collider involved.
+pub fn first_collision(colliders: &[Collider], steps: u32) -> Option<Vector2>{
+    find_contact(colliders, steps).map(|contact| contact.first_pos)
+}
+
+/// Two colliders touching at the earliest point anything collides.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Contact{
+    /// The index of the first collider in the list that's touching anything, and where it was.
+    pub first: usize,
+    pub first_pos: Vector2,
+    /// The index of a collider it touched, and where that was.
+    pub second: usize,
+    pub second_pos: Vector2
+}
+
+/// Find the earliest point at which anything in this list of colliders collides, checking `steps + 1` evenly spaced times.
+/// Returns the first collider in the list involved, along with one it collided with.
+pub fn find_contact(colliders: &[Collider], steps: u32) -> Option<Contact>{
     for i in 0..=steps{
         let time: f32 = (i as f32)/(steps as f32);
         // bleh
         let objs: Vec<(Vector2, ColliderType)> = colliders.iter().map(|c| (c.movement.pos_at(time), c.ty)).collect();
-        for l in 0..objs.len(){
-            for r in 0..objs.len(){
-                if l != r{
-                    let ((lpos, lty), (rpos, rty)) = (objs[l], objs[r]);
-                    if let Some(radius) = lty.radius_with(rty){
-                        if lpos.dist2(rpos) < radius*radius{
-                            return true;
-                        }
-                    }
+        // sweep along x, so only colliders that are close horizontally are compared
+        let mut by_x: Vec<usize> = (0..objs.len()).collect();
+        by_x.sort_by(|a, b| objs[*a].0.x.total_cmp(&objs[*b].0.x));
+        // for each collider, the first one found touching it
+        let mut touching: Vec<Option<usize>> = vec![None; objs.len()];
+        for (j, &l) in by_x.iter().enumerate(){
+            let (lpos, lty) = objs[l];
+            for &r in by_x[j + 1..].iter().take_while(|r| objs[**r].0.x - lpos.x < MAX_RADIUS){
+                let (rpos, rty) = objs[r];
+                if lty.radius_with(rty).is_some_and(|radius| lpos.dist2(rpos) < radius*radius){
+                    touching[l].get_or_insert(r);
+                    touching[r].get_or_insert(l);
                 }
             }
         }
+        if let Some((first, &Some(second))) = touching.iter().enumerate().find(|(_, t)| t.is_some()){
+            return Some(Contact{ first, first_pos: objs[first].0, second, second_pos: objs[second].0 });
+        }
     }
-    false
-}
\ No newline at end of file
+    None
+}
+
+/// The largest distance any two colliders must be kept apart by.
+const MAX_RADIUS: f32 = 58.0;
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::{Atom, Molecule, Puzzle, Solution};
+    use crate::sim::{Sim, SimError};
+
+    fn stay(ty: ColliderType, q: i32, r: i32) -> Collider{
+        Collider{ ty, movement: Movement::Stay{ at: HexIndex{ q, r } } }
+    }
+
+    fn rotate(ty: ColliderType, q: i32, r: i32, rotation: HexRotation) -> Collider{
+        Collider{ ty, movement: Movement::Rotate{ start: HexIndex{ q, r }, around: HexIndex::default(), rotation } }
+    }
+
+    #[test]
+    fn atoms_collide_only_when_they_get_too_close(){
+        let atom = ColliderType::Atom;
+        assert!(!collides(&[stay(atom, 0, 0), stay(atom, 1, 0)], 8));
+        let into = Collider{ ty: atom, movement: Movement::Translate{ start: HexIndex::default(), end: HexIndex{ q: 1, r: 0 } } };
+        assert!(collides(&[into, stay(atom, 1, 0)], 8));
+        // swinging clockwise from (2, 0) passes (2, -1), but swinging anticlockwise doesn't
+        assert!(collides(&[rotate(atom, 2, 0, HexRotation::R300), stay(atom, 2, -1)], 8));
+        assert!(!collides(&[rotate(atom, 2, 0, HexRotation::R60), stay(atom, 2, -1)], 8));
+    }
+
+    #[test]
+    fn grippers_and_walls_only_collide_with_some_colliders(){
+        use ColliderType::*;
+        assert!(collides(&[stay(ArmBase, 0, 0), stay(Atom, 0, 0)], 1));
+        assert!(collides(&[stay(ChamberWall, 0, 0), stay(Atom, 0, 0)], 1));
+        assert!(collides(&[stay(ChamberWall, 0, 0), stay(ArmGripper, 0, 0)], 1));
+        assert!(!collides(&[stay(ArmGripper, 0, 0), stay(Atom, 0, 0)], 1));
+        assert!(!collides(&[stay(ArmGripper, 0, 0), stay(ArmBase, 0, 0)], 1));
+        assert!(!collides(&[stay(ChamberWall, 0, 0), stay(ArmBase, 0, 0)], 1));
+    }
+
+    #[test]
+    fn contacts_report_both_colliders(){
+        let colliders = [stay(ColliderType::Atom, 5, 5), stay(ColliderType::Atom, 0, 0), stay(ColliderType::ArmBase, 0, 0)];
+        let contact = find_contact(&colliders, 1).unwrap();
+        assert_eq!((contact.first, contact.second), (1, 2));
+        assert_eq!(contact.first_pos.to_hex_index(), HexIndex::default());
+    }
+
+    /// An arm at the origin that grabs whatever is at (1, 0) and turns clockwise, towards (1, -1), with a salt input on each given hex
+    /// and a second arm at each given base.
+    fn swing(inputs: &[HexIndex], bases: &[HexIndex]) -> (Puzzle, Solution){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let mut builder = SolutionBuilder::new("solution", "puzzle").arm(HexIndex::default(), 0, 1);
+        for input in inputs{
+            builder = builder.input(0, *input, 0);
+        }
+        for base in bases{
+            builder = builder.arm(*base, 0, 1);
+        }
+        (puzzle, builder.instructions(0, "GR").build().unwrap())
+    }
+
+    #[test]
+    fn held_atoms_collide_with_atoms_and_arms(){
+        let (puzzle, solution) = swing(&[HexIndex{ q: 1, r: 0 }, HexIndex{ q: 1, r: -1 }], &[]);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.step().unwrap();
+        let error = sim.step().unwrap_err();
+        assert!(matches!(&error, SimError::Collision{ cycle: 1, parts, .. } if parts == &[0]), "{error:?}");
+
+        let (puzzle, solution) = swing(&[HexIndex{ q: 1, r: 0 }], &[HexIndex{ q: 1, r: -1 }]);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.step().unwrap();
+        let error = sim.step().unwrap_err();
+        assert!(matches!(&error, SimError::Collision{ cycle: 1, parts, .. } if parts == &[0, 2]), "{error:?}");
+    }
+
+    #[test]
+    fn empty_grippers_only_hit_walls(){
+        // an empty gripper passes over atoms, with or without walls elsewhere
+        let (puzzle, solution) = swing(&[HexIndex{ q: 1, r: -1 }], &[]);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.walls = vec![HexIndex{ q: 5, r: 5 }];
+        assert!(sim.step().and_then(|_| sim.step()).is_ok());
+
+        let (puzzle, solution) = swing(&[], &[]);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.walls = vec![HexIndex{ q: 1, r: -1 }];
+        sim.step().unwrap();
+        assert_eq!(sim.step(), Err(SimError::ArmHitWall{ cycle: 1, part: 0, pos: HexIndex{ q: 1, r: -1 } }));
+    }
+}
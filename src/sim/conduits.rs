@@ -0,0 +1,118 @@
+use crate::data::{HexIndex, Molecule};
+
+use super::{Sim, SimMolecule, SimPartType};
+
+// Conduits, which carry molecules dropped on one end over to the other.
+
+impl Sim{
+
+    /// Move each of the given molecules that lies entirely on one end of a conduit to the other end, keeping its orientation relative to the conduit.
+    /// Molecules that wouldn't land entirely on the other end, or would land on another atom, stay where they are.
+    pub(super) fn transfer_through_conduits(&mut self, molecules: &[usize]){
+        for &m in molecules{
+            if let Some(layout) = self.conduit_destination(m){
+                self.replace_molecule(m, SimMolecule{ layout, pos: HexIndex::default(), grabbed: false });
+            }
+        }
+    }
+
+    /// Why a molecule dropped on a conduit can't be carried by it, if it touches a conduit and can't be.
+    pub(super) fn conduit_drop_error(&self, m: usize) -> Option<&'static str>{
+        let positions: Vec<HexIndex> = self.molecules[m].positions().collect();
+        let conduits: Vec<&Vec<HexIndex>> = self.parts.iter().filter_map(|p| match &p.ty{
+            SimPartType::Conduit(hexes) => Some(hexes),
+            _ => None
+        }).collect();
+        if !positions.iter().any(|p| conduits.iter().any(|hexes| hexes.contains(p))) || self.conduit_destination(m).is_some(){
+            return None;
+        }
+        Some(if conduits.iter().any(|hexes| positions.iter().all(|p| hexes.contains(p))){
+            "molecule doesn't fit on the other end of its conduit"
+        }else{
+            "molecule is only partly on a conduit"
+        })
+    }
+
+    /// Where a molecule would be carried by the conduit it lies on, as board positions, if it can be.
+    fn conduit_destination(&self, m: usize) -> Option<Molecule>{
+        let molecule = &self.molecules[m];
+        let positions: Vec<HexIndex> = molecule.positions().collect();
+        let is_conduit = |i: usize| match &self.parts[i].ty{
+            SimPartType::Conduit(hexes) => Some(hexes),
+            _ => None
+        };
+        let from = (0..self.parts.len()).find(|i| is_conduit(*i).is_some_and(|hexes| positions.iter().all(|p| hexes.contains(p))))?;
+        let to = (0..self.parts.len()).find(|i| *i != from && self.parts[*i].index == self.parts[from].index && is_conduit(*i).is_some())?;
+        let (source, target) = (&self.parts[from], &self.parts[to]);
+        let turn = target.rotation - source.rotation;
+        let map = |pos: HexIndex| (pos - source.pos).rotated(HexIndex::default(), turn) + target.pos;
+        let target_hexes = is_conduit(to)?;
+        positions.iter()
+            .all(|p| target_hexes.contains(&map(*p)) && self.molecule_at(map(*p)).is_none_or(|other| other == m))
+            .then(|| molecule.layout.translated(molecule.pos).mapped_positions(map))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::{Atom, HexRotation};
+    use crate::sim::SimPart;
+
+    /// A board with a conduit end on (0, 0) and (1, 0), paired with one turned 60 degrees anticlockwise at (10, 0), which covers (10, 0) and (10, 1).
+    fn board() -> Sim{
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle").arm(HexIndex{ q: -6, r: 6 }, 0, 1).instructions(0, "G").build().unwrap();
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        let conduit = |pos: HexIndex, rotation, hexes: &[HexIndex]| SimPart{ pos, rotation, index: 7, ty: SimPartType::Conduit(hexes.to_vec()) };
+        sim.parts.push(conduit(HexIndex::default(), HexRotation::R0, &[HexIndex::default(), HexIndex{ q: 1, r: 0 }]));
+        sim.parts.push(conduit(HexIndex{ q: 10, r: 0 }, HexRotation::R60, &[HexIndex{ q: 10, r: 0 }, HexIndex{ q: 10, r: 1 }]));
+        sim
+    }
+
+    /// Add a salt bonded to a water on the hex after it, returning the molecule's index.
+    fn add_pair(sim: &mut Sim, salt: HexIndex) -> usize{
+        let (q, r) = (salt.q, salt.r);
+        let layout = Molecule::builder().atom(q, r, Atom::Salt).atom(q + 1, r, Atom::Water).bond((q, r), (q + 1, r)).build().unwrap();
+        sim.add_molecule(SimMolecule::new(layout));
+        sim.molecules.len() - 1
+    }
+
+    #[test]
+    fn transfers_keep_orientation_relative_to_the_conduit(){
+        let mut sim = board();
+        let m = add_pair(&mut sim, HexIndex::default());
+        assert_eq!(sim.conduit_drop_error(m), None);
+        sim.transfer_through_conduits(&[m]);
+        assert_eq!((sim.atom(HexIndex::default()), sim.atom(HexIndex{ q: 1, r: 0 })), (None, None));
+        assert_eq!((sim.atom(HexIndex{ q: 10, r: 0 }), sim.atom(HexIndex{ q: 10, r: 1 })), (Some(Atom::Salt), Some(Atom::Water)));
+        assert!(sim.molecules[m].bond_at(HexIndex{ q: 10, r: 0 }, HexIndex{ q: 10, r: 1 }).is_some());
+    }
+
+    #[test]
+    fn blocked_transfers_stay_put(){
+        let mut sim = board();
+        sim.add_molecule(SimMolecule::new(Molecule::builder().atom(10, 1, Atom::Fire).build().unwrap()));
+        let m = add_pair(&mut sim, HexIndex::default());
+        assert_eq!(sim.conduit_drop_error(m), Some("molecule doesn't fit on the other end of its conduit"));
+        sim.transfer_through_conduits(&[m]);
+        assert_eq!(sim.atom(HexIndex::default()), Some(Atom::Salt));
+        assert_eq!(sim.atom(HexIndex{ q: 10, r: 0 }), None);
+    }
+
+    #[test]
+    fn molecules_partly_on_a_conduit_stay_put(){
+        let mut sim = board();
+        let m = add_pair(&mut sim, HexIndex{ q: 1, r: 0 });
+        assert_eq!(sim.conduit_drop_error(m), Some("molecule is only partly on a conduit"));
+        sim.transfer_through_conduits(&[m]);
+        assert_eq!((sim.atom(HexIndex{ q: 1, r: 0 }), sim.atom(HexIndex{ q: 2, r: 0 })), (Some(Atom::Salt), Some(Atom::Water)));
+
+        let m = add_pair(&mut sim, HexIndex{ q: 3, r: 3 });
+        assert_eq!(sim.conduit_drop_error(m), None);
+        sim.transfer_through_conduits(&[m]);
+        assert_eq!(sim.atom(HexIndex{ q: 3, r: 3 }), Some(Atom::Salt));
+    }
+}
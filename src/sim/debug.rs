@@ -0,0 +1,48 @@
+use crate::data::{Atom, HexIndex};
+
+use super::observer::SimObserver;
+use super::{Sim, SimArm, SimError, SimPart, SimPartType};
+
+// Stepping through a simulation and looking at it between cycles, for debuggers.
+
+/// Collects the glyphs that fire.
+struct GlyphRecorder(Vec<usize>);
+
+impl SimObserver for GlyphRecorder{
+    fn glyph_activated(&mut self, part: usize){
+        self.0.push(part);
+    }
+}
+
+impl Sim{
+
+    /// Step until `cycle` cycles have been simulated, doing nothing if they already have.
+    /// On failure, the simulation is left as it was when the failing cycle stopped.
+    pub fn run_until(&mut self, cycle: i32) -> Result<(), SimError>{
+        while self.cycle < cycle{
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Every arm, with its index in `parts`.
+    pub fn arms(&self) -> impl Iterator<Item = (usize, &SimPart, &SimArm)> + '_{
+        self.parts.iter().enumerate().filter_map(|(i, part)| match &part.ty{
+            SimPartType::Arm(arm) => Some((i, part, arm)),
+            _ => None
+        })
+    }
+
+    /// Every atom on the board, with its position and the index of the molecule it's part of in `molecules`.
+    pub fn atoms(&self) -> impl Iterator<Item = (HexIndex, Atom, usize)> + '_{
+        self.molecules.iter().enumerate().flat_map(|(i, molecule)| molecule.layout.atoms.iter().map(move |(pos, atom)| (*pos + molecule.pos, *atom, i)))
+    }
+
+    /// Indices of the glyphs that would fire if glyphs acted on the board as it is now, in the order they'd fire.
+    /// Glyphs can enable or block ones after them, which is taken into account.
+    pub fn pending_glyph_activations(&self) -> Vec<usize>{
+        let mut recorder = GlyphRecorder(Vec::new());
+        self.clone().apply_glyphs(&mut recorder);
+        recorder.0
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+
+use crate::data::{Atom, Bond, BondType, HexIndex, HexMap, Molecule, PartType};
+
+use super::observer::SimObserver;
+use super::{Sim, SimMolecule, SimPartType};
+
+// Glyph behaviour, and the atom and bond edits glyphs are built from.
+
+/// The hexes covered by each glyph, relative to its position, in its default rotation. See `PartType::footprint`.
+pub(super) fn footprint(ty: &SimPartType) -> &'static [HexIndex]{
+    let ty = match ty{
+        SimPartType::Bonding => PartType::Bonding,
+        SimPartType::MultiBonding => PartType::MultiBonding,
+        SimPartType::Unbonding => PartType::Unbonding,
+        SimPartType::Calcification => PartType::Calcification,
+        SimPartType::Animismus => PartType::Animismus,
+        SimPartType::Projection => PartType::Projection,
+        SimPartType::Purification => PartType::Purification,
+        SimPartType::Duplication => PartType::Duplication,
+        SimPartType::Disposal => PartType::Disposal,
+        SimPartType::Equilibrium => PartType::Equilibrium,
+        SimPartType::Unification => PartType::Unification,
+        SimPartType::Dispersion => PartType::Dispersion,
+        SimPartType::TriplexBonding => PartType::TriplexBonding,
+        _ => return &[]
+    };
+    ty.footprint()
+}
+
+/// The element that belongs on each outer hex of unification and dispersion glyphs, following their footprint.
+const QUINTESSENCE_ELEMENTS: [Atom; 4] = [Atom::Air, Atom::Water, Atom::Fire, Atom::Earth];
+
+/// The hexes of a triplex bonder's footprint joined by each of its edges, and the colour of bond that edge makes.
+const TRIPLEX_EDGES: [(usize, usize, BondType); 3] = [
+    (0, 1, BondType::Triplex{ red: true, black: false, yellow: false }),
+    (0, 2, BondType::Triplex{ red: false, black: true, yellow: false }),
+    (1, 2, BondType::Triplex{ red: false, black: false, yellow: true })
+];
+
+fn is_cardinal(atom: Atom) -> bool{
+    matches!(atom, Atom::Air | Atom::Earth | Atom::Fire | Atom::Water)
+}
+
+/// The metal that a metal is promoted to by projection or purification, or `None` for gold and non-metals.
+fn next_metal(atom: Atom) -> Option<Atom>{
+    Some(match atom{
+        Atom::Lead => Atom::Tin,
+        Atom::Tin => Atom::Iron,
+        Atom::Iron => Atom::Copper,
+        Atom::Copper => Atom::Silver,
+        Atom::Silver => Atom::Gold,
+        _ => return None
+    })
+}
+
+impl Sim{
+
+    /// Apply every glyph whose conditions are met, in solution order.
+    /// Atoms that glyphs consume must be single atoms that aren't held, but atoms that glyphs bond or transmute may be held.
+    pub(super) fn apply_glyphs(&mut self, observer: &mut dyn SimObserver){
+        for i in 0..self.parts.len(){
+            let part = &self.parts[i];
+            let hexes: Vec<HexIndex> = footprint(&part.ty).iter().map(|h| h.rotated(HexIndex::default(), part.rotation) + part.pos).collect();
+            let fired = match self.parts[i].ty{
+                SimPartType::Bonding => self.add_bond(hexes[0], hexes[1]),
+                SimPartType::MultiBonding => {
+                    let mut fired = false;
+                    for prong in &hexes[1..]{
+                        fired |= self.add_bond(hexes[0], *prong);
+                    }
+                    fired
+                }
+                SimPartType::TriplexBonding => {
+                    let mut fired = false;
+                    for (a, b, colour) in TRIPLEX_EDGES{
+                        fired |= self.add_triplex_colour(hexes[a], hexes[b], colour);
+                    }
+                    fired
+                }
+                SimPartType::Unbonding => self.remove_bond(hexes[0], hexes[1]),
+                SimPartType::Calcification => match self.atom(hexes[0]){
+                    Some(atom) if is_cardinal(atom) => self.set_atom(hexes[0], Atom::Salt),
+                    _ => false
+                },
+                SimPartType::Duplication => match (self.atom(hexes[0]), self.atom(hexes[1])){
+                    (Some(source), Some(Atom::Salt)) if is_cardinal(source) => self.set_atom(hexes[1], source),
+                    _ => false
+                },
+                SimPartType::Projection => match (self.atom(hexes[0]), self.atom(hexes[1]).and_then(next_metal)){
+                    (Some(Atom::Quicksilver), Some(promoted)) if self.is_free_single(hexes[0]) => {
+                        self.remove_single(hexes[0]);
+                        self.set_atom(hexes[1], promoted)
+                    }
+                    _ => false
+                },
+                SimPartType::Purification => match (self.atom(hexes[0]), self.atom(hexes[1])){
+                    (Some(a), Some(b)) if a == b && next_metal(a).is_some() && self.is_free_single(hexes[0]) && self.is_free_single(hexes[1]) && self.atom(hexes[2]).is_none() => {
+                        self.remove_single(hexes[0]);
+                        self.remove_single(hexes[1]);
+                        self.spawn_atom(hexes[2], next_metal(a).unwrap());
+                        true
+                    }
+                    _ => false
+                },
+                SimPartType::Animismus => match (self.atom(hexes[0]), self.atom(hexes[1])){
+                    (Some(Atom::Salt), Some(Atom::Salt)) if self.is_free_single(hexes[0]) && self.is_free_single(hexes[1]) && self.atom(hexes[2]).is_none() && self.atom(hexes[3]).is_none() => {
+                        self.remove_single(hexes[0]);
+                        self.remove_single(hexes[1]);
+                        self.spawn_atom(hexes[2], Atom::Vitae);
+                        self.spawn_atom(hexes[3], Atom::Mors);
+                        true
+                    }
+                    _ => false
+                },
+                SimPartType::Unification => {
+                    let ready = self.atom(hexes[0]).is_none()
+                        && QUINTESSENCE_ELEMENTS.iter().zip(&hexes[1..]).all(|(element, hex)| self.atom(*hex) == Some(*element) && self.is_free_single(*hex));
+                    if ready{
+                        for hex in &hexes[1..]{
+                            self.remove_single(*hex);
+                        }
+                        self.spawn_atom(hexes[0], Atom::Quintessence);
+                    }
+                    ready
+                }
+                SimPartType::Dispersion => match self.atom(hexes[0]){
+                    Some(Atom::Quintessence) if self.is_free_single(hexes[0]) && hexes[1..].iter().all(|h| self.atom(*h).is_none()) => {
+                        self.remove_single(hexes[0]);
+                        for (element, hex) in QUINTESSENCE_ELEMENTS.iter().zip(&hexes[1..]){
+                            self.spawn_atom(*hex, *element);
+                        }
+                        true
+                    }
+                    _ => false
+                },
+                SimPartType::Disposal => match self.molecule_at(hexes[0]){
+                    Some(m) if !self.molecules[m].grabbed => {
+                        let molecule = self.remove_molecule(m);
+                        for atom in molecule.layout.atoms.values(){
+                            *self.destroyed_atoms.entry(*atom).or_insert(0) += 1;
+                        }
+                        true
+                    }
+                    _ => false
+                },
+                _ => false
+            };
+            if fired{
+                self.glyph_activations[i] += 1;
+                observer.glyph_activated(i);
+            }
+        }
+        self.update_grabbed();
+    }
+
+    /// The type of the atom at a position, if there is one.
+    pub fn atom(&self, pos: HexIndex) -> Option<Atom>{
+        self.molecule_at(pos).map(|m| self.molecules[m].atom_at(pos))
+    }
+
+    /// Change the type of the atom at a position, returning whether there was one.
+    fn set_atom(&mut self, pos: HexIndex, atom: Atom) -> bool{
+        let Some(m) = self.molecule_at(pos) else { return false };
+        let molecule = &mut self.molecules[m];
+        molecule.layout.atoms.insert(pos - molecule.pos, atom);
+        true
+    }
+
+    /// Whether the atom at a position is a single unbonded atom that isn't held.
+    fn is_free_single(&self, pos: HexIndex) -> bool{
+        self.molecule_at(pos).is_some_and(|m| self.molecules[m].layout.atoms.len() == 1 && !self.molecules[m].grabbed)
+    }
+
+    /// Remove the single-atom molecule at a position.
+    fn remove_single(&mut self, pos: HexIndex){
+        if let Some(m) = self.molecule_at(pos){
+            self.remove_molecule(m);
+        }
+    }
+
+    /// Create a new single-atom molecule.
+    fn spawn_atom(&mut self, pos: HexIndex, atom: Atom){
+        self.add_molecule(SimMolecule::new(Molecule{ atoms: HexMap::from([(pos, atom)]), bonds: HashSet::new() }));
+    }
+
+    /// Bond the atoms at two positions, merging their molecules, unless they're already bonded or either is missing.
+    /// Returns whether a bond was made.
+    fn add_bond(&mut self, a: HexIndex, b: HexIndex) -> bool{
+        let (Some(ma), Some(_)) = (self.molecule_at(a), self.molecule_at(b)) else { return false };
+        if self.molecules[ma].bond_at(a, b).is_some(){
+            return false;
+        }
+        self.set_bond(a, b, BondType::Normal);
+        true
+    }
+
+    /// Add a colour of triplex bond between two fire atoms, starting a partial triplex bond if they aren't bonded yet.
+    /// Returns whether the colour was added; atoms joined by a normal bond, or that already have this colour, are left alone.
+    fn add_triplex_colour(&mut self, a: HexIndex, b: HexIndex, colour: BondType) -> bool{
+        if self.atom(a) != Some(Atom::Fire) || self.atom(b) != Some(Atom::Fire){
+            return false;
+        }
+        let existing = self.molecule_at(a).and_then(|m| self.molecules[m].bond_at(a, b)).map(|bond| bond.ty);
+        let ty = match (existing, colour){
+            (None, _) => colour,
+            (Some(BondType::Triplex{ red, black, yellow }), BondType::Triplex{ red: r, black: k, yellow: y }) => BondType::Triplex{ red: red || r, black: black || k, yellow: yellow || y },
+            _ => return false
+        };
+        if existing == Some(ty){
+            return false;
+        }
+        self.set_bond(a, b, ty);
+        true
+    }
+
+    /// Set the bond between the atoms at two positions, replacing any bond already there and merging their molecules if needed.
+    /// Both atoms must exist.
+    fn set_bond(&mut self, a: HexIndex, b: HexIndex, ty: BondType){
+        let (Some(ma), Some(mb)) = (self.molecule_at(a), self.molecule_at(b)) else { return };
+        if ma == mb{
+            let molecule = &mut self.molecules[ma];
+            if let Some(old) = molecule.bond_at(a, b){
+                molecule.layout.bonds.remove(&old);
+            }
+            molecule.layout.bonds.insert(Bond{ start: a - molecule.pos, end: b - molecule.pos, ty });
+        }else{
+            let (first, second) = (ma.min(mb), ma.max(mb));
+            let second = self.remove_molecule(second);
+            let kept = &self.molecules[first];
+            let mut merged = kept.layout.translated(kept.pos);
+            let other = second.layout.translated(second.pos);
+            merged.atoms.extend(other.atoms);
+            merged.bonds.extend(other.bonds);
+            merged.bonds.insert(Bond{ start: a, end: b, ty });
+            let grabbed = kept.grabbed || second.grabbed;
+            self.replace_molecule(first, SimMolecule{ layout: merged, pos: HexIndex::default(), grabbed });
+        }
+    }
+
+    /// Remove the bond between the atoms at two positions, splitting their molecule if it's no longer connected.
+    /// Triplex bonds are removed whole, however many colours they have, rather than one colour at a time.
+    /// Returns whether there was a bond to remove.
+    fn remove_bond(&mut self, a: HexIndex, b: HexIndex) -> bool{
+        let Some(m) = self.molecule_at(a) else { return false };
+        let Some(bond) = self.molecules[m].bond_at(a, b) else { return false };
+        let mut molecule = self.remove_molecule(m);
+        molecule.layout.bonds.remove(&bond);
+        let pos = molecule.pos;
+        for component in molecule.layout.connected_components(){
+            self.add_molecule(SimMolecule{ layout: component, pos, grabbed: false });
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+
+    /// A board with just a glyph of the given type at the origin, and the board hexes it covers, in footprint order.
+    fn board(ty: PartType, rotation: i32) -> (Sim, Vec<HexIndex>){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle")
+            .glyph(ty, HexIndex::default(), rotation)
+            .arm(HexIndex{ q: 6, r: 6 }, 0, 1)
+            .instructions(0, "G")
+            .build().unwrap();
+        let sim = Sim::create(&puzzle, &solution).unwrap();
+        let part = &sim.parts[0];
+        let hexes = footprint(&part.ty).iter().map(|h| h.rotated(HexIndex::default(), part.rotation) + part.pos).collect();
+        (sim, hexes)
+    }
+
+    #[test]
+    fn bonders_bond_once(){
+        let (mut sim, hexes) = board(PartType::Bonding, 2);
+        sim.spawn_atom(hexes[0], Atom::Salt);
+        sim.spawn_atom(hexes[1], Atom::Water);
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.molecules.len(), 1);
+        assert!(sim.molecules[0].bond_at(hexes[0], hexes[1]).is_some());
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.glyph_activations[0], 1);
+    }
+
+    #[test]
+    fn calcification_only_salts_cardinals(){
+        let (mut sim, hexes) = board(PartType::Calcification, 0);
+        sim.spawn_atom(hexes[0], Atom::Fire);
+        sim.apply_glyphs(&mut ());
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.atom(hexes[0]), Some(Atom::Salt));
+        assert_eq!(sim.glyph_activations[0], 1);
+
+        let (mut sim, hexes) = board(PartType::Calcification, 0);
+        sim.spawn_atom(hexes[0], Atom::Lead);
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.atom(hexes[0]), Some(Atom::Lead));
+        assert_eq!(sim.glyph_activations[0], 0);
+    }
+
+    #[test]
+    fn purification_needs_an_empty_output(){
+        let (mut sim, hexes) = board(PartType::Purification, 3);
+        sim.spawn_atom(hexes[0], Atom::Lead);
+        sim.spawn_atom(hexes[1], Atom::Lead);
+        sim.spawn_atom(hexes[2], Atom::Salt);
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.glyph_activations[0], 0);
+
+        sim.remove_single(hexes[2]);
+        sim.apply_glyphs(&mut ());
+        assert_eq!((sim.atom(hexes[0]), sim.atom(hexes[1]), sim.atom(hexes[2])), (None, None, Some(Atom::Tin)));
+        assert_eq!(sim.glyph_activations[0], 1);
+    }
+
+    #[test]
+    fn dispersion_undoes_unification(){
+        let (mut sim, hexes) = board(PartType::Dispersion, 1);
+        sim.spawn_atom(hexes[0], Atom::Quintessence);
+        sim.apply_glyphs(&mut ());
+        let dispersed: Vec<Option<Atom>> = hexes.iter().map(|h| sim.atom(*h)).collect();
+        assert_eq!(dispersed, [None, Some(Atom::Air), Some(Atom::Water), Some(Atom::Fire), Some(Atom::Earth)]);
+
+        let (mut sim, unification) = board(PartType::Unification, 1);
+        for (hex, atom) in unification.iter().zip(&dispersed){
+            if let Some(atom) = atom{
+                sim.spawn_atom(*hex, *atom);
+            }
+        }
+        sim.apply_glyphs(&mut ());
+        assert_eq!(sim.atom(unification[0]), Some(Atom::Quintessence));
+        assert_eq!(sim.molecules.len(), 1);
+    }
+
+    fn triplex(red: bool, black: bool, yellow: bool) -> BondType{
+        BondType::Triplex{ red, black, yellow }
+    }
+
+    fn bond_type(sim: &Sim, a: HexIndex, b: HexIndex) -> Option<BondType>{
+        sim.molecule_at(a).and_then(|m| sim.molecules[m].bond_at(a, b)).map(|bond| bond.ty)
+    }
+
+    #[test]
+    fn triplex_bonders_add_one_colour_per_edge(){
+        let (mut sim, hexes) = board(PartType::TriplexBonding, 0);
+        sim.spawn_atom(hexes[0], Atom::Fire);
+        sim.spawn_atom(hexes[1], Atom::Fire);
+        sim.spawn_atom(hexes[2], Atom::Salt);
+        sim.apply_glyphs(&mut ());
+        assert_eq!(bond_type(&sim, hexes[0], hexes[1]), Some(triplex(true, false, false)));
+        assert_eq!(bond_type(&sim, hexes[0], hexes[2]), None);
+
+        // colours from other edges accumulate on a partial bond, but a normal bond is left alone
+        let (mut sim, hexes) = board(PartType::TriplexBonding, 0);
+        for hex in &hexes{
+            sim.spawn_atom(*hex, Atom::Fire);
+        }
+        sim.set_bond(hexes[0], hexes[2], triplex(false, false, true));
+        sim.set_bond(hexes[1], hexes[2], BondType::Normal);
+        sim.apply_glyphs(&mut ());
+        assert_eq!(bond_type(&sim, hexes[0], hexes[1]), Some(triplex(true, false, false)));
+        assert_eq!(bond_type(&sim, hexes[0], hexes[2]), Some(triplex(false, true, true)));
+        assert_eq!(bond_type(&sim, hexes[1], hexes[2]), Some(BondType::Normal));
+    }
+
+    #[test]
+    fn unbonders_remove_triplex_bonds_whole(){
+        let (mut sim, hexes) = board(PartType::Unbonding, 0);
+        sim.spawn_atom(hexes[0], Atom::Fire);
+        sim.spawn_atom(hexes[1], Atom::Fire);
+        sim.set_bond(hexes[0], hexes[1], triplex(true, true, true));
+        sim.apply_glyphs(&mut ());
+        assert_eq!(bond_type(&sim, hexes[0], hexes[1]), None);
+        assert_eq!(sim.molecules.len(), 2);
+    }
+}
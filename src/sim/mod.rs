@@ -1,26 +1,141 @@
-mod collision;
+mod area;
+pub mod collision;
+mod conduits;
+mod debug;
+mod glyphs;
+pub mod observer;
+pub mod state;
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 
-use crate::data::{Atom, Bond, HexIndex, HexRotation, Molecule, Part, PartType, Puzzle, Solution};
+use collision::{Collider, ColliderType, Movement};
+use observer::SimObserver;
+use crate::tape::{self, Tape};
+use crate::validate::{self, ValidationError};
+use crate::data::{Atom, Bond, BondType, HexIndex, HexMap, HexRotation, Instruction, Molecule, Part, PartCategory, PartType, Puzzle, Solution, POLYMER_REPETITIONS};
 
 // Data types
 
 #[derive(Clone, Debug)]
 pub struct Sim{
     pub parts: Vec<SimPart>,
-    pub molecules: Vec<SimMolecule>
+    /// Every molecule on the board. Call `reindex_atoms` after changing these directly, so that atoms can be found by position.
+    pub molecules: Vec<SimMolecule>,
+    /// The number of cycles that have been fully simulated.
+    pub cycle: i32,
+    /// The number of cycles before every arm's tape loops.
+    pub period: i32,
+    /// The number of products in the puzzle.
+    pub product_count: usize,
+    /// The number of molecules each output must consume before the solution completes.
+    pub required_outputs: u64,
+    /// The number of times each part has fired, indexed like the solution's parts. Always 0 for non-glyphs.
+    pub glyph_activations: Vec<u32>,
+    /// Atoms destroyed without being output, by element.
+    pub destroyed_atoms: HashMap<Atom, u32>,
+    /// Every molecule consumed by an output so far, in the order they were consumed.
+    pub deliveries: Vec<Delivery>,
+    /// Every hex that has been occupied by anything so far. The number of these is the area.
+    pub swept_hexes: HexMap<()>,
+    /// The area after every cycle on which it changed, in order.
+    pub area_changes: Vec<AreaChange>,
+    /// In production puzzles, the hexes just outside each chamber, which atoms and grippers collide with. Empty otherwise.
+    pub walls: Vec<HexIndex>,
+    /// Every molecule as it was just before arms last moved, and how it moved, for showing movement partway through a cycle.
+    pub last_movement: Vec<(SimMolecule, Motion)>,
+    /// The index in `molecules` of the molecule with an atom on each hex, so that atoms can be found without searching every molecule.
+    atom_index: HexMap<usize>,
+    /// The settings the simulation was created with.
+    options: SimOptions
 }
 
+/// Settings that change which solutions a `Sim` accepts.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SimOptions{
+    /// Whether parts may overlap, as the community's overlap category allows. Atoms still collide with each other as usual.
+    pub allow_overlap: bool,
+    /// Whether a molecule dropped where it can't go stops the simulation, rather than staying where it is as the game allows.
+    /// Such molecules fail with `SimError::WrongOutput` when left on an output that won't consume them, or `SimError::InvalidDrop` when dropped on a conduit that can't carry them.
+    pub strict_drops: bool
+}
+
+/// A product molecule consumed by an output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Delivery{
+    /// The cycle on which the molecule was consumed.
+    pub cycle: i32,
+    /// Index of the product that was consumed.
+    pub product: usize
+}
+
+/// The area of a solution after a cycle on which it changed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AreaChange{
+    /// The cycle after which the area was measured.
+    pub cycle: i32,
+    /// The number of hexes occupied by anything up to and including that cycle.
+    pub area: usize
+}
+
+/// Why a solution couldn't be simulated to completion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimError{
+    /// The solution can't be simulated at all, e.g. because it has parts that don't fit the puzzle.
+    InvalidSolution(&'static str),
+    /// An arm was given an instruction it can't perform during the given cycle, such as an arm that isn't a piston extending.
+    InvalidInstruction{ cycle: i32, part: usize, instruction: Instruction, reason: &'static str },
+    /// During the given cycle, the arms in `parts` held the same molecule, at the gripper at `pos`, and tried to move it in different ways.
+    ConflictingMotion{ cycle: i32, pos: HexIndex, parts: Vec<usize> },
+    /// Something collided with an atom during the given cycle, near the given hex.
+    /// `hexes` are the nearest hexes to the two things that collided, and `parts` are the arms that were moving them, in order.
+    Collision{ cycle: i32, pos: HexIndex, hexes: [HexIndex; 2], parts: Vec<usize> },
+    /// The gripper of an arm hit the wall of a chamber, at the given hex, during the given cycle.
+    ArmHitWall{ cycle: i32, part: usize, pos: HexIndex },
+    /// With `SimOptions::strict_drops`, a molecule was left on the output with index `output_index` in `Sim::parts` during the given cycle, which won't consume it.
+    /// `got` is the molecule as it lies on the board.
+    WrongOutput{ cycle: i32, output_index: usize, got: Molecule },
+    /// With `SimOptions::strict_drops`, a molecule with an atom at `pos` was dropped on a conduit that can't carry it during the given cycle.
+    InvalidDrop{ cycle: i32, pos: HexIndex, reason: &'static str },
+    /// The solution didn't complete within the cycle limit.
+    TimedOut{ cycle_limit: i32 }
+}
+
+impl std::fmt::Display for SimError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            SimError::InvalidSolution(reason) => write!(f, "invalid solution: {reason}"),
+            SimError::InvalidInstruction{ cycle, part, instruction, reason } => write!(f, "{reason}: part {part} can't perform {instruction:?} on cycle {cycle}"),
+            SimError::ConflictingMotion{ cycle, pos, .. } => write!(f, "molecule at ({}, {}) moved in two directions at once on cycle {cycle}", pos.q, pos.r),
+            SimError::Collision{ cycle, pos, .. } => write!(f, "collision at ({}, {}) on cycle {cycle}", pos.q, pos.r),
+            SimError::ArmHitWall{ cycle, part, pos } => write!(f, "arm {part} hit a chamber wall at ({}, {}) on cycle {cycle}", pos.q, pos.r),
+            SimError::WrongOutput{ cycle, output_index, .. } => write!(f, "molecule left on output {output_index} doesn't match its product on cycle {cycle}"),
+            SimError::InvalidDrop{ cycle, pos, reason } => write!(f, "{reason}: molecule dropped at ({}, {}) on cycle {cycle}", pos.q, pos.r),
+            SimError::TimedOut{ cycle_limit } => write!(f, "solution did not complete within {cycle_limit} cycles")
+        }
+    }
+}
+
+impl std::error::Error for SimError{}
+
+/// The number of intervals each movement is split into when checking for collisions.
+/// Atoms move at most about a third of their diameter between checks, even at the end of a length 3 arm.
+const COLLISION_STEPS: u32 = 16;
+
 // it's like Molecule but we copy less and offset more
 #[derive(Clone, Debug)]
 pub struct SimMolecule{
     pub layout: Molecule,
     pub pos: HexIndex,
+    /// Whether any gripper is holding an atom of this molecule.
     pub grabbed: bool
 }
 
 impl SimMolecule{
+    pub fn new(layout: Molecule) -> SimMolecule{
+        SimMolecule{ layout, pos: HexIndex::default(), grabbed: false }
+    }
+
     pub fn contains_pos(&self, pos: HexIndex) -> bool{
         self.layout.contains_pos(pos - self.pos)
     }
@@ -29,29 +144,99 @@ impl SimMolecule{
         self.layout.atoms[&(pos - self.pos)]
     }
 
+    /// The positions of every atom in this molecule, on the board.
+    pub fn positions(&self) -> impl Iterator<Item = HexIndex> + '_{
+        self.layout.atoms.keys().map(|p| *p + self.pos)
+    }
+
+    /// The bond between two atoms of this molecule, given their positions on the board, if they're bonded.
     pub fn bond_at(&self, pos_a: HexIndex, pos_b: HexIndex) -> Option<Bond>{
-        todo!()
+        let (a, b) = (pos_a - self.pos, pos_b - self.pos);
+        self.layout.bonds.iter().find(|bond| (bond.start == a && bond.end == b) || (bond.start == b && bond.end == a)).cloned()
     }
 
-    // also wrong, should accept other molecule's position and rotation
+    /// Whether this molecule has exactly the atoms and bonds of `m`, where `m` is given in board positions. See `mismatches` for how it differs.
     pub fn is(&self, m: &Molecule) -> bool{
-        // if two molecules have the same number of atoms, bond layout, & the first contains all the atoms of the latter, they're the same
-        if self.layout.atoms.len() != m.atoms.len(){
+        // if two molecules have the same number of atoms & bonds, and the first contains all the atoms and bonds of the latter, they're the same
+        if self.layout.atoms.len() != m.atoms.len() || self.layout.bonds.len() != m.bonds.len(){
             return false
         }
 
-        if self.layout.bonds != m.bonds{
-            return false;
-        }
-
         for atom in &m.atoms{
             if !self.contains_pos(*atom.0) || self.atom_at(*atom.0) != *atom.1{
                 return false
             }
         }
 
-        return true;
+        m.bonds.iter().all(|bond| self.bond_at(bond.start, bond.end).is_some_and(|b| b.ty == bond.ty))
+    }
+
+    /// Every way this molecule differs from `m`, where `m` is given in board positions, or nothing if `is` would accept it.
+    /// Atoms and bonds of this molecule away from `m`'s atoms are reported as extra.
+    pub fn mismatches(&self, m: &Molecule) -> Vec<OutputMismatch>{
+        let mut mismatches = Vec::new();
+        for (&pos, &expected) in &m.atoms{
+            if !self.contains_pos(pos){
+                mismatches.push(OutputMismatch::MissingAtom{ pos, expected });
+            }else if self.atom_at(pos) != expected{
+                mismatches.push(OutputMismatch::WrongAtom{ pos, expected, found: self.atom_at(pos) });
+            }
+        }
+        mismatches.extend(self.positions().filter(|pos| !m.atoms.contains_key(pos)).map(|pos| OutputMismatch::ExtraAtom{ pos, found: self.atom_at(pos) }));
+        for bond in &m.bonds{
+            match self.bond_at(bond.start, bond.end){
+                None => mismatches.push(OutputMismatch::MissingBond{ start: bond.start, end: bond.end, expected: bond.ty }),
+                Some(found) if found.ty != bond.ty => mismatches.push(OutputMismatch::WrongBond{ start: bond.start, end: bond.end, expected: bond.ty, found: found.ty }),
+                Some(_) => {}
+            }
+        }
+        for bond in &self.layout.bonds{
+            let (start, end) = (bond.start + self.pos, bond.end + self.pos);
+            if !m.bonds.iter().any(|b| (b.start == start && b.end == end) || (b.start == end && b.end == start)){
+                mismatches.push(OutputMismatch::ExtraBond{ start, end, found: bond.ty });
+            }
+        }
+        mismatches
     }
+
+    /// Move this molecule.
+    fn apply(&mut self, motion: Motion){
+        match motion{
+            Motion::Stay => {}
+            Motion::Translate(by) => self.pos += by,
+            Motion::Rotate{ around, by } => self.layout = self.layout.rotated(around - self.pos, by)
+        }
+    }
+}
+
+/// A way a molecule on an output differs from the product it expects. Positions are on the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OutputMismatch{
+    /// The product has an atom here, but the molecule doesn't.
+    MissingAtom{ pos: HexIndex, expected: Atom },
+    /// The molecule has a different atom here than the product.
+    WrongAtom{ pos: HexIndex, expected: Atom, found: Atom },
+    /// The molecule has an atom here, outside of the product.
+    ExtraAtom{ pos: HexIndex, found: Atom },
+    /// The product has a bond here, but the molecule doesn't.
+    MissingBond{ start: HexIndex, end: HexIndex, expected: BondType },
+    /// The molecule has a different type of bond here than the product.
+    WrongBond{ start: HexIndex, end: HexIndex, expected: BondType, found: BondType },
+    /// The molecule has a bond here that the product doesn't.
+    ExtraBond{ start: HexIndex, end: HexIndex, found: BondType }
+}
+
+/// A molecule on an output that the output won't consume, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedOutput{
+    /// The index of the output in `Sim::parts`.
+    pub part: usize,
+    /// The index of the molecule in `Sim::molecules`.
+    pub molecule: usize,
+    /// Whether the molecule is being held, which stops it being consumed even if it matches.
+    pub held: bool,
+    /// How the molecule differs from the product, empty if only being held stops it.
+    pub mismatches: Vec<OutputMismatch>
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -60,28 +245,449 @@ pub struct AtomLookupResult<'a>{
     pub molecule: &'a SimMolecule
 }
 
+/// A rigid movement of a molecule or gripper over one cycle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Motion{
+    Stay,
+    Translate(HexIndex),
+    Rotate{ around: HexIndex, by: HexRotation }
+}
+
+impl Motion{
+    /// The path taken by something at a position that moves with this motion.
+    pub fn movement_of(self, pos: HexIndex) -> Movement{
+        match self{
+            Motion::Stay => Movement::Stay{ at: pos },
+            Motion::Translate(by) => Movement::Translate{ start: pos, end: pos + by },
+            Motion::Rotate{ around, by } => Movement::Rotate{ start: pos, around, rotation: by }
+        }
+    }
+}
+
 impl Sim{
+    /// Set up a solution to be simulated, failing if it can't be legally simulated, such as if its parts overlap.
     pub fn create(puzzle: &Puzzle, solution: &Solution) -> Result<Sim, &'static str>{
+        Sim::create_with_options(puzzle, solution, SimOptions::default())
+    }
+
+    /// Set up a solution to be simulated, with settings that allow some illegal solutions.
+    pub fn create_with_options(puzzle: &Puzzle, solution: &Solution, options: SimOptions) -> Result<Sim, &'static str>{
+        if puzzle.reagents.iter().chain(&puzzle.products).any(|m| m.atoms.values().any(|a| matches!(a, Atom::Unknown(_)))){
+            return Err("puzzle contains atoms of unknown types");
+        }
         let sol_clean = puzzle.clean_solution(solution)?;
-        Ok(Sim{
-            parts: sol_clean.parts.iter().map(|p| SimPart::from_solution_part(p, puzzle, solution)).collect::<Result<Vec<_>, _>>()?,
-            molecules: Vec::new()
-        })
+        if let Some(error) = validate::find_chamber_errors(&sol_clean, puzzle).first(){
+            return Err(match error{
+                ValidationError::SharedChamber{ .. } => "solution places an input and output in the same chamber of an isolated puzzle",
+                _ => "solution contains a part outside of every chamber"
+            });
+        }
+        if !options.allow_overlap && !validate::find_overlaps(&sol_clean, puzzle).is_empty(){
+            return Err("solution contains overlapping parts");
+        }
+        let mut parts = sol_clean.parts.iter().map(|p| SimPart::from_solution_part(p, puzzle, solution)).collect::<Result<Vec<_>, _>>()?;
+
+        // lay every arm's tape on a shared timeline, starting from the first instruction of any arm
+        let tapes = sol_clean.parts.iter().map(|p| if p.ty.category() == PartCategory::Arm { Tape::from_part(p).map(Some) } else { Ok(None) }).collect::<Result<Vec<_>, _>>()?;
+        let (start, period) = tape::timeline(tapes.iter().flatten())?;
+        for (sim_part, tape) in parts.iter_mut().zip(&tapes){
+            if let (SimPartType::Arm(arm), Some(tape)) = (&mut sim_part.ty, tape){
+                arm.tape = (start..start + period).map(|idx| tape.at(idx)).collect();
+            }
+        }
+
+        // arms on a track start at the point of the track they were placed on
+        let tracks: Vec<Vec<HexIndex>> = parts.iter().filter_map(|p| match &p.ty { SimPartType::Track(path) => Some(path.clone()), _ => None }).collect();
+        for part in &mut parts{
+            let pos = part.pos;
+            if let SimPartType::Arm(arm) = &mut part.ty{
+                arm.track = tracks.iter().find_map(|path| path.iter().position(|h| *h == pos).map(|i| (path.clone(), i)));
+            }
+        }
+
+        let mut sim = Sim{
+            parts,
+            molecules: Vec::new(),
+            cycle: 0,
+            period,
+            product_count: puzzle.products.len(),
+            required_outputs: 6 * puzzle.product_multiplier.max(1) as u64,
+            glyph_activations: vec![0; sol_clean.parts.len()],
+            destroyed_atoms: HashMap::new(),
+            deliveries: Vec::new(),
+            swept_hexes: HexMap::new(),
+            area_changes: Vec::new(),
+            last_movement: Vec::new(),
+            atom_index: HexMap::new(),
+            options,
+            walls: puzzle.production_info.as_ref().map_or(Vec::new(), |info| info.wall_hexes().into_iter().collect())
+        };
+        sim.mark_area();
+        Ok(sim)
     }
 
     // need a way to remove or modify the molecule (or schedule those)
     pub fn lookup_atom<T>(&self, pos: HexIndex, f: impl for<'a> FnOnce(AtomLookupResult<'a>) -> T) -> Option<T>{
-        for molecule in &self.molecules{
-            if molecule.contains_pos(pos){
-                return Some(f(AtomLookupResult{
-                    atom_ty: molecule.atom_at(pos),
-                    molecule: &molecule
-                }))
+        let molecule = &self.molecules[self.molecule_at(pos)?];
+        Some(f(AtomLookupResult{
+            atom_ty: molecule.atom_at(pos),
+            molecule
+        }))
+    }
+
+    /// Index of the molecule with an atom at a position, if there is one.
+    pub fn molecule_at(&self, pos: HexIndex) -> Option<usize>{
+        self.atom_index.get(&pos).copied()
+    }
+
+    /// Rebuild the index used to find atoms by position, after changing `molecules` directly.
+    pub fn reindex_atoms(&mut self){
+        self.atom_index.clear();
+        for m in 0..self.molecules.len(){
+            self.index_molecule(m);
+        }
+    }
+
+    fn index_molecule(&mut self, m: usize){
+        for pos in self.molecules[m].positions(){
+            self.atom_index.insert(pos, m);
+        }
+    }
+
+    fn unindex_molecule(&mut self, m: usize){
+        for pos in self.molecules[m].positions(){
+            self.atom_index.remove(&pos);
+        }
+    }
+
+    /// Add a molecule to the board, keeping the atom index up to date.
+    fn add_molecule(&mut self, molecule: SimMolecule){
+        self.molecules.push(molecule);
+        self.index_molecule(self.molecules.len() - 1);
+    }
+
+    /// Remove a molecule from the board, keeping the atom index up to date. Later molecules move down an index, as with `Vec::remove`.
+    fn remove_molecule(&mut self, m: usize) -> SimMolecule{
+        self.unindex_molecule(m);
+        for index in self.atom_index.values_mut(){
+            if *index > m{
+                *index -= 1;
             }
         }
+        self.molecules.remove(m)
+    }
+
+    /// Replace a molecule on the board, keeping the atom index up to date.
+    fn replace_molecule(&mut self, m: usize, molecule: SimMolecule){
+        self.unindex_molecule(m);
+        self.molecules[m] = molecule;
+        self.index_molecule(m);
+    }
+
+    /// Simulate a single cycle.
+    /// Inputs spawn and arms grab and drop at the start of the cycle, then arms move, with glyphs and outputs acting before and after movement.
+    pub fn step(&mut self) -> Result<(), SimError>{
+        self.step_observed(&mut ())
+    }
+
+    /// Simulate a single cycle like `step`, telling an observer about everything that happens during it.
+    pub fn step_observed(&mut self, observer: &mut dyn SimObserver) -> Result<(), SimError>{
+        observer.cycle_started(self);
+        self.spawn_inputs(observer);
+        let instructions = self.current_instructions();
+        self.grab_and_drop(&instructions)?;
+        self.apply_glyphs(observer);
+        self.consume_outputs(observer);
+        self.check_outputs()?;
+        self.mark_area();
+
+        self.move_arms(&instructions, observer)?;
+        self.apply_glyphs(observer);
+        self.consume_outputs(observer);
+        self.mark_area();
 
-        None
+        self.cycle += 1;
+        self.record_area();
+        observer.cycle_ended(self);
+        Ok(())
     }
+
+    /// The number of hexes that have been occupied by anything so far.
+    pub fn area(&self) -> usize{
+        self.swept_hexes.len()
+    }
+
+    /// Record the current area if it's changed since it was last recorded.
+    pub fn record_area(&mut self){
+        let area = self.area();
+        if self.area_changes.last().is_none_or(|last| last.area != area){
+            self.area_changes.push(AreaChange{ cycle: self.cycle, area });
+        }
+    }
+
+    /// Whether every product has an output, and every output has consumed the required number of molecules.
+    pub fn is_complete(&self) -> bool{
+        let mut has_output = vec![false; self.product_count];
+        for part in &self.parts{
+            if let SimPartType::Output(_, count) | SimPartType::PolymerOutput(_, count) = part.ty{
+                has_output[part.index] = true;
+                if count < self.required_outputs{
+                    return false;
+                }
+            }
+        }
+        !has_output.is_empty() && has_output.into_iter().all(|h| h)
+    }
+
+    /// The instruction each part executes this cycle, indexed like `parts`. Always `Blank` for non-arms.
+    pub fn current_instructions(&self) -> Vec<Instruction>{
+        self.instructions_on(self.cycle)
+    }
+
+    /// The instruction each part executes on a cycle, counting from 0, with tapes looping every period. Indexed like `parts`, and always `Blank` for non-arms.
+    pub fn instructions_on(&self, cycle: i32) -> Vec<Instruction>{
+        self.parts.iter().map(|p| match &p.ty{
+            SimPartType::Arm(arm) if self.period > 0 => arm.tape[cycle.rem_euclid(self.period) as usize],
+            _ => Instruction::Blank
+        }).collect()
+    }
+
+    fn spawn_inputs(&mut self, observer: &mut dyn SimObserver){
+        for i in 0..self.parts.len(){
+            if let SimPartType::Input(reagent) = &self.parts[i].ty{
+                let placed = reagent.rotated(HexIndex::default(), self.parts[i].rotation).translated(self.parts[i].pos);
+                if placed.atoms.keys().all(|pos| self.molecule_at(*pos).is_none()){
+                    self.add_molecule(SimMolecule::new(placed));
+                    observer.input_spawned(i, self.molecules.len() - 1);
+                }
+            }
+        }
+    }
+
+    fn grab_and_drop(&mut self, instructions: &[Instruction]) -> Result<(), SimError>{
+        let was_grabbed: Vec<bool> = self.molecules.iter().map(|m| m.grabbed).collect();
+        for (part, instr) in self.parts.iter_mut().zip(instructions){
+            let SimPartType::Arm(arm) = &mut part.ty else { continue };
+            match instr{
+                Instruction::Grab if !arm.grabbing => {
+                    arm.grabbing = true;
+                    for (i, gripper) in arm.gripper_positions(part.pos, part.rotation).into_iter().enumerate(){
+                        arm.holding[i] = self.atom_index.contains_key(&gripper);
+                    }
+                }
+                Instruction::Drop => {
+                    arm.grabbing = false;
+                    arm.holding.iter_mut().for_each(|h| *h = false);
+                }
+                _ => {}
+            }
+        }
+        self.update_grabbed();
+        let dropped: Vec<usize> = (0..self.molecules.len()).filter(|m| was_grabbed[*m] && !self.molecules[*m].grabbed).collect();
+        if self.options.strict_drops{
+            for &m in &dropped{
+                if let Some(reason) = self.conduit_drop_error(m){
+                    let pos = self.molecules[m].positions().next().unwrap_or_default();
+                    return Err(SimError::InvalidDrop{ cycle: self.cycle, pos, reason });
+                }
+            }
+        }
+        self.transfer_through_conduits(&dropped);
+        Ok(())
+    }
+
+    /// Recompute which molecules are held by any gripper.
+    fn update_grabbed(&mut self){
+        let held = self.held_positions();
+        for molecule in &mut self.molecules{
+            molecule.grabbed = held.iter().any(|pos| molecule.contains_pos(*pos));
+        }
+    }
+
+    /// The positions of every gripper that's holding an atom.
+    fn held_positions(&self) -> Vec<HexIndex>{
+        let mut held = Vec::new();
+        for part in &self.parts{
+            if let SimPartType::Arm(arm) = &part.ty{
+                for (i, gripper) in arm.gripper_positions(part.pos, part.rotation).into_iter().enumerate(){
+                    if arm.holding[i]{
+                        held.push(gripper);
+                    }
+                }
+            }
+        }
+        held
+    }
+
+    fn move_arms(&mut self, instructions: &[Instruction], observer: &mut dyn SimObserver) -> Result<(), SimError>{
+        let cycle = self.cycle;
+        // every held molecule must be moved the same way by every gripper holding it, including grippers that stay still
+        let mut motions: Vec<Option<Motion>> = vec![None; self.molecules.len()];
+        let mut holders: Vec<Vec<usize>> = vec![Vec::new(); self.molecules.len()];
+        // what each collider belongs to, for reporting collisions
+        let mut colliders = Vec::new();
+        let mut owners = Vec::new();
+        for (index, (part, instr)) in self.parts.iter_mut().zip(instructions).enumerate(){
+            let SimPartType::Arm(arm) = &mut part.ty else { continue };
+            let grippers = arm.gripper_positions(part.pos, part.rotation);
+            if let Instruction::RotateClockwise | Instruction::RotateAnticlockwise = instr{
+                let by = if *instr == Instruction::RotateClockwise { HexRotation::R300 } else { HexRotation::R60 };
+                for gripper in &grippers{
+                    for hex in area::arm_hexes(part.pos, *gripper, arm.length){
+                        self.swept_hexes.extend(area::swept_hexes(hex, part.pos, by));
+                    }
+                }
+            }
+            let start = part.pos;
+            let base_motion = arm.perform(*instr, &mut part.pos, &mut part.rotation)
+                .map_err(|reason| SimError::InvalidInstruction{ cycle, part: index, instruction: *instr, reason })?;
+            observer.arm_moved(index, *instr, base_motion);
+            colliders.push(Collider{ ty: ColliderType::ArmBase, movement: Movement::Translate{ start, end: part.pos } });
+            owners.push(ColliderOwner::Arm(index));
+            // grippers only collide with chamber walls, so they're left out of puzzles without any
+            if !self.walls.is_empty(){
+                colliders.extend(grippers.iter().map(|g| Collider{ ty: ColliderType::ArmGripper, movement: base_motion.movement_of(*g) }));
+                owners.extend(grippers.iter().map(|_| ColliderOwner::Arm(index)));
+            }
+            for (i, gripper) in grippers.into_iter().enumerate(){
+                if !arm.holding[i]{
+                    continue;
+                }
+                let motion = match *instr{
+                    Instruction::PivotClockwise => Motion::Rotate{ around: gripper, by: HexRotation::R300 },
+                    Instruction::PivotAnticlockwise => Motion::Rotate{ around: gripper, by: HexRotation::R60 },
+                    _ => base_motion
+                };
+                if let Some(&molecule) = self.atom_index.get(&gripper){
+                    holders[molecule].push(index);
+                    match motions[molecule]{
+                        Some(existing) if existing != motion => return Err(SimError::ConflictingMotion{ cycle, pos: gripper, parts: holders.swap_remove(molecule) }),
+                        _ => motions[molecule] = Some(motion)
+                    }
+                }
+            }
+        }
+        self.last_movement.clear();
+        // take moving molecules out of the index before any of them move, so they can move into each other's old hexes
+        let moving: Vec<usize> = (0..motions.len()).filter(|m| motions[*m].is_some_and(|motion| motion != Motion::Stay)).collect();
+        for &m in &moving{
+            self.unindex_molecule(m);
+        }
+        for (m, (molecule, motion)) in self.molecules.iter_mut().zip(motions).enumerate(){
+            let motion = motion.unwrap_or(Motion::Stay);
+            self.last_movement.push((molecule.clone(), motion));
+            for pos in molecule.positions(){
+                if let Motion::Rotate{ around, by } = motion{
+                    self.swept_hexes.extend(area::swept_hexes(pos, around, by));
+                }
+                colliders.push(Collider{ ty: ColliderType::Atom, movement: motion.movement_of(pos) });
+                owners.push(ColliderOwner::Molecule(m));
+            }
+            molecule.apply(motion);
+        }
+        for &m in &moving{
+            self.index_molecule(m);
+        }
+        colliders.extend(self.walls.iter().map(|at| Collider{ ty: ColliderType::ChamberWall, movement: Movement::Stay{ at: *at } }));
+        owners.extend(self.walls.iter().map(|_| ColliderOwner::Wall));
+
+        if let Some(contact) = collision::find_contact(&colliders, COLLISION_STEPS){
+            let hexes = [contact.first_pos.to_hex_index(), contact.second_pos.to_hex_index()];
+            let gripper = |i: usize| colliders[i].ty == ColliderType::ArmGripper;
+            return Err(match (owners[contact.first], owners[contact.second]){
+                (ColliderOwner::Arm(part), ColliderOwner::Wall) if gripper(contact.first) => SimError::ArmHitWall{ cycle, part, pos: hexes[1] },
+                (ColliderOwner::Wall, ColliderOwner::Arm(part)) if gripper(contact.second) => SimError::ArmHitWall{ cycle, part, pos: hexes[0] },
+                (first, second) => {
+                    let mut parts = Vec::new();
+                    for owner in [first, second]{
+                        match owner{
+                            ColliderOwner::Arm(part) => parts.push(part),
+                            ColliderOwner::Molecule(m) => parts.extend(&holders[m]),
+                            ColliderOwner::Wall => {}
+                        }
+                    }
+                    parts.sort();
+                    parts.dedup();
+                    SimError::Collision{ cycle, pos: hexes[0], hexes, parts }
+                }
+            });
+        }
+        self.update_grabbed();
+        Ok(())
+    }
+
+    /// The product the output at `parts[part]` accepts, in board positions, or `None` if that part isn't an output.
+    fn placed_product(&self, part: usize) -> Option<Molecule>{
+        let part = &self.parts[part];
+        let (SimPartType::Output(product, _) | SimPartType::PolymerOutput(product, _)) = &part.ty else { return None };
+        Some(product.rotated(HexIndex::default(), part.rotation).translated(part.pos))
+    }
+
+    /// Every molecule with an atom on an output's footprint that the output won't consume as the board is now.
+    /// An output only consumes a molecule that isn't held and covers its footprint exactly, with the same atoms and bonds in the output's orientation.
+    pub fn rejected_outputs(&self) -> Vec<RejectedOutput>{
+        let mut rejected = Vec::new();
+        for part in 0..self.parts.len(){
+            let Some(placed) = self.placed_product(part) else { continue };
+            let mut molecules: Vec<usize> = placed.atoms.keys().filter_map(|pos| self.molecule_at(*pos)).collect();
+            molecules.sort_unstable();
+            molecules.dedup();
+            for molecule in molecules{
+                let held = self.molecules[molecule].grabbed;
+                let mismatches = self.molecules[molecule].mismatches(&placed);
+                if held || !mismatches.is_empty(){
+                    rejected.push(RejectedOutput{ part, molecule, held, mismatches });
+                }
+            }
+        }
+        rejected
+    }
+
+    /// With `SimOptions::strict_drops`, fail if a molecule that isn't held is left on an output that won't consume it.
+    fn check_outputs(&self) -> Result<(), SimError>{
+        if !self.options.strict_drops{
+            return Ok(());
+        }
+        match self.rejected_outputs().into_iter().find(|r| !r.held){
+            Some(rejected) => {
+                let molecule = &self.molecules[rejected.molecule];
+                Err(SimError::WrongOutput{ cycle: self.cycle, output_index: rejected.part, got: molecule.layout.translated(molecule.pos) })
+            }
+            None => Ok(())
+        }
+    }
+
+    /// Remove every molecule that matches an output and isn't held.
+    /// A polymer output takes a chain of `POLYMER_REPETITIONS` units at once, which satisfies every molecule it requires.
+    fn consume_outputs(&mut self, observer: &mut dyn SimObserver){
+        for i in 0..self.parts.len(){
+            let Some(placed) = self.placed_product(i) else { continue };
+            let Some(&first) = placed.atoms.keys().next() else { continue };
+            let Some(molecule) = self.molecule_at(first) else { continue };
+            if self.molecules[molecule].grabbed || !self.molecules[molecule].is(&placed){
+                continue;
+            }
+            let index = self.parts[i].index;
+            let consumed = self.remove_molecule(molecule);
+            match &mut self.parts[i].ty{
+                SimPartType::Output(_, count) => *count += 1,
+                SimPartType::PolymerOutput(_, count) => *count = self.required_outputs,
+                _ => {}
+            }
+            self.deliveries.push(Delivery{ cycle: self.cycle + 1, product: index });
+            observer.output_consumed(i, &consumed);
+        }
+    }
+}
+
+/// What a collider checked during a cycle belongs to.
+#[derive(Copy, Clone, Debug)]
+enum ColliderOwner{
+    /// The base or a gripper of the arm with this index in `parts`.
+    Arm(usize),
+    /// An atom of the molecule with this index in `molecules`.
+    Molecule(usize),
+    Wall
 }
 
 // Parts
@@ -90,55 +696,144 @@ impl Sim{
 pub struct SimPart{
     pub pos: HexIndex,
     pub rotation: HexRotation,
+    /// If this is an input or output, index of which reagent or product this is for. If this is a conduit, index of which conduit this is an end of.
+    pub index: usize,
     pub ty: SimPartType
 }
 
 #[derive(Clone, Debug)]
 pub enum SimPartType{
+    /// An input, with polymer reagents already extended to the chain it spawns.
     Input(Molecule),
     Output(Molecule, u64),
-    Arms,
-    Track,
+    /// A polymer output, with its product already extended to the chain it accepts.
+    PolymerOutput(Molecule, u64),
+    Arm(SimArm),
+    /// A track, as the hexes it covers on the board in order, ending with the first again if it loops.
+    Track(Vec<HexIndex>),
     Bonding, MultiBonding, Unbonding, Calcification,
     Animismus,
     Projection, Purification,
-    Conduit,
+    Duplication, Disposal, Equilibrium,
+    Unification, Dispersion, TriplexBonding,
+    /// An end of a conduit, as the hexes it covers on the board.
+    Conduit(Vec<HexIndex>),
+}
+
+/// The state of an arm or piston.
+#[derive(Clone, Debug)]
+pub struct SimArm{
+    pub ty: PartType,
+    pub length: i32,
+    /// The instruction this arm runs on each cycle of a period.
+    pub tape: Vec<Instruction>,
+    /// Whether the arm's grippers are closed.
+    pub grabbing: bool,
+    /// Whether each gripper is holding an atom.
+    pub holding: Vec<bool>,
+    /// If this arm is on a track, the hexes of that track in order, and the index of the one the arm is on.
+    pub track: Option<(Vec<HexIndex>, usize)>
+}
+
+impl SimArm{
+    pub fn new(ty: PartType, length: i32) -> SimArm{
+        let grippers = Self::gripper_rotations(&ty).len();
+        SimArm{ ty, length, tape: Vec::new(), grabbing: false, holding: vec![false; grippers], track: None }
+    }
+
+    /// The directions of each gripper relative to the arm's rotation.
+    /// The grippers of multi-gripper arms grab and drop together, and each holds, moves, and sweeps area like the gripper of a single arm.
+    pub fn gripper_rotations(ty: &PartType) -> &'static [u8]{
+        match ty{
+            PartType::BiArm => &[0, 3],
+            PartType::TriArm => &[0, 2, 4],
+            PartType::HexArm => &[0, 1, 2, 3, 4, 5],
+            _ => &[0]
+        }
+    }
+
+    /// The position of each gripper, given the arm's base position and rotation.
+    pub fn gripper_positions(&self, pos: HexIndex, rotation: HexRotation) -> Vec<HexIndex>{
+        Self::gripper_rotations(&self.ty).iter()
+            .map(|turns| HexIndex{ q: self.length, r: 0 }.rotated(HexIndex::default(), rotation + HexRotation::from_unsigned(*turns)) + pos)
+            .collect()
+    }
+
+    /// Update this arm's state for a movement instruction, returning how held molecules move.
+    /// Pivots are reported as `Stay`, since they move around each gripper rather than the base.
+    fn perform(&mut self, instr: Instruction, pos: &mut HexIndex, rotation: &mut HexRotation) -> Result<Motion, &'static str>{
+        Ok(match instr{
+            Instruction::RotateClockwise => {
+                *rotation += HexRotation::R300;
+                Motion::Rotate{ around: *pos, by: HexRotation::R300 }
+            }
+            Instruction::RotateAnticlockwise => {
+                *rotation += HexRotation::R60;
+                Motion::Rotate{ around: *pos, by: HexRotation::R60 }
+            }
+            Instruction::Extend | Instruction::Retract => {
+                if self.ty != PartType::PistonArm{
+                    return Err("only pistons can extend or retract");
+                }
+                let next = self.length + if instr == Instruction::Extend { 1 } else { -1 };
+                if !(1..=3).contains(&next){
+                    Motion::Stay
+                }else{
+                    self.length = next;
+                    let dir = HexIndex{ q: if instr == Instruction::Extend { 1 } else { -1 }, r: 0 }.rotated(HexIndex::default(), *rotation);
+                    Motion::Translate(dir)
+                }
+            }
+            Instruction::Advance | Instruction::Retreat => {
+                let Some((path, idx)) = &mut self.track else { return Err("arm moved along a track without being on one") };
+                // looped tracks list their first hex again at the end, and arms wrap around through it; open tracks stop at their ends
+                let looped = path.len() > 3 && path.first() == path.last();
+                let len = if looped { path.len() - 1 } else { path.len() };
+                let next = match (instr == Instruction::Advance, looped){
+                    (true, true) => Some((*idx + 1) % len),
+                    (false, true) => Some((*idx + len - 1) % len),
+                    (true, false) => Some(*idx + 1).filter(|n| *n < len),
+                    (false, false) => idx.checked_sub(1)
+                };
+                match next{
+                    Some(next) => {
+                        let by = path[next] - path[*idx];
+                        *idx = next;
+                        *pos += by;
+                        Motion::Translate(by)
+                    }
+                    None => Motion::Stay
+                }
+            }
+            _ => Motion::Stay
+        })
+    }
 }
 
 impl SimPart{
+    /// The hexes this part covers on the board if it's a glyph, or nothing otherwise.
+    pub fn glyph_hexes(&self) -> Vec<HexIndex>{
+        glyphs::footprint(&self.ty).iter().map(|h| h.rotated(HexIndex::default(), self.rotation) + self.pos).collect()
+    }
+
     pub fn from_solution_part(part: &Part, puzzle: &Puzzle, solution: &Solution) -> Result<SimPart, &'static str>{
         Ok(SimPart{
             pos: part.pos,
             rotation: HexRotation::from_signed(part.rotation),
+            index: if part.ty == PartType::Conduit { part.conduit_index } else { part.index }.max(0) as usize,
             ty: SimPartType::from_solution_part(part, puzzle, solution)?
         })
     }
-
-    pub fn tick(&mut self, sim: &mut Sim){
-        match &mut self.ty{
-            SimPartType::Input(m) => {}
-            SimPartType::Output(m, outputs) => {
-                // we need exactly 1 molecule that touches the output everywhere
-                // so we can just lookup for an arbitrary position (here the centre)
-                sim.lookup_atom(self.pos, |result| {
-                    if result.molecule.is(m){
-                        // wrong
-                        todo!()
-                    }
-                });
-            }
-            _ => panic!("a")
-        }
-    }
 }
 
 impl SimPartType{
-    pub fn from_solution_part(part: &Part, puzzle: &Puzzle, solution: &Solution) -> Result<SimPartType, &'static str>{
+    pub fn from_solution_part(part: &Part, puzzle: &Puzzle, _solution: &Solution) -> Result<SimPartType, &'static str>{
         Ok(match part.ty{
-            PartType::Input => SimPartType::Input(puzzle.reagents[part.index as usize].clone()),
+            PartType::Input => SimPartType::Input(puzzle.reagents[part.index as usize].extended(POLYMER_REPETITIONS)),
             PartType::Output => SimPartType::Output(puzzle.products[part.index as usize].clone(), 0),
-            PartType::Arm | PartType::BiArm | PartType::TriArm | PartType::HexArm | PartType::PistonArm => SimPartType::Arms,
-            PartType::Track => SimPartType::Track,
+            PartType::PolymerOutput => SimPartType::PolymerOutput(puzzle.products[part.index as usize].extended(POLYMER_REPETITIONS), 0),
+            PartType::Arm | PartType::BiArm | PartType::TriArm | PartType::HexArm | PartType::PistonArm => SimPartType::Arm(SimArm::new(part.ty.clone(), part.arm_length)),
+            PartType::Track => SimPartType::Track(part.track_hexes.iter().map(|h| *h + part.pos).collect()),
             PartType::Bonding => SimPartType::Bonding,
             PartType::MultiBonding => SimPartType::MultiBonding,
             PartType::Unbonding => SimPartType::Unbonding,
@@ -146,8 +841,257 @@ impl SimPartType{
             PartType::Animismus => SimPartType::Animismus,
             PartType::Projection => SimPartType::Projection,
             PartType::Purification => SimPartType::Purification,
-            PartType::Conduit => SimPartType::Conduit,
-            _ => { println!("{:?}", part.ty); return Err("unknown part type"); }
+            PartType::Duplication => SimPartType::Duplication,
+            PartType::Disposal => SimPartType::Disposal,
+            PartType::Equilibrium => SimPartType::Equilibrium,
+            PartType::Unification => SimPartType::Unification,
+            PartType::Dispersion => SimPartType::Dispersion,
+            PartType::TriplexBonding => SimPartType::TriplexBonding,
+            PartType::Conduit => SimPartType::Conduit(part.conduit_hexes.iter().map(|h| *h + part.pos).collect()),
+            _ => return Err("unsupported part type")
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::Metrics;
+    use crate::verify::verify;
+
+    const STRICT: SimOptions = SimOptions{ allow_overlap: false, strict_drops: true };
+
+    /// A salt input at (1, 0), and an arm that carries each salt halfway round to the output at (-1, 0) for a product of the given atom.
+    fn carry_salt(product: Atom) -> (Puzzle, Solution){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let product = Molecule::builder().atom(0, 0, product).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt).product(product).build();
+        let solution = SolutionBuilder::new("solution", "puzzle")
+            .input(0, HexIndex{ q: 1, r: 0 }, 0)
+            .output(0, HexIndex{ q: -1, r: 0 }, 0)
+            .arm(HexIndex::default(), 0, 1)
+            .instructions(0, "GRRRgX")
+            .build().unwrap();
+        (puzzle, solution)
+    }
+
+    fn run(sim: &mut Sim, cycles: i32) -> Result<(), SimError>{
+        (0..cycles).try_for_each(|_| sim.step())
+    }
+
+    fn atoms(sim: &Sim) -> Vec<(HexIndex, bool)>{
+        let mut atoms: Vec<(HexIndex, bool)> = sim.molecules.iter().flat_map(|m| m.positions().map(|pos| (pos, m.grabbed))).collect();
+        atoms.sort_by_key(|(pos, _)| (pos.q, pos.r));
+        atoms
+    }
+
+    #[test]
+    fn cycles_spawn_grab_rotate_drop_and_consume(){
+        let (puzzle, solution) = carry_salt(Atom::Salt);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        assert_eq!(sim.period, 8);
+        assert!(sim.molecules.is_empty());
+        sim.step().unwrap();
+        assert_eq!(atoms(&sim), [(HexIndex{ q: 1, r: 0 }, true)]);
+        sim.step().unwrap();
+        assert_eq!(atoms(&sim), [(HexIndex{ q: 1, r: -1 }, true)]);
+        // the input refills once its hex is clear
+        run(&mut sim, 2).unwrap();
+        assert_eq!(atoms(&sim), [(HexIndex{ q: -1, r: 0 }, true), (HexIndex{ q: 1, r: 0 }, false)]);
+        assert!(sim.deliveries.is_empty());
+        sim.step().unwrap();
+        assert_eq!(atoms(&sim), [(HexIndex{ q: 1, r: 0 }, false)]);
+        assert_eq!(sim.deliveries, [Delivery{ cycle: 5, product: 0 }]);
+        assert!(!sim.is_complete());
+    }
+
+    #[test]
+    fn completion_and_metrics_follow_the_loop(){
+        let (puzzle, solution) = carry_salt(Atom::Salt);
+        let result = verify(&puzzle, &solution).unwrap();
+        // one salt per 8 cycle loop, with the first output on cycle 5 and the sixth 40 cycles later
+        assert_eq!(result.metrics, Metrics{ cycles: 45, cost: 20, area: 5, instructions: 6 });
+        assert_eq!(result.product_completion_cycles, [45]);
+        assert_eq!(result.deliveries.iter().map(|d| d.cycle).collect::<Vec<_>>(), [5, 13, 21, 29, 37, 45]);
+
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        run(&mut sim, 44).unwrap();
+        assert!(!sim.is_complete());
+        sim.step().unwrap();
+        assert!(sim.is_complete());
+    }
+
+    #[test]
+    fn wrong_outputs_only_fail_when_strict(){
+        let (puzzle, solution) = carry_salt(Atom::Air);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        assert_eq!(run(&mut sim, 8), Ok(()));
+
+        let mut sim = Sim::create_with_options(&puzzle, &solution, STRICT).unwrap();
+        let salt = Molecule::builder().atom(-1, 0, Atom::Salt).build().unwrap();
+        assert_eq!(run(&mut sim, 8), Err(SimError::WrongOutput{ cycle: 4, output_index: 1, got: salt }));
+    }
+
+    #[test]
+    fn drops_on_a_conduit_without_another_end_fail_when_strict(){
+        let (puzzle, solution) = carry_salt(Atom::Air);
+        let mut sim = Sim::create_with_options(&puzzle, &solution, STRICT).unwrap();
+        sim.parts[1].ty = SimPartType::Conduit(vec![HexIndex{ q: -1, r: 0 }]);
+        let error = run(&mut sim, 8).unwrap_err();
+        assert!(matches!(error, SimError::InvalidDrop{ cycle: 4, pos: HexIndex{ q: -1, r: 0 }, .. }), "{error:?}");
+    }
+
+    const MULTI_ARMS: [PartType; 3] = [PartType::BiArm, PartType::TriArm, PartType::HexArm];
+
+    /// A multi-gripper arm of the given type and length at the origin, facing the default way, with a salt input on each of the given hexes.
+    fn multi_arm(ty: &PartType, length: i32, inputs: &[HexIndex], tape: &str) -> (Puzzle, Solution){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let mut builder = SolutionBuilder::new("solution", "puzzle");
+        for input in inputs{
+            builder = builder.input(0, *input, 0);
+        }
+        let solution = builder.arm_of(ty.clone(), HexIndex::default(), 0, length).instructions(0, tape).build().unwrap();
+        (puzzle, solution)
+    }
+
+    fn grippers(ty: &PartType, length: i32, rotation: HexRotation) -> Vec<HexIndex>{
+        SimArm::new(ty.clone(), length).gripper_positions(HexIndex::default(), rotation)
+    }
+
+    #[test]
+    fn multi_arms_grab_move_and_drop_with_every_gripper(){
+        for ty in &MULTI_ARMS{
+            let start = grippers(ty, 1, HexRotation::R0);
+            let (puzzle, solution) = multi_arm(ty, 1, &start, "GRg");
+            let mut sim = Sim::create(&puzzle, &solution).unwrap();
+            sim.step().unwrap();
+            assert_eq!(sim.molecules.len(), start.len());
+            assert!(sim.molecules.iter().all(|m| m.grabbed), "{ty:?}");
+            sim.step().unwrap();
+            let moved: HashSet<HexIndex> = sim.molecules.iter().flat_map(|m| m.positions()).collect();
+            assert_eq!(moved, grippers(ty, 1, HexRotation::R300).into_iter().collect(), "{ty:?}");
+            sim.step().unwrap();
+            assert!(sim.molecules.iter().all(|m| !m.grabbed), "{ty:?}");
+        }
+    }
+
+    #[test]
+    fn multi_arms_collide_through_every_gripper(){
+        for ty in &MULTI_ARMS{
+            // the last gripper swings its atom past a salt left halfway along its arc
+            let (last, next) = (*grippers(ty, 2, HexRotation::R0).last().unwrap(), *grippers(ty, 2, HexRotation::R300).last().unwrap());
+            let between = HexIndex{ q: (last.q + next.q) / 2, r: (last.r + next.r) / 2 };
+            let (puzzle, solution) = multi_arm(ty, 2, &[last, between], "GR");
+            let mut sim = Sim::create(&puzzle, &solution).unwrap();
+            sim.step().unwrap();
+            let error = sim.step().unwrap_err();
+            assert!(matches!(&error, SimError::Collision{ cycle: 1, parts, .. } if parts == &[2]), "{ty:?}: {error:?}");
+        }
+    }
+
+    #[test]
+    fn multi_arms_sweep_area_with_every_gripper(){
+        for (ty, area) in MULTI_ARMS.iter().zip([5, 7, 7]){
+            let (puzzle, solution) = multi_arm(ty, 1, &[], "R");
+            let mut sim = Sim::create(&puzzle, &solution).unwrap();
+            assert_eq!(sim.area(), 1 + grippers(ty, 1, HexRotation::R0).len(), "{ty:?}");
+            sim.step().unwrap();
+            assert_eq!(sim.area(), area, "{ty:?}");
+        }
+    }
+
+    #[test]
+    fn period_overrides_lengthen_every_arms_loop_without_counting(){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = |second: &str| SolutionBuilder::new("solution", "puzzle")
+            .arm(HexIndex::default(), 0, 1)
+            .arm(HexIndex{ q: 4, r: 0 }, 0, 1)
+            .instructions(0, "Gg")
+            .instructions(1, second)
+            .build().unwrap();
+
+        let plain = solution("R");
+        assert_eq!(Sim::create(&puzzle, &plain).unwrap().period, 2);
+        let overridden = solution("R   O");
+        let sim = Sim::create(&puzzle, &overridden).unwrap();
+        assert_eq!(sim.period, 5);
+        // the first arm waits for the override before starting its tape again
+        assert_eq!(sim.instructions_on(2)[0], Instruction::Blank);
+        assert_eq!(sim.instructions_on(5)[0], Instruction::Grab);
+        assert_eq!(overridden.instructions(), plain.instructions());
+    }
+
+    /// Where an arm on a track of the given hexes, starting on the first, is after each instruction of its tape.
+    fn track_positions(hexes: &[(i32, i32)], tape: &str) -> Vec<(i32, i32)>{
+        let hexes: Vec<HexIndex> = hexes.iter().map(|(q, r)| HexIndex{ q: *q, r: *r }).collect();
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle").track(&hexes).arm(hexes[0], 0, 1).instructions(0, tape).build().unwrap();
+        let mut sim = Sim::create_with_options(&puzzle, &solution, SimOptions{ allow_overlap: true, strict_drops: false }).unwrap();
+        (0..tape.len()).map(|_| {
+            sim.step().unwrap();
+            (sim.parts[1].pos.q, sim.parts[1].pos.r)
+        }).collect()
+    }
+
+    #[test]
+    fn open_tracks_stop_at_their_ends(){
+        let line = [(0, 0), (1, 0), (2, 0)];
+        assert_eq!(track_positions(&line, "AAAa"), [(1, 0), (2, 0), (2, 0), (1, 0)]);
+        assert_eq!(track_positions(&line, "aA"), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn looped_tracks_wrap_through_their_first_hex(){
+        let triangle = [(0, 0), (1, 0), (0, 1), (0, 0)];
+        assert_eq!(track_positions(&triangle, "AAAA"), [(1, 0), (0, 1), (0, 0), (1, 0)]);
+        assert_eq!(track_positions(&triangle, "a"), [(0, 1)]);
+        // a track needs more than three hexes listed to loop, so one that only goes there and back is open
+        let there_and_back = [(0, 0), (1, 0), (0, 0)];
+        assert_eq!(track_positions(&there_and_back, "AAAa"), [(1, 0), (0, 0), (0, 0), (1, 0)]);
+    }
+
+    /// A salt polymer input at the origin, and an arm that swings its chain onto a polymer output for a chain of the given atom.
+    fn carry_polymer(product: Atom) -> (Puzzle, Solution){
+        let unit = |atom| Molecule::builder().atom(0, 0, atom).atom(1, 0, Atom::Repeat).bond((0, 0), (1, 0)).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(unit(Atom::Salt)).product(unit(product)).build();
+        let mut solution = SolutionBuilder::new("solution", "puzzle")
+            .input(0, HexIndex::default(), 0)
+            .output(0, HexIndex{ q: -1, r: 1 }, 1)
+            .arm(HexIndex{ q: -1, r: 0 }, 0, 1)
+            .instructions(0, "Grg")
+            .build().unwrap();
+        solution.parts[1].ty = PartType::PolymerOutput;
+        (puzzle, solution)
+    }
+
+    #[test]
+    fn polymer_outputs_take_a_whole_chain(){
+        let (puzzle, solution) = carry_polymer(Atom::Salt);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        sim.step().unwrap();
+        assert_eq!(atoms(&sim).len(), POLYMER_REPETITIONS as usize);
+        run(&mut sim, 2).unwrap();
+        // one chain satisfies every product the output needs
+        assert_eq!(sim.deliveries, [Delivery{ cycle: 3, product: 0 }]);
+        assert!(sim.is_complete());
+        assert_eq!(verify(&puzzle, &solution).unwrap().metrics.cycles, 3);
+    }
+
+    #[test]
+    fn polymer_outputs_reject_chains_of_the_wrong_unit(){
+        let (puzzle, solution) = carry_polymer(Atom::Water);
+        let mut sim = Sim::create(&puzzle, &solution).unwrap();
+        run(&mut sim, 3).unwrap();
+        assert!(sim.deliveries.is_empty());
+        assert!(!sim.is_complete());
+        assert_eq!(sim.rejected_outputs().len(), 1);
+        let mut sim = Sim::create_with_options(&puzzle, &solution, STRICT).unwrap();
+        assert!(matches!(run(&mut sim, 3), Err(SimError::WrongOutput{ output_index: 1, .. })));
+    }
+}
@@ -0,0 +1,30 @@
+use crate::data::Instruction;
+
+use super::{Motion, Sim, SimMolecule};
+
+// Hooks into the simulation, for following along with what happens during each cycle.
+
+/// Receives events as a `Sim` steps through cycles, for renderers, statistics, and debuggers. Every method does nothing by default.
+/// Parts are identified by their index in `Sim::parts`, and molecules by their index in `Sim::molecules` at the time of the event.
+pub trait SimObserver{
+    /// Called before anything happens in a cycle.
+    fn cycle_started(&mut self, _sim: &Sim){}
+
+    /// Called after a cycle completes without error, once `Sim::cycle` has advanced.
+    fn cycle_ended(&mut self, _sim: &Sim){}
+
+    /// Called when a glyph fires. Glyphs may fire twice a cycle, before and after arms move.
+    fn glyph_activated(&mut self, _part: usize){}
+
+    /// Called when an input spawns a new molecule.
+    fn input_spawned(&mut self, _part: usize, _molecule: usize){}
+
+    /// Called when an output consumes a molecule, which has already been removed from the board.
+    fn output_consumed(&mut self, _part: usize, _molecule: &SimMolecule){}
+
+    /// Called for every arm as arms move, with the instruction it performed and how its base moved, before collisions are checked.
+    fn arm_moved(&mut self, _part: usize, _instruction: Instruction, _motion: Motion){}
+}
+
+/// Ignores every event.
+impl SimObserver for (){}
@@ -0,0 +1,86 @@
+use crate::data::{HexIndex, HexMap};
+use crate::store::fingerprint;
+
+use super::{Sim, SimMolecule, SimPartType};
+
+// Snapshots of the parts of simulation state that determine what happens next, for detecting loops.
+
+/// Everything about a simulation that affects its future, ignoring counters like outputs consumed and glyph activations.
+/// Two simulations of the same solution with equal keys behave identically from then on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StateKey{
+    /// How far through its tapes the simulation is.
+    tape: i32,
+    arms: Vec<i32>,
+    molecules: Vec<Vec<i32>>
+}
+
+impl StateKey{
+    /// A hash of this state that's the same across runs, platforms, and compiler versions, unlike `Hash`, so it can be stored and compared later.
+    pub fn stable_hash(&self) -> u64{
+        let values = [self.tape, self.arms.len() as i32].into_iter()
+            .chain(self.arms.iter().copied())
+            .chain([self.molecules.len() as i32])
+            .chain(self.molecules.iter().flat_map(|m| [m.len() as i32].into_iter().chain(m.iter().copied())));
+        let bytes: Vec<u8> = values.flat_map(i32::to_le_bytes).collect();
+        fingerprint(&bytes)
+    }
+}
+
+impl Sim{
+
+    /// Snapshot the state that determines how this simulation continues.
+    pub fn state_key(&self) -> StateKey{
+        self.key_of(|_| true, |_| true)
+    }
+
+    /// Snapshot the state like `state_key`, but only including molecules that are held or have an atom on one of the given hexes.
+    /// If those hexes include everything parts can reach, the molecules left out can't affect how the simulation continues.
+    pub fn active_state_key(&self, reachable: &HexMap<()>) -> StateKey{
+        self.key_of(|molecule| molecule.grabbed || molecule.positions().any(|pos| reachable.contains_key(&pos)), |_| true)
+    }
+
+    /// Snapshot the state like `active_state_key`, but leaving out the atoms and bonds of those molecules that aren't on the given hexes.
+    /// Molecules that only change out of reach, such as chains pulled further out every loop, compare equal.
+    pub fn reachable_state_key(&self, reachable: &HexMap<()>) -> StateKey{
+        self.key_of(|molecule| molecule.grabbed || molecule.positions().any(|pos| reachable.contains_key(&pos)), |pos| reachable.contains_key(&pos))
+    }
+
+    fn key_of(&self, include: impl Fn(&SimMolecule) -> bool, keep: impl Fn(HexIndex) -> bool) -> StateKey{
+        let mut arms = Vec::new();
+        for part in &self.parts{
+            let SimPartType::Arm(arm) = &part.ty else { continue };
+            arms.extend([part.pos.q, part.pos.r, part.rotation.turns() as i32, arm.length, arm.grabbing as i32]);
+            arms.extend(arm.holding.iter().map(|h| *h as i32));
+            arms.push(arm.track.as_ref().map_or(-1, |(_, idx)| *idx as i32));
+        }
+        let mut molecules: Vec<Vec<i32>> = self.molecules.iter().filter(|m| include(m)).map(|molecule| {
+            let mut atoms: Vec<[i32; 3]> = molecule.layout.atoms.iter()
+                .map(|(pos, atom)| (*pos + molecule.pos, atom))
+                .filter(|(pos, _)| keep(*pos))
+                .map(|(pos, atom)| [pos.q, pos.r, atom.id() as i32])
+                .collect();
+            atoms.sort();
+            let mut bonds: Vec<[i32; 5]> = molecule.layout.bonds.iter().filter(|bond| keep(bond.start + molecule.pos) && keep(bond.end + molecule.pos)).map(|bond| {
+                let (start, end) = (bond.start + molecule.pos, bond.end + molecule.pos);
+                let (a, b) = ((start.q, start.r), (end.q, end.r));
+                let (a, b) = (a.min(b), a.max(b));
+                [a.0, a.1, b.0, b.1, bond.ty.id() as i32]
+            }).collect();
+            bonds.sort();
+            let mut key = vec![molecule.grabbed as i32, atoms.len() as i32];
+            key.extend(atoms.into_iter().flatten());
+            key.extend(bonds.into_iter().flatten());
+            key
+        }).collect();
+        molecules.sort();
+        let tape = if self.period > 0 { self.cycle % self.period } else { 0 };
+        StateKey{ tape, arms, molecules }
+    }
+
+    /// A stable hash of every atom, bond, and arm, and how far through its tapes this simulation is. See `StateKey::stable_hash`.
+    pub fn state_hash(&self) -> u64{
+        self.state_key().stable_hash()
+    }
+}
+
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::data::{Puzzle, Solution};
+use crate::parse::{parse_any, parse_puzzle, FileKind, ParseError};
+
+/// A stable 64-bit fingerprint of a file's contents (FNV-1a), used to recognise files that have already been parsed.
+pub fn fingerprint(data: &[u8]) -> u64{
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data{
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// A thread-safe cache of parsed puzzles, keyed by name and by fingerprint.
+/// Puzzles are loaded lazily from a list of directories the first time they're asked for, and shared between callers afterwards.
+#[derive(Debug, Default)]
+pub struct PuzzleStore{
+    dirs: Vec<PathBuf>,
+    inner: RwLock<StoreInner>
+}
+
+#[derive(Debug, Default)]
+struct StoreInner{
+    by_name: HashMap<String, Arc<Puzzle>>,
+    by_fingerprint: HashMap<u64, Arc<Puzzle>>,
+    /// Whether every directory has been fully scanned, so that missing names can fail fast.
+    scanned: bool
+}
+
+impl PuzzleStore{
+    /// Create an empty store that loads puzzles from nothing but what's inserted.
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Create a store that loads puzzles from `.puzzle` files in the given directories, searched in order.
+    pub fn with_dirs(dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self{
+        PuzzleStore{ dirs: dirs.into_iter().map(Into::into).collect(), inner: RwLock::default() }
+    }
+
+    /// Get a puzzle by name, loading it from disk if it hasn't been yet.
+    /// `<name>.puzzle` is tried in each directory first, then every puzzle file is scanned in case it was renamed.
+    pub fn get(&self, name: &str) -> Option<Arc<Puzzle>>{
+        if let Some(puzzle) = self.read().by_name.get(name){
+            return Some(puzzle.clone());
+        }
+        for dir in &self.dirs{
+            if let Ok(puzzle) = self.load_path(&dir.join(format!("{name}.puzzle"))){
+                if puzzle.name == name{
+                    return Some(puzzle);
+                }
+            }
+        }
+        if !self.read().scanned{
+            self.scan();
+        }
+        self.read().by_name.get(name).cloned()
+    }
+
+    /// Get a puzzle that was loaded from a file with the given fingerprint.
+    pub fn get_by_fingerprint(&self, fingerprint: u64) -> Option<Arc<Puzzle>>{
+        self.read().by_fingerprint.get(&fingerprint).cloned()
+    }
+
+    /// Parse a puzzle file, or return the cached puzzle if a file with the same contents has already been parsed.
+    pub fn load_bytes(&self, data: &[u8]) -> Result<Arc<Puzzle>, &'static str>{
+        let fingerprint = fingerprint(data);
+        if let Some(puzzle) = self.get_by_fingerprint(fingerprint){
+            return Ok(puzzle);
+        }
+        let puzzle = Arc::new(parse_puzzle(data)?);
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.by_name.entry(puzzle.name.clone()).or_insert_with(|| puzzle.clone());
+        Ok(inner.by_fingerprint.entry(fingerprint).or_insert(puzzle).clone())
+    }
+
+    /// Read and parse a puzzle file, or return the cached puzzle if a file with the same contents has already been parsed.
+    pub fn load_path(&self, path: &Path) -> Result<Arc<Puzzle>, &'static str>{
+        self.load_bytes(&fs::read(path).map_err(|_| "could not read puzzle file")?)
+    }
+
+    /// Load every `.puzzle` file in the store's directories, skipping any that can't be read or parsed.
+    pub fn scan(&self){
+        for dir in &self.dirs{
+            let Ok(entries) = fs::read_dir(dir) else { continue };
+            for entry in entries.flatten(){
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "puzzle"){
+                    let _ = self.load_path(&path);
+                }
+            }
+        }
+        self.inner.write().unwrap_or_else(|e| e.into_inner()).scanned = true;
+    }
+
+    /// The number of distinct puzzles loaded so far.
+    pub fn len(&self) -> usize{
+        self.read().by_fingerprint.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, StoreInner>{
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A problem with a file found while scanning a save or workshop directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanError{
+    /// The file couldn't be read or parsed.
+    Parse(ParseError),
+    /// Another file already had a solution to this puzzle in this slot.
+    DuplicateSlot{ puzzle_name: String, slot: u32 },
+    /// Another file already had a puzzle with this name.
+    DuplicatePuzzle{ name: String }
+}
+
+/// A file that was skipped while scanning, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanDiagnostic{
+    pub path: PathBuf,
+    pub error: ScanError
+}
+
+/// A parsed file, and where it came from.
+#[derive(Clone, Debug)]
+pub struct Indexed<T>{
+    pub path: PathBuf,
+    pub value: T
+}
+
+/// Every puzzle and solution in a save or workshop directory, keyed by puzzle name and solution slot.
+/// Slots come from the number the game appends to solution file names, like `P007-2.solution`, and are 0 for files without one.
+#[derive(Clone, Debug, Default)]
+pub struct SaveIndex{
+    pub puzzles: HashMap<String, Indexed<Puzzle>>,
+    /// Solutions by the name of the puzzle they solve, as recorded in the file, then by slot.
+    pub solutions: HashMap<String, BTreeMap<u32, Indexed<Solution>>>,
+    /// Files that were skipped, in the order they were found.
+    pub diagnostics: Vec<ScanDiagnostic>
+}
+
+impl SaveIndex{
+    /// Recursively scan a directory for `.puzzle` and `.solution` files, skipping and recording any that can't be read or parsed.
+    /// Files are visited in path order, so that which of two duplicates is kept doesn't depend on the file system.
+    pub fn scan(dir: impl AsRef<Path>) -> SaveIndex{
+        let mut paths = Vec::new();
+        collect_files(dir.as_ref(), &mut paths);
+        paths.sort();
+        let mut index = SaveIndex::default();
+        for path in paths{
+            let parsed = fs::read(&path)
+                .map_err(|e| ParseError::Unreadable{ kind: e.kind() })
+                .and_then(|data| parse_any(&data));
+            let error = match parsed{
+                Ok(FileKind::Puzzle(puzzle)) => index.insert_puzzle(path.clone(), puzzle),
+                Ok(FileKind::Solution(solution)) => index.insert_solution(path.clone(), solution),
+                Err(error) => Err(ScanError::Parse(error))
+            };
+            if let Err(error) = error{
+                index.diagnostics.push(ScanDiagnostic{ path, error });
+            }
+        }
+        index
+    }
+
+    fn insert_puzzle(&mut self, path: PathBuf, puzzle: Puzzle) -> Result<(), ScanError>{
+        if self.puzzles.contains_key(&puzzle.name){
+            return Err(ScanError::DuplicatePuzzle{ name: puzzle.name });
+        }
+        self.puzzles.insert(puzzle.name.clone(), Indexed{ path, value: puzzle });
+        Ok(())
+    }
+
+    fn insert_solution(&mut self, path: PathBuf, solution: Solution) -> Result<(), ScanError>{
+        let slot = solution_slot(&path);
+        let slots = self.solutions.entry(solution.puzzle_name.clone()).or_default();
+        if slots.contains_key(&slot){
+            return Err(ScanError::DuplicateSlot{ puzzle_name: solution.puzzle_name, slot });
+        }
+        slots.insert(slot, Indexed{ path, value: solution });
+        Ok(())
+    }
+
+    /// Get a puzzle by name.
+    pub fn puzzle(&self, name: &str) -> Option<&Puzzle>{
+        self.puzzles.get(name).map(|p| &p.value)
+    }
+
+    /// Get the solution to a puzzle in a slot.
+    pub fn solution(&self, puzzle_name: &str, slot: u32) -> Option<&Solution>{
+        self.solutions.get(puzzle_name)?.get(&slot).map(|s| &s.value)
+    }
+
+    /// Every solution to a puzzle, with its slot, in slot order.
+    pub fn solutions_for(&self, puzzle_name: &str) -> impl Iterator<Item = (u32, &Solution)>{
+        self.solutions.get(puzzle_name).into_iter().flatten().map(|(slot, s)| (*slot, &s.value))
+    }
+}
+
+/// The slot number at the end of a solution's file name, after the last `-`, or 0 if there isn't one.
+fn solution_slot(path: &Path) -> u32{
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit_once('-'))
+        .and_then(|(_, slot)| slot.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Recursively list every puzzle and solution file under a directory, skipping directories that can't be read.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>){
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten(){
+        let path = entry.path();
+        if path.is_dir(){
+            collect_files(&path, files);
+        }else if path.extension().is_some_and(|e| e == "puzzle" || e == "solution"){
+            files.push(path);
+        }
+    }
+}
+
+/// How puzzle names are compared when pairing solutions with puzzles. By default names must match exactly.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NameMatching{
+    /// Compare names without regard to case.
+    pub ignore_case: bool,
+    /// Ignore whitespace at the start and end of names.
+    pub trim: bool,
+    /// Treat spaces, `-` and `_` as the same character.
+    pub unify_separators: bool
+}
+
+impl NameMatching{
+    /// Normalize a name, so that names that match are equal afterwards.
+    pub fn normalize(self, name: &str) -> String{
+        let name = if self.trim { name.trim() } else { name };
+        name.chars()
+            .map(|c| if self.unify_separators && (c == '-' || c == '_' || c.is_whitespace()) { ' ' } else { c })
+            .flat_map(|c| if self.ignore_case { c.to_lowercase().collect::<Vec<_>>() } else { vec![c] })
+            .collect()
+    }
+}
+
+/// A collection of puzzles and solutions, paired up by the puzzle names that solutions record.
+#[derive(Clone, Debug, Default)]
+pub struct Registry{
+    matching: NameMatching,
+    /// Puzzles by normalized name.
+    puzzles: HashMap<String, Arc<Puzzle>>,
+    solutions: Vec<Solution>,
+    /// Indices into `solutions` by normalized puzzle name.
+    by_puzzle: HashMap<String, Vec<usize>>
+}
+
+impl Registry{
+    /// Create an empty registry that compares names as given.
+    pub fn new(matching: NameMatching) -> Self{
+        Registry{ matching, ..Registry::default() }
+    }
+
+    /// Add a puzzle, returning the puzzle it replaced if one with a matching name was already added.
+    pub fn add_puzzle(&mut self, puzzle: impl Into<Arc<Puzzle>>) -> Option<Arc<Puzzle>>{
+        let puzzle = puzzle.into();
+        self.puzzles.insert(self.matching.normalize(&puzzle.name), puzzle)
+    }
+
+    /// Add a solution. Solutions are never replaced, since many may solve the same puzzle.
+    pub fn add_solution(&mut self, solution: Solution){
+        self.by_puzzle.entry(self.matching.normalize(&solution.puzzle_name)).or_default().push(self.solutions.len());
+        self.solutions.push(solution);
+    }
+
+    /// Add every puzzle and solution from a scanned directory. Solutions are added in path order.
+    pub fn add_index(&mut self, index: SaveIndex){
+        for (_, puzzle) in index.puzzles{
+            self.add_puzzle(puzzle.value);
+        }
+        let mut solutions: Vec<Indexed<Solution>> = index.solutions.into_values().flat_map(BTreeMap::into_values).collect();
+        solutions.sort_by(|a, b| a.path.cmp(&b.path));
+        for solution in solutions{
+            self.add_solution(solution.value);
+        }
+    }
+
+    /// Get a puzzle by a name that matches its own.
+    pub fn puzzle(&self, name: &str) -> Option<&Arc<Puzzle>>{
+        self.puzzles.get(&self.matching.normalize(name))
+    }
+
+    /// Get the puzzle a solution solves, if it's been added.
+    pub fn puzzle_for(&self, solution: &Solution) -> Option<&Arc<Puzzle>>{
+        self.puzzle(&solution.puzzle_name)
+    }
+
+    /// Every solution to the puzzle with a matching name, in the order they were added.
+    pub fn solutions_for(&self, puzzle_name: &str) -> impl Iterator<Item = &Solution>{
+        self.by_puzzle.get(&self.matching.normalize(puzzle_name)).into_iter().flatten().map(|i| &self.solutions[*i])
+    }
+
+    /// Every solution, in the order they were added.
+    pub fn solutions(&self) -> &[Solution]{
+        &self.solutions
+    }
+
+    /// Every solution to a puzzle that hasn't been added, in the order they were added.
+    pub fn orphaned_solutions(&self) -> impl Iterator<Item = &Solution>{
+        self.solutions.iter().filter(|s| self.puzzle_for(s).is_none())
+    }
+
+    /// The puzzle names that solutions refer to but no added puzzle matches, each given as first recorded, in order.
+    pub fn missing_puzzles(&self) -> Vec<&str>{
+        let mut seen = HashSet::new();
+        self.orphaned_solutions()
+            .filter(|s| seen.insert(self.matching.normalize(&s.puzzle_name)))
+            .map(|s| s.puzzle_name.as_str())
+            .collect()
+    }
+}
@@ -0,0 +1,262 @@
+use crate::data::{Instruction, Part, PartCategory, Solution};
+
+// Arm tapes laid out on a timeline, with reset and repeat expanded into the instructions they stand for.
+
+/// The most slots a tape, or the shared timeline of every tape in a solution, may cover.
+/// Instructions can be placed anywhere in an `i32`, but laying out tapes far longer than any solution needs would use unbounded memory.
+pub const MAX_PERIOD: i32 = 1_000_000;
+
+/// The instructions an arm runs, one per cycle, from its first instruction onwards.
+/// Reset and repeat instructions are expanded the way the game shows them, so every entry is a primitive instruction or a blank.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Tape{
+    /// The timeline index of the first instruction, or 0 for an empty tape.
+    pub start: i32,
+    /// The expanded instructions, starting at `start`.
+    pub instructions: Vec<Instruction>
+}
+
+/// The net effect of a sequence of instructions on an arm, relative to where it started.
+#[derive(Copy, Clone, Debug, Default)]
+struct ArmOffset{
+    grabbing: bool,
+    /// Net anticlockwise rotations.
+    rotation: i32,
+    /// Net piston extensions.
+    extension: i32,
+    /// Net steps forwards along a track.
+    track: i32
+}
+
+impl ArmOffset{
+    fn apply(&mut self, instruction: Instruction){
+        match instruction{
+            Instruction::Grab => self.grabbing = true,
+            Instruction::Drop => self.grabbing = false,
+            Instruction::RotateAnticlockwise => self.rotation += 1,
+            Instruction::RotateClockwise => self.rotation -= 1,
+            Instruction::Extend => self.extension += 1,
+            Instruction::Retract => self.extension -= 1,
+            Instruction::Advance => self.track += 1,
+            Instruction::Retreat => self.track -= 1,
+            _ => {}
+        }
+    }
+
+    /// The instructions that return an arm to where it started: drop anything held, undo extensions, unwind rotations, then return along the track.
+    fn undo(self) -> Vec<Instruction>{
+        let mut result = Vec::new();
+        if self.grabbing{
+            result.push(Instruction::Drop);
+        }
+        let repeated = |count: i32, forwards: Instruction, backwards: Instruction| std::iter::repeat_n(if count > 0 { backwards } else { forwards }, count.unsigned_abs() as usize);
+        result.extend(repeated(self.extension, Instruction::Extend, Instruction::Retract));
+        result.extend(repeated(self.rotation, Instruction::RotateAnticlockwise, Instruction::RotateClockwise));
+        result.extend(repeated(self.track, Instruction::Advance, Instruction::Retreat));
+        result
+    }
+}
+
+impl Tape{
+    /// Lay out the instructions of an arm on a timeline, expanding reset and repeat instructions.
+    ///
+    /// A repeat replays every slot since the start of the tape or the end of the last reset, including blanks.
+    /// A reset drops anything held and moves the arm back to where it started, taking one slot even if there's nothing to undo.
+    /// A period override does nothing, but holds its slot so that the tape, and with it the period every arm loops over, lasts at least until it.
+    /// It doesn't end a section, so a later repeat replays its slot as a blank.
+    /// Fails if two instructions share a slot, an expansion runs into a later instruction, or the tape would cover more than `MAX_PERIOD` slots.
+    pub fn from_part(part: &Part) -> Result<Tape, &'static str>{
+        let mut sorted = part.instructions.clone();
+        sorted.sort_by_key(|(_, idx)| *idx);
+        if sorted.windows(2).any(|pair| pair[0].1 == pair[1].1){
+            return Err("two instructions share a slot");
+        }
+        let Some(&(_, start)) = sorted.first() else { return Ok(Tape::default()) };
+
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut offset = ArmOffset::default();
+        // index into `instructions` of the first slot a repeat replays
+        let mut section_start = 0;
+        for (instruction, idx) in sorted{
+            let slot = usize::try_from(i64::from(idx) - i64::from(start)).ok().filter(|s| *s < MAX_PERIOD as usize).ok_or("tape is too long")?;
+            if slot < instructions.len(){
+                return Err("expanded reset or repeat overlaps a later instruction");
+            }
+            instructions.resize(slot, Instruction::Blank);
+            let expanded = match instruction{
+                Instruction::Repeat => instructions[section_start..].to_vec(),
+                Instruction::Reset => {
+                    let undo = offset.undo();
+                    if undo.is_empty() { vec![Instruction::Blank] } else { undo }
+                }
+                Instruction::PeriodOverride => vec![Instruction::Blank],
+                other => vec![other]
+            };
+            for step in &expanded{
+                offset.apply(*step);
+            }
+            instructions.extend(expanded);
+            if instructions.len() > MAX_PERIOD as usize || start.checked_add(instructions.len() as i32).is_none(){
+                return Err("tape is too long");
+            }
+            if instruction == Instruction::Reset{
+                offset = ArmOffset::default();
+                section_start = instructions.len();
+            }
+        }
+        Ok(Tape{ start, instructions })
+    }
+
+    /// The number of slots this tape covers, from its first instruction to the end of its last expansion.
+    pub fn period(&self) -> i32{
+        self.instructions.len() as i32
+    }
+
+    /// The timeline index just after the end of this tape.
+    pub fn end(&self) -> i32{
+        self.start + self.period()
+    }
+
+    /// The instruction at a timeline index, which is blank outside of the tape.
+    pub fn at(&self, idx: i32) -> Instruction{
+        usize::try_from(i64::from(idx) - i64::from(self.start)).ok().and_then(|i| self.instructions.get(i)).cloned().unwrap_or_default()
+    }
+}
+
+/// Where a shared timeline for a set of tapes starts, and how many slots it covers: from the first instruction of any tape to the end of the last.
+/// Simulations run the slots of this timeline in order, so tapes that lay out the same over it behave the same wherever it starts.
+/// Fails if the timeline would cover more than `MAX_PERIOD` slots.
+pub fn timeline<'a>(tapes: impl IntoIterator<Item = &'a Tape> + Clone) -> Result<(i32, i32), &'static str>{
+    let start = tapes.clone().into_iter().filter(|t| t.period() > 0).map(|t| t.start).min().unwrap_or(0);
+    let period = tapes.into_iter().map(|t| i64::from(t.end()) - i64::from(start)).max().unwrap_or(0).max(0);
+    if period > i64::from(MAX_PERIOD){
+        return Err("tapes are too long");
+    }
+    Ok((start, period as i32))
+}
+
+impl Solution{
+    /// Remove every stored blank that doesn't change how any arm runs, then recompute the recorded instruction metric, if there is one.
+    /// Blanks are kept where they're needed to set where the shared timeline ends or to pad a section that a repeat replays; other runs of blanks collapse to nothing.
+    /// Returns how many instructions were removed. Solutions whose tapes can't be laid out are left as they are.
+    pub fn compact_tapes(&mut self) -> i32{
+        let arms: Vec<usize> = (0..self.parts.len()).filter(|i| self.parts[*i].ty.category() == PartCategory::Arm).collect();
+        let Ok(mut tapes) = arms.iter().map(|i| Tape::from_part(&self.parts[*i])).collect::<Result<Vec<_>, _>>() else { return 0 };
+        // tapes behave the same if they run the same instructions over their timeline, wherever it starts
+        let laid_out = |tapes: &[Tape]| {
+            let (start, period) = timeline(tapes).ok()?;
+            Some(tapes.iter().map(|t| (start..start + period).map(|idx| t.at(idx)).collect::<Vec<_>>()).collect::<Vec<_>>())
+        };
+        let Some(original) = laid_out(&tapes) else { return 0 };
+
+        let mut removed = 0;
+        for (arm, &part_idx) in arms.iter().enumerate(){
+            for i in (0..self.parts[part_idx].instructions.len()).rev(){
+                if self.parts[part_idx].instructions[i].0 != Instruction::Blank{
+                    continue;
+                }
+                let mut candidate = self.parts[part_idx].clone();
+                candidate.instructions.remove(i);
+                let Ok(tape) = Tape::from_part(&candidate) else { continue };
+                let previous = std::mem::replace(&mut tapes[arm], tape);
+                if laid_out(&tapes).as_ref() == Some(&original){
+                    self.parts[part_idx] = candidate;
+                    removed += 1;
+                }else{
+                    tapes[arm] = previous;
+                }
+            }
+        }
+        let instructions = self.instructions();
+        if let Some(metrics) = &mut self.metrics{
+            metrics.instructions = instructions;
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::data::{HexIndex, PartType};
+
+    fn arm(instructions: &[(Instruction, i32)]) -> Part{
+        Part{
+            ty: PartType::Arm,
+            pos: HexIndex::default(),
+            rotation: 0,
+            arm_number: 0,
+            arm_length: 1,
+            index: 0,
+            conduit_index: 0,
+            track_hexes: Vec::new(),
+            conduit_hexes: Vec::new(),
+            instructions: instructions.to_vec()
+        }
+    }
+
+    fn expand(tape: &str, start: i32) -> Result<Tape, &'static str>{
+        let instructions: Vec<_> = tape.bytes().enumerate()
+            .filter(|(_, id)| *id != b' ')
+            .map(|(i, id)| (Instruction::from_id(id).unwrap(), start + i as i32))
+            .collect();
+        Tape::from_part(&arm(&instructions))
+    }
+
+    fn ids(tape: &Tape) -> String{
+        tape.instructions.iter().map(|i| i.id() as char).collect()
+    }
+
+    #[test]
+    fn reset_undoes_held_rotation_and_extension(){
+        let tape = expand("GrrEX", 5).unwrap();
+        assert_eq!(tape.start, 5);
+        assert_eq!(ids(&tape), "GrrEgeRR");
+    }
+
+    #[test]
+    fn reset_with_nothing_to_undo_takes_a_slot(){
+        assert_eq!(ids(&expand("X", 0).unwrap()), " ");
+    }
+
+    #[test]
+    fn repeat_replays_since_last_reset(){
+        assert_eq!(ids(&expand("Gg X r C", 0).unwrap()), "Gg   r  r ");
+    }
+
+    #[test]
+    fn period_override_extends_the_tape(){
+        let tape = expand("G   O", 0).unwrap();
+        assert_eq!(tape.period(), 5);
+        assert_eq!(tape.at(4), Instruction::Blank);
+    }
+
+    #[test]
+    fn repeats_replay_period_overrides_as_blanks(){
+        assert_eq!(ids(&expand("GOC", 0).unwrap()), "G G ");
+        assert_eq!(ids(&expand("GO C", 0).unwrap()), "G  G  ");
+        assert_eq!(ids(&expand("GC  O", 0).unwrap()), "GG   ");
+    }
+
+    #[test]
+    fn overlapping_expansion_fails(){
+        assert!(expand("GrXR", 0).is_err());
+        assert!(Tape::from_part(&arm(&[(Instruction::Grab, 0), (Instruction::Drop, 0)])).is_err());
+    }
+
+    #[test]
+    fn extreme_indices_fail_instead_of_overflowing(){
+        assert!(Tape::from_part(&arm(&[(Instruction::Grab, i32::MIN), (Instruction::Drop, i32::MAX)])).is_err());
+        assert!(Tape::from_part(&arm(&[(Instruction::Grab, 0), (Instruction::Drop, MAX_PERIOD)])).is_err());
+        assert!(Tape::from_part(&arm(&[(Instruction::Grab, i32::MAX)])).is_err());
+        assert!(expand("G", i32::MAX - 1).is_ok());
+    }
+
+    #[test]
+    fn timeline_covers_every_tape(){
+        let tapes = [expand("G", -3).unwrap(), expand("rr", 4).unwrap(), Tape::default()];
+        assert_eq!(timeline(&tapes), Ok((-3, 9)));
+        let far = [expand("G", i32::MIN).unwrap(), expand("G", i32::MAX - 1).unwrap()];
+        assert!(timeline(&far).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::{HashMap, HashSet};
+
+use bitflags::Flags;
+
+use crate::data::{Atom, Bond, BondType, HexIndex, Instruction, Molecule, Part, PartCategory, PartType, Permissions, Puzzle, Solution, POLYMER_REPETITIONS};
+
+// Static checks of a solution against its puzzle, without simulating it.
+
+/// A reason a solution isn't a legal solution to a puzzle.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationError{
+    /// A part isn't enabled by the puzzle's permissions.
+    ForbiddenPart{ part_index: usize, ty: PartType },
+    /// An arm has an instruction that isn't enabled by the puzzle's permissions.
+    ForbiddenInstruction{ part_index: usize, instruction: Instruction },
+    /// An input refers to a reagent that the puzzle doesn't have.
+    InvalidInput{ part_index: usize, index: i32 },
+    /// An output refers to a product that the puzzle doesn't have.
+    InvalidOutput{ part_index: usize, index: i32 },
+    /// An arm or piston is shorter than 1 or longer than 3.
+    InvalidArmLength{ part_index: usize, length: i32 },
+    /// A track covers no hexes.
+    EmptyTrack{ part_index: usize },
+    /// Two consecutive hexes of a track aren't adjacent. `hex_index` is the index of the second, and `hex` is the second itself.
+    DisconnectedTrack{ part_index: usize, hex_index: usize, hex: HexIndex },
+    /// A track covers the same hex more than once, other than repeating its first hex at the end to close a loop.
+    RepeatedTrackHex{ part_index: usize, hex: HexIndex },
+    /// A conduit appears in a puzzle that isn't a production puzzle.
+    ConduitOutsideProduction{ part_index: usize },
+    /// A conduit covers no hexes.
+    EmptyConduit{ part_index: usize },
+    /// A part that isn't an arm has an instruction, at the given position on its tape.
+    InstructionOnNonArm{ part_index: usize, instruction: Instruction, index: i32 },
+    /// An arm other than a piston has an extend or retract instruction, at the given position on its tape.
+    ExtensionOnNonPiston{ part_index: usize, instruction: Instruction, index: i32 },
+    /// An arm that wasn't placed on a track has an advance or retreat instruction, at the given position on its tape. `hex` is where the arm was placed.
+    MovementOffTrack{ part_index: usize, instruction: Instruction, index: i32, hex: HexIndex },
+    /// Two parts cover the same hex in a way the game doesn't allow. See `find_overlaps`.
+    Overlap(Overlap),
+    /// In a production puzzle, a part covers a hex outside of every chamber.
+    OutsideChamber{ part_index: usize, hex: HexIndex },
+    /// In a production puzzle with isolation, an output is in the same chamber as an input.
+    SharedChamber{ input_index: usize, output_index: usize }
+}
+
+/// Two parts covering the same hex, where `first` comes before `second` in the solution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Overlap{
+    pub first: usize,
+    pub second: usize,
+    pub hex: HexIndex
+}
+
+/// A reason a molecule's atoms and bonds don't fit together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MoleculeError{
+    /// A bond has an end where there's no atom.
+    MissingAtom{ bond: Bond, pos: HexIndex },
+    /// A bond starts and ends at the same atom.
+    SelfBond{ bond: Bond },
+    /// Two bonds connect the same pair of atoms, in either direction or with different types. `bond` is the later of the two in canonical order.
+    DuplicateBond{ bond: Bond },
+    /// A triplex bond connects an atom that isn't fire.
+    NonFireTriplex{ bond: Bond, atom: Atom }
+}
+
+/// Check that every bond of a molecule connects two different atoms, that no two bonds connect the same atoms, and that triplex bonds only connect fire.
+/// Returns every problem found, ordered by the bonds' positions, or an empty list for a well-formed molecule.
+pub fn validate_molecule(molecule: &Molecule) -> Vec<MoleculeError>{
+    let mut bonds: Vec<&Bond> = molecule.bonds.iter().collect();
+    bonds.sort_by_key(|b| (b.start.r, b.start.q, b.end.r, b.end.q, b.ty.id()));
+    let mut errors = Vec::new();
+    let mut connected = HashSet::new();
+    for bond in bonds.into_iter().copied(){
+        if bond.start == bond.end{
+            errors.push(MoleculeError::SelfBond{ bond });
+        }else if !connected.insert(if (bond.start.r, bond.start.q) < (bond.end.r, bond.end.q) { (bond.start, bond.end) } else { (bond.end, bond.start) }){
+            errors.push(MoleculeError::DuplicateBond{ bond });
+        }
+        let ends = if bond.start == bond.end { 1 } else { 2 };
+        for pos in [bond.start, bond.end].into_iter().take(ends){
+            match molecule.atoms.get(&pos){
+                None => errors.push(MoleculeError::MissingAtom{ bond, pos }),
+                Some(&atom) if atom != Atom::Fire && matches!(bond.ty, BondType::Triplex{ .. }) => errors.push(MoleculeError::NonFireTriplex{ bond, atom }),
+                _ => {}
+            }
+        }
+    }
+    errors
+}
+
+/// A reagent or product of a puzzle, by index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MoleculeSlot{
+    Reagent(usize),
+    Product(usize)
+}
+
+/// A reason a puzzle isn't well-formed, such that it couldn't have been made in the puzzle editor or couldn't be solved.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PuzzleError{
+    /// The puzzle has no reagents.
+    NoReagents,
+    /// The puzzle has no products.
+    NoProducts,
+    /// The number of each product required isn't positive.
+    InvalidProductMultiplier(i32),
+    /// A molecule has no atoms.
+    EmptyMolecule(MoleculeSlot),
+    /// A molecule's atoms aren't all connected by bonds.
+    DisconnectedMolecule(MoleculeSlot),
+    /// A molecule has an atom of a type this crate doesn't know.
+    UnknownAtom{ slot: MoleculeSlot, pos: HexIndex },
+    /// A molecule's bonds don't fit its atoms.
+    InvalidMolecule(MoleculeSlot, MoleculeError),
+    /// A repeat atom is in a reagent, there's more than one in a product,
+    /// or it's placed such that the repeated units would overlap or not join up.
+    MisplacedRepeat{ slot: MoleculeSlot, pos: HexIndex },
+    /// Two chambers of a production puzzle cover the same hex.
+    OverlappingChambers{ first: usize, second: usize },
+    /// Permission bits are set that don't correspond to any permission.
+    UnknownPermissions(u64),
+    /// No kind of arm is enabled.
+    NoArms,
+    /// Arms are enabled but can't grab or rotate.
+    NoGrabInstructions
+}
+
+impl Molecule{
+    /// Check that the bonds of this molecule fit its atoms. See `validate_molecule`.
+    pub fn validate(&self) -> Vec<MoleculeError>{
+        validate_molecule(self)
+    }
+}
+
+/// Check that a puzzle has reagents and products that are connected molecules, that repeat atoms are where polymers can use them,
+/// that chambers are separate, and that its permissions allow solving it.
+/// Returns every problem found, or an empty list for a well-formed puzzle.
+pub fn validate_puzzle(puzzle: &Puzzle) -> Vec<PuzzleError>{
+    let mut errors = Vec::new();
+    if puzzle.reagents.is_empty(){
+        errors.push(PuzzleError::NoReagents);
+    }
+    if puzzle.products.is_empty(){
+        errors.push(PuzzleError::NoProducts);
+    }
+    if puzzle.product_multiplier < 1{
+        errors.push(PuzzleError::InvalidProductMultiplier(puzzle.product_multiplier));
+    }
+    let slots = puzzle.reagents.iter().enumerate().map(|(i, m)| (MoleculeSlot::Reagent(i), m))
+        .chain(puzzle.products.iter().enumerate().map(|(i, m)| (MoleculeSlot::Product(i), m)));
+    for (slot, molecule) in slots{
+        if molecule.atoms.is_empty(){
+            errors.push(PuzzleError::EmptyMolecule(slot));
+            continue;
+        }
+        if !is_connected(molecule){
+            errors.push(PuzzleError::DisconnectedMolecule(slot));
+        }
+        errors.extend(molecule.atoms.iter().filter(|(_, a)| matches!(a, Atom::Unknown(_))).map(|(pos, _)| PuzzleError::UnknownAtom{ slot, pos: *pos }));
+        errors.extend(validate_molecule(molecule).into_iter().map(|e| PuzzleError::InvalidMolecule(slot, e)));
+        let repeats: Vec<HexIndex> = molecule.atoms.iter().filter(|(_, a)| **a == Atom::Repeat).map(|(pos, _)| *pos).collect();
+        let misplaced = match (slot, repeats.as_slice()){
+            (_, []) => &[][..],
+            (MoleculeSlot::Reagent(_), _) | (_, [_, _, ..]) => &repeats[..],
+            (MoleculeSlot::Product(_), &[step]) => {
+                // each unit starts at the last one's repeat atom, so it has to join onto an atom at the origin without landing on any others
+                let origin = HexIndex::default();
+                let overlaps = molecule.extended(POLYMER_REPETITIONS).atoms.len() != (molecule.atoms.len() - 1) * POLYMER_REPETITIONS as usize;
+                if step == origin || !molecule.atoms.contains_key(&origin) || overlaps { &repeats[..] } else { &[][..] }
+            }
+        };
+        errors.extend(misplaced.iter().map(|pos| PuzzleError::MisplacedRepeat{ slot, pos: *pos }));
+    }
+    if let Some(info) = &puzzle.production_info{
+        for (second, chamber) in info.chambers.iter().enumerate(){
+            for first in 0..second{
+                if chamber.hexes().any(|h| info.chambers[first].contains(h)){
+                    errors.push(PuzzleError::OverlappingChambers{ first, second });
+                }
+            }
+        }
+    }
+    let named = Permissions::FLAGS.iter().filter(|f| f.is_named()).fold(0, |bits, f| bits | f.value().bits());
+    let unknown = puzzle.permissions.bits() & !named;
+    if unknown != 0{
+        errors.push(PuzzleError::UnknownPermissions(unknown));
+    }
+    if !puzzle.permissions.intersects(Permissions::SIMPLE_ARM | Permissions::MULTI_ARMS | Permissions::PISTON_ARM | Permissions::BERLO){
+        errors.push(PuzzleError::NoArms);
+    }else if !puzzle.permissions.contains(Permissions::GRAB_TURN_INSTRUCTIONS){
+        errors.push(PuzzleError::NoGrabInstructions);
+    }
+    errors
+}
+
+impl Puzzle{
+    /// Check that this puzzle is well-formed. See `validate_puzzle`.
+    pub fn validate(&self) -> Vec<PuzzleError>{
+        validate_puzzle(self)
+    }
+}
+
+/// Whether every atom of a molecule can be reached from every other by following bonds.
+fn is_connected(molecule: &Molecule) -> bool{
+    let Some(&start) = molecule.atoms.keys().next() else { return true };
+    let mut reached = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(pos) = stack.pop(){
+        for bond in &molecule.bonds{
+            let other = if bond.start == pos { bond.end } else if bond.end == pos { bond.start } else { continue };
+            if molecule.atoms.contains_key(&other) && reached.insert(other){
+                stack.push(other);
+            }
+        }
+    }
+    reached.len() == molecule.atoms.len()
+}
+
+/// Check that a solution only uses parts and instructions the puzzle allows, and that its parts are well-formed.
+/// Returns every problem found, or an empty list for a legal solution.
+pub fn validate(solution: &Solution, puzzle: &Puzzle) -> Vec<ValidationError>{
+    let mut errors = Vec::new();
+    let track_hexes: HashSet<HexIndex> = solution.parts.iter().filter(|p| p.ty == PartType::Track).flat_map(|p| p.footprint()).collect();
+    for (part_index, part) in solution.parts.iter().enumerate(){
+        if !part_allowed(&part.ty, puzzle){
+            errors.push(ValidationError::ForbiddenPart{ part_index, ty: part.ty.clone() });
+        }
+        match part.ty{
+            PartType::Input if part.index < 0 || part.index as usize >= puzzle.reagents.len() => {
+                errors.push(ValidationError::InvalidInput{ part_index, index: part.index });
+            }
+            PartType::Output | PartType::PolymerOutput if part.index < 0 || part.index as usize >= puzzle.products.len() => {
+                errors.push(ValidationError::InvalidOutput{ part_index, index: part.index });
+            }
+            PartType::Track => check_track(part_index, part, &mut errors),
+            PartType::Conduit => {
+                if puzzle.production_info.is_none(){
+                    errors.push(ValidationError::ConduitOutsideProduction{ part_index });
+                }
+                if part.conduit_hexes.is_empty(){
+                    errors.push(ValidationError::EmptyConduit{ part_index });
+                }
+            }
+            _ => {}
+        }
+        if part.ty.category() == PartCategory::Arm && part.ty != PartType::Berlo{
+            if !(1..=3).contains(&part.arm_length){
+                errors.push(ValidationError::InvalidArmLength{ part_index, length: part.arm_length });
+            }
+            let mut reported = HashSet::new();
+            for (instruction, _) in &part.instructions{
+                if !instruction_allowed(*instruction, puzzle.permissions) && reported.insert(*instruction){
+                    errors.push(ValidationError::ForbiddenInstruction{ part_index, instruction: *instruction });
+                }
+            }
+        }
+        for &(instruction, index) in &part.instructions{
+            let error = match instruction{
+                _ if part.ty.category() != PartCategory::Arm => ValidationError::InstructionOnNonArm{ part_index, instruction, index },
+                Instruction::Extend | Instruction::Retract if part.ty != PartType::PistonArm => ValidationError::ExtensionOnNonPiston{ part_index, instruction, index },
+                Instruction::Advance | Instruction::Retreat if !track_hexes.contains(&part.pos) => ValidationError::MovementOffTrack{ part_index, instruction, index, hex: part.pos },
+                _ => continue
+            };
+            errors.push(error);
+        }
+    }
+    errors.extend(find_overlaps(solution, puzzle).into_iter().map(ValidationError::Overlap));
+    errors.extend(find_chamber_errors(solution, puzzle));
+    errors
+}
+
+/// Find every part of a solution that the puzzle's permissions don't allow, including conduits outside of production puzzles.
+/// This is the subset of `validate` concerned with which parts may be placed, in the order the parts appear.
+pub fn find_forbidden_parts(solution: &Solution, puzzle: &Puzzle) -> Vec<ValidationError>{
+    solution.parts.iter().enumerate()
+        .filter(|(_, part)| !part_allowed(&part.ty, puzzle))
+        .map(|(part_index, part)| ValidationError::ForbiddenPart{ part_index, ty: part.ty.clone() })
+        .collect()
+}
+
+impl Solution{
+    /// Every part of this solution that the puzzle doesn't allow, as `ValidationError::ForbiddenPart` errors. See `find_forbidden_parts`.
+    pub fn check_permissions(&self, puzzle: &Puzzle) -> Vec<ValidationError>{
+        find_forbidden_parts(self, puzzle)
+    }
+}
+
+/// The flags leaderboards attach to a record of a solution.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SolutionProperties{
+    /// The solution places no track.
+    pub trackless: bool,
+    /// Some parts overlap in a way the game doesn't allow. See `find_overlaps`.
+    pub overlap: bool,
+    /// Some glyph isn't enabled by the puzzle's permissions.
+    pub forbidden_glyphs: bool
+}
+
+/// Work out the leaderboard flags of a solution to a puzzle.
+pub fn solution_properties(solution: &Solution, puzzle: &Puzzle) -> SolutionProperties{
+    SolutionProperties{
+        trackless: !solution.parts.iter().any(|p| p.ty.category() == PartCategory::Track),
+        overlap: !find_overlaps(solution, puzzle).is_empty(),
+        forbidden_glyphs: solution.parts.iter().any(|p| p.ty.category() == PartCategory::Glyph && !part_allowed(&p.ty, puzzle))
+    }
+}
+
+/// Check that every part of a solution to a production puzzle is inside a chamber, and that isolated puzzles keep inputs and outputs apart.
+/// Returns no errors for puzzles that aren't production puzzles.
+pub fn find_chamber_errors(solution: &Solution, puzzle: &Puzzle) -> Vec<ValidationError>{
+    let Some(info) = &puzzle.production_info else { return Vec::new() };
+    let mut errors = Vec::new();
+    let mut chambers: Vec<HashSet<usize>> = Vec::with_capacity(solution.parts.len());
+    for (part_index, part) in solution.parts.iter().enumerate(){
+        let mut covered = HashSet::new();
+        for hex in part.footprint_in(puzzle){
+            match info.chamber_at(hex){
+                Some(chamber) => { covered.insert(chamber); }
+                None => errors.push(ValidationError::OutsideChamber{ part_index, hex })
+            }
+        }
+        chambers.push(covered);
+    }
+    if info.isolation{
+        let is_input = |i: &usize| solution.parts[*i].ty == PartType::Input;
+        let is_output = |i: &usize| matches!(solution.parts[*i].ty, PartType::Output | PartType::PolymerOutput);
+        for output_index in (0..solution.parts.len()).filter(is_output){
+            for input_index in (0..solution.parts.len()).filter(is_input){
+                if !chambers[output_index].is_disjoint(&chambers[input_index]){
+                    errors.push(ValidationError::SharedChamber{ input_index, output_index });
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Find every pair of parts whose footprints share a hex, other than arms placed on track.
+/// Each pair is reported once for every hex they share, in the order the parts appear in the solution.
+pub fn find_overlaps(solution: &Solution, puzzle: &Puzzle) -> Vec<Overlap>{
+    let mut occupied: HashMap<HexIndex, Vec<usize>> = HashMap::new();
+    let mut overlaps = Vec::new();
+    for (second, part) in solution.parts.iter().enumerate(){
+        let hexes: HashSet<HexIndex> = part.footprint_in(puzzle).into_iter().collect();
+        for hex in hexes{
+            let here = occupied.entry(hex).or_default();
+            for &first in here.iter(){
+                if !may_share_hex(&solution.parts[first].ty, &part.ty){
+                    overlaps.push(Overlap{ first, second, hex });
+                }
+            }
+            here.push(second);
+        }
+    }
+    overlaps.sort_by_key(|o| (o.second, o.first, o.hex.r, o.hex.q));
+    overlaps
+}
+
+/// Whether parts of two types may cover the same hex. Only arms may be placed on track.
+fn may_share_hex(a: &PartType, b: &PartType) -> bool{
+    matches!((a.category(), b.category()), (PartCategory::Arm, PartCategory::Track) | (PartCategory::Track, PartCategory::Arm))
+}
+
+/// Check that a track covers some hexes, each adjacent to the one before, without covering any twice except to close a loop.
+fn check_track(part_index: usize, part: &Part, errors: &mut Vec<ValidationError>){
+    let hexes = &part.track_hexes;
+    if hexes.is_empty(){
+        errors.push(ValidationError::EmptyTrack{ part_index });
+    }
+    let closing = if part.is_looped_track() { hexes.len() - 1 } else { hexes.len() };
+    let mut seen = HashSet::new();
+    for (hex_index, hex) in hexes.iter().enumerate(){
+        if !seen.insert(*hex) && hex_index != closing{
+            errors.push(ValidationError::RepeatedTrackHex{ part_index, hex: *hex });
+        }
+        if hex_index > 0{
+            let step = *hex - hexes[hex_index - 1];
+            if step.q.abs().max(step.r.abs()).max(step.s().abs()) != 1{
+                errors.push(ValidationError::DisconnectedTrack{ part_index, hex_index, hex: *hex });
+            }
+        }
+    }
+}
+
+fn part_allowed(ty: &PartType, puzzle: &Puzzle) -> bool{
+    match ty.required_permission(){
+        _ if *ty == PartType::Conduit => puzzle.production_info.is_some(),
+        _ if matches!(ty, PartType::Unknown(_)) => false,
+        Some(needed) => puzzle.permissions.contains(needed),
+        None => true
+    }
+}
+
+fn instruction_allowed(instruction: Instruction, permissions: Permissions) -> bool{
+    let needed = match instruction{
+        Instruction::Grab | Instruction::RotateClockwise | Instruction::RotateAnticlockwise => Permissions::GRAB_TURN_INSTRUCTIONS,
+        Instruction::Drop => Permissions::DROP_INSTRUCTION,
+        Instruction::PivotClockwise | Instruction::PivotAnticlockwise => Permissions::PIVOT_INSTRUCTIONS,
+        Instruction::Reset => Permissions::RESET_INSTRUCTION,
+        Instruction::Repeat => Permissions::REPEAT_INSTRUCTION,
+        Instruction::Extend | Instruction::Retract => Permissions::PISTON_ARM,
+        Instruction::Advance | Instruction::Retreat => Permissions::TRACK,
+        Instruction::Blank | Instruction::PeriodOverride => return true
+    };
+    permissions.contains(needed)
+}
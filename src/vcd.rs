@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+use crate::data::{Instruction, PartCategory, Solution};
+use crate::sim::Sim;
+
+// Value Change Dump output, for viewing simulation timing in waveform viewers.
+
+/// Records per-cycle signals from a simulation as a VCD file, with one time unit per cycle.
+/// Signals are each arm's instruction (as an 8-bit character code, 0 when blank), a pulse for each glyph firing, and a pulse for each product delivered.
+/// Arm instructions are the ones the simulation runs, with reset and repeat expanded, looping every period.
+#[derive(Clone, Debug)]
+pub struct VcdRecorder{
+    out: String,
+    /// Part index, identifier, and last value of each arm's instruction signal.
+    arms: Vec<(usize, String, Option<u8>)>,
+    /// Part index, identifier, last value, and last activation count of each glyph.
+    glyphs: Vec<(usize, String, Option<bool>, u32)>,
+    /// Identifier and last value of each product's delivery signal.
+    products: Vec<(String, Option<bool>)>,
+    /// The number of deliveries already accounted for.
+    deliveries_seen: usize
+}
+
+impl VcdRecorder{
+    /// Start recording a simulation of a solution, before its first step.
+    pub fn new(solution: &Solution, sim: &Sim) -> Self{
+        let mut ids = (0..).map(identifier);
+        let arms: Vec<(usize, String, Option<u8>)> = solution.parts.iter().enumerate()
+            .filter(|(_, p)| p.ty.category() == PartCategory::Arm)
+            .map(|(i, _)| (i, ids.next().unwrap(), None))
+            .collect();
+        let glyphs: Vec<(usize, String, Option<bool>, u32)> = solution.parts.iter().enumerate()
+            .filter(|(_, p)| p.ty.category() == PartCategory::Glyph)
+            .map(|(i, _)| (i, ids.next().unwrap(), None, sim.glyph_activations.get(i).copied().unwrap_or(0)))
+            .collect();
+        let products: Vec<(String, Option<bool>)> = (0..sim.product_count).map(|_| (ids.next().unwrap(), None)).collect();
+
+        let mut out = String::new();
+        out.push_str("$timescale 1 ns $end\n$scope module solution $end\n");
+        for (part_index, id, _) in &arms{
+            let _ = writeln!(out, "$var wire 8 {id} arm{part_index} $end");
+        }
+        for (part_index, id, _, _) in &glyphs{
+            let _ = writeln!(out, "$var wire 1 {id} glyph{part_index} $end");
+        }
+        for (product, (id, _)) in products.iter().enumerate(){
+            let _ = writeln!(out, "$var wire 1 {id} product{product} $end");
+        }
+        out.push_str("$upscope $end\n$enddefinitions $end\n");
+        VcdRecorder{ out, arms, glyphs, products, deliveries_seen: sim.deliveries.len() }
+    }
+
+    /// Record the signals for the cycle that was just simulated. Call this after every step.
+    pub fn record(&mut self, sim: &Sim){
+        let cycle = sim.cycle - 1;
+        let mut changes = String::new();
+
+        let instructions = sim.instructions_on(cycle.max(0));
+        for (part_index, id, last) in &mut self.arms{
+            let instr = instructions.get(*part_index).copied().unwrap_or_default();
+            let value = if instr == Instruction::Blank { 0 } else { instr.id() };
+            if *last != Some(value){
+                let _ = writeln!(changes, "b{value:b} {id}");
+                *last = Some(value);
+            }
+        }
+        for (part_index, id, last, activations) in &mut self.glyphs{
+            let current = sim.glyph_activations.get(*part_index).copied().unwrap_or(0);
+            let fired = current > *activations;
+            *activations = current;
+            write_bit(&mut changes, id, last, fired);
+        }
+        let new_deliveries = &sim.deliveries[self.deliveries_seen..];
+        for (product, (id, last)) in self.products.iter_mut().enumerate(){
+            write_bit(&mut changes, id, last, new_deliveries.iter().any(|d| d.product == product));
+        }
+        self.deliveries_seen = sim.deliveries.len();
+
+        if !changes.is_empty(){
+            let _ = writeln!(self.out, "#{}", cycle.max(0));
+            self.out.push_str(&changes);
+        }
+    }
+
+    /// Finish recording, returning the contents of the VCD file.
+    pub fn finish(mut self, sim: &Sim) -> String{
+        let _ = writeln!(self.out, "#{}", sim.cycle);
+        self.out
+    }
+}
+
+fn write_bit(out: &mut String, id: &str, last: &mut Option<bool>, value: bool){
+    if *last != Some(value){
+        let _ = writeln!(out, "{}{id}", if value { '1' } else { '0' });
+        *last = Some(value);
+    }
+}
+
+/// A short identifier for a VCD signal, using the printable characters from `!` to `~`.
+fn identifier(mut n: usize) -> String{
+    let mut id = String::new();
+    loop{
+        id.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0{
+            return id;
+        }
+        n -= 1;
+    }
+}
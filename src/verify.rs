@@ -0,0 +1,384 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, Mul};
+
+use crate::analysis::steady_state::find_steady_state;
+use crate::data::{Atom, HexIndex, Metrics, Puzzle, Solution};
+use crate::sim::{AreaChange, Delivery, Sim, SimError, SimOptions, SimPartType};
+
+/// The number of cycles a solution may run for before verification gives up on it.
+pub const DEFAULT_CYCLE_LIMIT: i32 = 100_000;
+
+/// Everything recorded while verifying a solution, so that statistics beyond the basic metrics don't need a second simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationResult{
+    /// The metrics computed for the solution.
+    pub metrics: Metrics,
+    /// Community metrics computed from the area the solution covered.
+    pub extended: ExtendedMetrics,
+    /// For each product, the cycle on which its output consumed its last required molecule.
+    pub product_completion_cycles: Vec<i32>,
+    /// Every product molecule consumed by an output, in the order they were consumed.
+    pub deliveries: Vec<Delivery>,
+    /// The area after every cycle on which it changed, in order.
+    pub area_changes: Vec<AreaChange>,
+    /// Atoms destroyed without being output or left on the board at completion, by element.
+    pub waste_atoms: HashMap<Atom, u32>,
+    /// The number of times each part fired, indexed like the solution's parts. Always 0 for non-glyphs.
+    pub glyph_activations: Vec<u32>
+}
+
+/// Simulate a solution to completion, returning its metrics and statistics if it completes within `DEFAULT_CYCLE_LIMIT` cycles.
+pub fn verify(puzzle: &Puzzle, solution: &Solution) -> Result<VerificationResult, SimError>{
+    verify_with_limit(puzzle, solution, DEFAULT_CYCLE_LIMIT)
+}
+
+/// Simulate a solution to completion, returning its metrics and statistics if it completes within `cycle_limit` cycles.
+pub fn verify_with_limit(puzzle: &Puzzle, solution: &Solution, cycle_limit: i32) -> Result<VerificationResult, SimError>{
+    verify_with_options(puzzle, solution, cycle_limit, SimOptions::default())
+}
+
+/// Simulate a solution to completion within `cycle_limit` cycles, with settings that allow some illegal solutions, such as overlapping parts.
+pub fn verify_with_options(puzzle: &Puzzle, solution: &Solution, cycle_limit: i32, options: SimOptions) -> Result<VerificationResult, SimError>{
+    let mut sim = Sim::create_with_options(puzzle, solution, options).map_err(SimError::InvalidSolution)?;
+    let mut product_completion_cycles = vec![None; puzzle.products.len()];
+    while !sim.is_complete(){
+        if sim.cycle >= cycle_limit{
+            return Err(SimError::TimedOut{ cycle_limit });
+        }
+        sim.step()?;
+        for part in &sim.parts{
+            if let SimPartType::Output(_, count) | SimPartType::PolymerOutput(_, count) = part.ty{
+                let completion = &mut product_completion_cycles[part.index];
+                if completion.is_none() && count >= sim.required_outputs{
+                    *completion = Some(sim.cycle);
+                }
+            }
+        }
+    }
+
+    let mut waste_atoms = sim.destroyed_atoms.clone();
+    for molecule in &sim.molecules{
+        for atom in molecule.layout.atoms.values(){
+            *waste_atoms.entry(*atom).or_insert(0) += 1;
+        }
+    }
+
+    Ok(VerificationResult{
+        extended: ExtendedMetrics::from_hexes(sim.swept_hexes.keys()),
+        // production puzzles are scored on instructions instead of area, so they don't record one
+        metrics: Metrics{ cycles: sim.cycle, cost: solution.cost(), area: if puzzle.production_info.is_some() { 0 } else { sim.area() as i32 }, instructions: solution.instructions() },
+        // every product has an output that completed
+        product_completion_cycles: product_completion_cycles.into_iter().map(|c| c.unwrap_or(sim.cycle)).collect(),
+        waste_atoms,
+        glyph_activations: sim.glyph_activations,
+        deliveries: sim.deliveries,
+        area_changes: sim.area_changes
+    })
+}
+
+/// One of the four metrics the game records.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MetricKind{
+    Cycles, Cost, Area, Instructions
+}
+
+impl MetricKind{
+    /// Every metric, in the order the game lists them.
+    pub const ALL: [MetricKind; 4] = [MetricKind::Cycles, MetricKind::Cost, MetricKind::Area, MetricKind::Instructions];
+
+    /// Get this metric's value from a set of metrics.
+    pub fn of(self, metrics: &Metrics) -> i32{
+        match self{
+            MetricKind::Cycles => metrics.cycles,
+            MetricKind::Cost => metrics.cost,
+            MetricKind::Area => metrics.area,
+            MetricKind::Instructions => metrics.instructions
+        }
+    }
+
+    /// The name of this metric, in lowercase.
+    pub fn name(self) -> &'static str{
+        match self{
+            MetricKind::Cycles => "cycles",
+            MetricKind::Cost => "cost",
+            MetricKind::Area => "area",
+            MetricKind::Instructions => "instructions"
+        }
+    }
+}
+
+/// A metric whose value recorded in a solution file disagrees with the one computed by simulating it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MetricMismatch{
+    pub metric: MetricKind,
+    pub recorded: i32,
+    pub computed: i32
+}
+
+/// The metrics of a solution, compared against the ones recorded in its file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetricsReport{
+    /// The metrics computed by simulation.
+    pub computed: Metrics,
+    /// The metrics recorded in the solution file, if it has any.
+    pub recorded: Option<Metrics>,
+    /// Every recorded metric that disagrees with the computed one, in the order the game lists them.
+    pub mismatches: Vec<MetricMismatch>
+}
+
+impl MetricsReport{
+    /// Compare computed metrics against recorded ones.
+    pub fn new(computed: Metrics, recorded: Option<Metrics>) -> MetricsReport{
+        let mismatches = recorded.map_or(Vec::new(), |recorded| {
+            MetricKind::ALL.into_iter()
+                .filter(|metric| metric.of(&recorded) != metric.of(&computed))
+                .map(|metric| MetricMismatch{ metric, recorded: metric.of(&recorded), computed: metric.of(&computed) })
+                .collect()
+        });
+        MetricsReport{ computed, recorded, mismatches }
+    }
+
+    /// Whether the solution records metrics, and all of them are correct.
+    /// Solutions without recorded metrics have no mismatches, but aren't consistent, since there's nothing to trust.
+    pub fn is_consistent(&self) -> bool{
+        self.recorded.is_some() && self.mismatches.is_empty()
+    }
+}
+
+/// Simulate a solution and compare its metrics against the ones recorded in its file.
+pub fn check_metrics(puzzle: &Puzzle, solution: &Solution) -> Result<MetricsReport, SimError>{
+    Ok(MetricsReport::new(verify(puzzle, solution)?.metrics, solution.metrics))
+}
+
+/// Metrics tracked by the community in addition to the game's own.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct ExtendedMetrics{
+    /// The fewest rows of hexes that the solution's area fits in, over every board rotation.
+    pub height: i32,
+    /// The narrowest the solution's area is, over every board rotation, in hex widths.
+    /// Rows are offset by half a hex, so this may be a multiple of 0.5.
+    pub width: f32
+}
+
+impl ExtendedMetrics{
+    /// Measure the height and width of a set of hexes.
+    pub fn from_hexes<'a>(hexes: impl IntoIterator<Item = &'a HexIndex>) -> ExtendedMetrics{
+        let Some(spans) = spans(hexes) else { return ExtendedMetrics::default() };
+        ExtendedMetrics{
+            height: spans.iter().map(|(rows, _)| *rows).min().unwrap_or(0),
+            width: spans.iter().map(|(_, columns)| *columns).min().unwrap_or(0) as f32 / 2.0
+        }
+    }
+}
+
+/// For each of the three row directions, the number of rows a set of hexes covers and how wide it is in half-hexes, or `None` for no hexes.
+pub(crate) fn spans<'a>(hexes: impl IntoIterator<Item = &'a HexIndex>) -> Option<[(i32, i32); 3]>{
+    // for each of the three row directions, the range of rows covered and the range of half-hex columns covered
+    let mut rows = [(i32::MAX, i32::MIN); 3];
+    let mut columns = [(i32::MAX, i32::MIN); 3];
+    for hex in hexes{
+        let (q, r, s) = (hex.q, hex.r, hex.s());
+        for (i, (row, column)) in [(r, q - s), (q, s - r), (s, r - q)].into_iter().enumerate(){
+            rows[i] = (rows[i].0.min(row), rows[i].1.max(row));
+            columns[i] = (columns[i].0.min(column), columns[i].1.max(column));
+        }
+    }
+    if rows[0].0 > rows[0].1{
+        return None;
+    }
+    Some(std::array::from_fn(|i| (rows[i].1 - rows[i].0 + 1, columns[i].1 - columns[i].0 + 2)))
+}
+
+impl Solution{
+    /// Simulate this solution to completion and return the number of cycles it took, ignoring any metrics stored in the file.
+    pub fn compute_cycles(&self, puzzle: &Puzzle) -> Result<i32, SimError>{
+        verify(puzzle, self).map(|result| result.metrics.cycles)
+    }
+}
+
+/// The long-run behaviour of a solution that eventually repeats itself exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Throughput{
+    /// The first cycle of the repeating loop.
+    pub loop_start: i32,
+    /// The number of cycles in one pass through the loop.
+    pub loop_cycles: i32,
+    /// The number of molecules of each product output during one pass through the loop.
+    pub outputs_per_loop: Vec<u64>
+}
+
+impl Throughput{
+    /// The exact rate at which a product is output, as a reduced fraction `(products, cycles)`.
+    pub fn rate(&self, product: usize) -> (u64, u64){
+        let (products, cycles) = (self.outputs_per_loop[product], self.loop_cycles as u64);
+        let divisor = gcd(products, cycles).max(1);
+        (products / divisor, cycles / divisor)
+    }
+
+    /// The exact average number of cycles per molecule of the slowest product, or `None` if some product is never output in the loop.
+    pub fn cycles_per_product(&self) -> Option<Rational>{
+        let slowest = *self.outputs_per_loop.iter().min()?;
+        (slowest > 0).then(|| Rational::new(self.loop_cycles as u64, slowest))
+    }
+}
+
+/// A non-negative fraction, always kept in lowest terms so that equal values compare and hash equal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational{
+    numerator: u64,
+    denominator: u64
+}
+
+impl Rational{
+    /// The fraction `numerator / denominator`, reduced. Panics if `denominator` is 0.
+    pub fn new(numerator: u64, denominator: u64) -> Rational{
+        assert!(denominator != 0, "rational with a denominator of 0");
+        let divisor = gcd(numerator, denominator);
+        Rational{ numerator: numerator / divisor, denominator: denominator / divisor }
+    }
+
+    pub fn numerator(self) -> u64{
+        self.numerator
+    }
+
+    pub fn denominator(self) -> u64{
+        self.denominator
+    }
+
+    /// The nearest float to this fraction, for display or approximate comparisons.
+    pub fn to_f64(self) -> f64{
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The sum of two fractions, or `None` if it doesn't fit once reduced.
+    pub fn checked_add(self, rhs: Rational) -> Option<Rational>{
+        let divisor = gcd(self.denominator, rhs.denominator);
+        let (left, right) = (u128::from(self.numerator) * u128::from(rhs.denominator / divisor), u128::from(rhs.numerator) * u128::from(self.denominator / divisor));
+        Rational::reduced(left.checked_add(right)?, u128::from(self.denominator / divisor) * u128::from(rhs.denominator))
+    }
+
+    /// The product of two fractions, or `None` if it doesn't fit once reduced.
+    pub fn checked_mul(self, rhs: Rational) -> Option<Rational>{
+        Rational::reduced(u128::from(self.numerator) * u128::from(rhs.numerator), u128::from(self.denominator) * u128::from(rhs.denominator))
+    }
+
+    /// A fraction computed with wider integers, reduced and then narrowed if it fits.
+    fn reduced(numerator: u128, denominator: u128) -> Option<Rational>{
+        let divisor = gcd_wide(numerator, denominator);
+        Some(Rational{ numerator: u64::try_from(numerator / divisor).ok()?, denominator: u64::try_from(denominator / divisor).ok()? })
+    }
+}
+
+impl Ord for Rational{
+    fn cmp(&self, other: &Rational) -> Ordering{
+        (self.numerator as u128 * other.denominator as u128).cmp(&(other.numerator as u128 * self.denominator as u128))
+    }
+}
+
+impl PartialOrd for Rational{
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering>{
+        Some(self.cmp(other))
+    }
+}
+
+impl From<u64> for Rational{
+    fn from(value: u64) -> Rational{
+        Rational{ numerator: value, denominator: 1 }
+    }
+}
+
+impl Add for Rational{
+    type Output = Rational;
+    /// Panics if the sum doesn't fit. See `checked_add`.
+    fn add(self, rhs: Rational) -> Rational{
+        self.checked_add(rhs).expect("rational addition overflowed")
+    }
+}
+
+impl Mul for Rational{
+    type Output = Rational;
+    /// Panics if the product doesn't fit. See `checked_mul`.
+    fn mul(self, rhs: Rational) -> Rational{
+        self.checked_mul(rhs).expect("rational multiplication overflowed")
+    }
+}
+
+impl Display for Rational{
+    /// Written as `numerator/denominator`, or just the numerator for whole numbers.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        if self.denominator == 1{
+            write!(f, "{}", self.numerator)
+        }else{
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64{
+    gcd_wide(a.into(), b.into()) as u64
+}
+
+fn gcd_wide(a: u128, b: u128) -> u128{
+    if b == 0 { a } else { gcd_wide(b, a % b) }
+}
+
+/// Simulate a solution past completion until it enters a loop, and measure how fast it outputs products there. See `find_steady_state`.
+/// Solutions that haven't repeated within `cycle_limit` cycles, such as ones whose molecules keep growing, time out.
+pub fn throughput(puzzle: &Puzzle, solution: &Solution, cycle_limit: i32, options: SimOptions) -> Result<Throughput, SimError>{
+    find_steady_state(puzzle, solution, cycle_limit, options).map(|steady| steady.throughput)
+}
+
+impl VerificationResult{
+    /// The cycles on which molecules of the given product were consumed, in order.
+    pub fn delivery_cycles(&self, product: usize) -> impl Iterator<Item = i32> + '_{
+        self.deliveries.iter().filter(move |d| d.product == product).map(|d| d.cycle)
+    }
+
+    /// The area after the given cycle.
+    pub fn area_at(&self, cycle: i32) -> usize{
+        self.area_changes.iter().take_while(|c| c.cycle <= cycle).last().map_or(0, |c| c.area)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::builder::{PuzzleBuilder, SolutionBuilder};
+    use crate::data::{Atom, HexIndex, Molecule};
+
+    #[test]
+    fn rationals_stay_reduced(){
+        assert_eq!(Rational::new(6, 4), Rational::new(3, 2));
+        assert_eq!(Rational::new(1, 6) + Rational::new(1, 3), Rational::new(1, 2));
+        assert_eq!(Rational::new(2, 3) * Rational::new(9, 4), Rational::new(3, 2));
+        assert_eq!(Rational::new(3, 2).to_string(), "3/2");
+        assert!(Rational::new(2, 3) < Rational::new(3, 4));
+    }
+
+    #[test]
+    fn rational_overflow_is_reported(){
+        let max = Rational::from(u64::MAX);
+        assert_eq!(max.checked_mul(Rational::from(2)), None);
+        assert_eq!(max.checked_add(Rational::from(1)), None);
+        assert_eq!(Rational::new(1, u64::MAX).checked_add(Rational::new(1, u64::MAX - 1)), None);
+        // results that fit once reduced are found even if the unreduced fraction doesn't
+        assert_eq!(max.checked_mul(Rational::new(3, u64::MAX)), Some(Rational::from(3)));
+        assert_eq!(Rational::new(u64::MAX, 2).checked_add(Rational::new(1, 2)), Some(Rational::from(u64::MAX / 2 + 1)));
+    }
+
+    #[test]
+    fn compute_cycles_matches_verification(){
+        let salt = Molecule::builder().atom(0, 0, Atom::Salt).build().unwrap();
+        let puzzle = PuzzleBuilder::new("puzzle").reagent(salt.clone()).product(salt).build();
+        let solution = SolutionBuilder::new("solution", "puzzle")
+            .input(0, HexIndex{ q: 1, r: 0 }, 0)
+            .output(0, HexIndex{ q: -1, r: 0 }, 0)
+            .arm(HexIndex::default(), 0, 1)
+            .instructions(0, "GRRRgX")
+            .build().unwrap();
+        assert_eq!(solution.compute_cycles(&puzzle), Ok(45));
+        assert_eq!(solution.compute_cycles(&puzzle), verify(&puzzle, &solution).map(|r| r.metrics.cycles));
+    }
+}
@@ -0,0 +1,48 @@
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+use crate::data::{Puzzle, Solution};
+use crate::json::{from_json, to_json};
+use crate::parse::{parse_puzzle, parse_solution};
+use crate::validate::validate;
+use crate::verify::verify;
+
+// Bindings for JavaScript, so that web tools can check solutions without a server.
+// Puzzles and solutions cross the boundary as JSON, in the same format as `json::to_json`.
+
+/// Parse a puzzle file into JSON.
+#[wasm_bindgen(js_name = parsePuzzle)]
+pub fn parse_puzzle_json(data: &[u8]) -> Result<String, JsError>{
+    Ok(to_json(&parse_puzzle(data).map_err(|e| JsError::new(&e.to_string()))?))
+}
+
+/// Parse a solution file into JSON.
+#[wasm_bindgen(js_name = parseSolution)]
+pub fn parse_solution_json(data: &[u8]) -> Result<String, JsError>{
+    Ok(to_json(&parse_solution(data).map_err(|e| JsError::new(&e.to_string()))?))
+}
+
+/// Check a solution against the rules of its puzzle without simulating it, both given as JSON.
+/// Returns a description of each problem found, which is empty if the solution is legal.
+#[wasm_bindgen(js_name = validate)]
+pub fn validate_json(puzzle: &str, solution: &str) -> Result<Vec<String>, JsError>{
+    let (puzzle, solution) = decode(puzzle, solution)?;
+    Ok(validate(&solution, &puzzle).iter().map(|e| format!("{e:?}")).collect())
+}
+
+/// Simulate a solution to completion, both given as JSON.
+/// Returns JSON containing its `metrics` and `extended` metrics, or fails with why it didn't complete.
+#[wasm_bindgen(js_name = simulate)]
+pub fn simulate_json(puzzle: &str, solution: &str) -> Result<String, JsError>{
+    let (puzzle, solution) = decode(puzzle, solution)?;
+    let result = verify(&puzzle, &solution).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(json!({
+        "metrics": result.metrics,
+        "extended": { "height": result.extended.height, "width": result.extended.width },
+        "productCompletionCycles": result.product_completion_cycles
+    }).to_string())
+}
+
+fn decode(puzzle: &str, solution: &str) -> Result<(Puzzle, Solution), JsError>{
+    Ok((from_json(puzzle).map_err(JsError::new)?, from_json(solution).map_err(JsError::new)?))
+}